@@ -6,6 +6,7 @@ pub fn criterion_benchmark(c: &mut Criterion) {
     let position = Position::from_fen("1k1r3r/pppqb1pp/1nn1p3/3bPp2/1P1PN3/P2BBN2/5PPP/2RQ1RK1 w - f6 0 15");
     
     c.bench_function("position_eval", |b| b.iter(|| position.evaluate()));
+    c.bench_function("legal_moves", |b| b.iter(|| position.legal_moves()));
 
     //load maps first
     //load_maps();
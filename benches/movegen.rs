@@ -0,0 +1,31 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use siegfried::{position::Position, maps::load_maps};
+
+//a spread of opening, middlegame and endgame positions for the move generator
+const FENS: [&str; 6] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "1k1r3r/pppqb1pp/1nn1p3/3bPp2/1P1PN3/P2BBN2/5PPP/2RQ1RK1 w - f6 0 15",
+    "r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N1PN2/PP3PPP/R2QKB1R w KQ - 0 8",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    "8/8/4k3/3p4/3P1K2/8/5R2/8 b - - 0 60",
+];
+
+pub fn criterion_benchmark(c: &mut Criterion) {
+    //build the magic and leaper tables before timing so the first sample doesn't pay for them
+    load_maps();
+
+    let positions: Vec<Position> = FENS.iter().map(|fen| Position::from_fen(fen)).collect();
+    c.bench_function("legal_moves_suite", |b| b.iter(|| {
+        positions.iter().map(|position| position.legal_moves().len()).sum::<usize>()
+    }));
+
+    let start = Position::new_game();
+    let mut group = c.benchmark_group("perft");
+    group.sample_size(10);
+    group.bench_function("perft_5_start", |b| b.iter(|| start.perft(5)));
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -9,6 +9,10 @@ pub const WHITE_QUEENSIDE_CASTLE: Bitboard = 0xE;
 pub const BLACK_KINGSIDE_CASTLE: Bitboard = 0x6000000000000000;
 pub const BLACK_QUEENSIDE_CASTLE: Bitboard = 0xE00000000000000;
 
+//squares the king crosses when castling queenside, b1/b8 may be attacked
+pub const WHITE_QUEENSIDE_CASTLE_PATH: Bitboard = 0xC;
+pub const BLACK_QUEENSIDE_CASTLE_PATH: Bitboard = 0xC00000000000000;
+
 
 //FILES - or - COLUMNS
 pub const FILE_ABB: Bitboard = 0x0101010101010101;
@@ -20,6 +24,8 @@ pub const FILE_FBB: Bitboard = FILE_ABB << 5;
 pub const FILE_GBB: Bitboard = FILE_ABB << 6;
 pub const FILE_HBB: Bitboard = FILE_ABB << 7;
 
+pub const FILES: [Bitboard; 8] = [FILE_ABB, FILE_BBB, FILE_CBB, FILE_DBB, FILE_EBB, FILE_FBB, FILE_GBB, FILE_HBB];
+
 pub const NOT_FILE_ABB: Bitboard = FILE_BBB|FILE_CBB|FILE_DBB|FILE_EBB|FILE_FBB|FILE_GBB|FILE_HBB;
 pub const NOT_FILE_HBB: Bitboard = FILE_ABB|FILE_BBB|FILE_CBB|FILE_DBB|FILE_EBB|FILE_FBB|FILE_GBB;
 
@@ -45,9 +51,36 @@ pub trait BitboardMethods {
     fn unset_bit(&self, square: Square) -> Bitboard;
     fn pop_lsb(&mut self) -> Bitboard;
     fn to_square(&self) -> Square;
+    #[deprecated(note = "allocates, iterate with squares() instead")]
     fn get_squares(&self) -> Vec<Square>;
+    fn squares(&self) -> BitIter;
+}
+
+//walks the set squares from a1 upwards by popping the lowest bit, no allocation
+#[derive(Copy)]
+#[derive(Clone)]
+pub struct BitIter(pub Bitboard);
+
+impl Iterator for BitIter{
+    type Item = Square;
+
+    fn next(&mut self) -> Option<Square> {
+        if self.0 == 0 {
+            return None;
+        }
+        let square = self.0.trailing_zeros() as Square;
+        self.0 &= self.0 - 1;
+        return Some(square);
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let count = self.0.count_ones() as usize;
+        return (count, Some(count));
+    }
 }
 
+impl ExactSizeIterator for BitIter{}
+
 pub trait BitboardConstants {
     const EMPTY: Bitboard;
     const FULL: Bitboard;
@@ -86,7 +119,11 @@ impl BitboardMethods for Bitboard{
         }
         return squares;
     }
-    
+
+    fn squares(&self) -> BitIter {
+        return BitIter(*self);
+    }
+
 }
 
 
@@ -0,0 +1,150 @@
+use crate::position::Move;
+
+const DEFAULT_EVAL_CACHE_SIZE: usize = 1 << 16;
+
+#[derive(Copy)]
+#[derive(Clone)]
+struct EvalCacheEntry{
+    key: u64,
+    score: f32,
+}
+
+//fixed size, always-replace cache of static evaluations keyed by zobrist hash
+pub struct EvalCache{
+    entries: Vec<Option<EvalCacheEntry>>,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl EvalCache{
+    pub fn new() -> EvalCache{
+        return EvalCache::with_size(DEFAULT_EVAL_CACHE_SIZE);
+    }
+
+    //size is rounded up to a power of two so the index is a mask
+    pub fn with_size(size: usize) -> EvalCache{
+        return EvalCache{
+            entries: vec![None; size.max(1).next_power_of_two()],
+            hits: 0,
+            misses: 0,
+        };
+    }
+
+    fn index(&self, key: u64) -> usize{
+        return key as usize & (self.entries.len() - 1);
+    }
+
+    pub fn probe(&mut self, key: u64) -> Option<f32>{
+        let index = self.index(key);
+        match self.entries[index]{
+            Some(entry) if entry.key == key => {
+                self.hits += 1;
+                Some(entry.score)
+            }
+            _ => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    pub fn store(&mut self, key: u64, score: f32){
+        let index = self.index(key);
+        self.entries[index] = Some(EvalCacheEntry{ key, score });
+    }
+
+    pub fn hit_rate(&self) -> f32{
+        let probes = self.hits + self.misses;
+        if probes == 0{
+            return 0.0;
+        }
+        return self.hits as f32 / probes as f32;
+    }
+
+    pub fn clear(&mut self){
+        self.entries.iter_mut().for_each(|entry| *entry = None);
+        self.hits = 0;
+        self.misses = 0;
+    }
+}
+
+const DEFAULT_TRANSPOSITION_TABLE_SIZE: usize = 1 << 16;
+
+//what a stored score says about the real one
+#[derive(PartialEq, Eq)]
+#[derive(Debug)]
+#[derive(Copy)]
+#[derive(Clone)]
+pub enum Bound{
+    Exact,
+    //the search failed high, the score is at least this
+    Lower,
+    //the search failed low, the score is at most this
+    Upper,
+}
+
+#[derive(Copy)]
+#[derive(Clone)]
+pub struct TranspositionEntry{
+    pub key: u64,
+    pub depth: u8,
+    pub score: f32,
+    pub bound: Bound,
+    pub best_move: Option<Move>,
+}
+
+//fixed size, always-replace table of search results keyed by zobrist hash
+pub struct TranspositionTable{
+    entries: Vec<Option<TranspositionEntry>>,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl TranspositionTable{
+    pub fn new() -> TranspositionTable{
+        return TranspositionTable::with_size(DEFAULT_TRANSPOSITION_TABLE_SIZE);
+    }
+
+    //size is rounded up to a power of two so the index is a mask
+    pub fn with_size(size: usize) -> TranspositionTable{
+        return TranspositionTable{
+            entries: vec![None; size.max(1).next_power_of_two()],
+            hits: 0,
+            misses: 0,
+        };
+    }
+
+    fn index(&self, key: u64) -> usize{
+        return key as usize & (self.entries.len() - 1);
+    }
+
+    pub fn probe(&mut self, key: u64) -> Option<TranspositionEntry>{
+        let entry = self.get(key);
+        if entry.is_some(){
+            self.hits += 1;
+        }
+        else{
+            self.misses += 1;
+        }
+        return entry;
+    }
+
+    //look up without counting towards the hit rate
+    pub fn get(&self, key: u64) -> Option<TranspositionEntry>{
+        return match self.entries[self.index(key)]{
+            Some(entry) if entry.key == key => Some(entry),
+            _ => None,
+        };
+    }
+
+    pub fn store(&mut self, entry: TranspositionEntry){
+        let index = self.index(entry.key);
+        self.entries[index] = Some(entry);
+    }
+
+    pub fn clear(&mut self){
+        self.entries.iter_mut().for_each(|entry| *entry = None);
+        self.hits = 0;
+        self.misses = 0;
+    }
+}
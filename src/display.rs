@@ -1,6 +1,6 @@
-use std::ops::{Shr};
+use std::{io::{self, Write}, ops::{Shr}};
 
-use crate::{bitboard::*, position::{Position, SidePiecesMethods}, types::*};
+use crate::{bitboard::*, position::Position, types::*};
 
 /* 
 const UNICODE_WHITE_PAWN: char = '♙';
@@ -57,7 +57,7 @@ fn get_rank_string(rank: u8) -> String{
 }
 
 //Lazy but good enough way to print a bitboard
-pub fn print_bitboard(board: Bitboard){
+pub fn write_bitboard<W: Write>(board: Bitboard, w: &mut W) -> io::Result<()>{
     
     let rank8 = (board&RANK_8BB).shr(8*7) as u8;
     let rank7 = (board&RANK_7BB).shr(8*6) as u8;
@@ -68,18 +68,22 @@ pub fn print_bitboard(board: Bitboard){
     let rank2 = (board&RANK_2BB).shr(8*1) as u8;
     let rank1 = (board&RANK_1BB) as u8;
 
-    println!("8   {}", get_rank_string(rank8));
-    println!("7   {}", get_rank_string(rank7));
-    println!("6   {}", get_rank_string(rank6));
-    println!("5   {}", get_rank_string(rank5));
-    println!("4   {}", get_rank_string(rank4));
-    println!("3   {}", get_rank_string(rank3));
-    println!("2   {}", get_rank_string(rank2));
-    println!("1   {}", get_rank_string(rank1));
-    println!("\n     A  B  C  D  E  F  G  H");
+    writeln!(w, "8   {}", get_rank_string(rank8))?;
+    writeln!(w, "7   {}", get_rank_string(rank7))?;
+    writeln!(w, "6   {}", get_rank_string(rank6))?;
+    writeln!(w, "5   {}", get_rank_string(rank5))?;
+    writeln!(w, "4   {}", get_rank_string(rank4))?;
+    writeln!(w, "3   {}", get_rank_string(rank3))?;
+    writeln!(w, "2   {}", get_rank_string(rank2))?;
+    writeln!(w, "1   {}", get_rank_string(rank1))?;
+    writeln!(w, "\n     A  B  C  D  E  F  G  H")
 }
 
-pub fn print_bitboard_alt(board: Bitboard){
+pub fn print_bitboard(board: Bitboard){
+    write_bitboard(board, &mut io::stdout()).unwrap();
+}
+
+pub fn write_bitboard_alt<W: Write>(board: Bitboard, w: &mut W) -> io::Result<()>{
     let mut board_string: String = String::from("");
 
     let ranks = [RANK_1BB, RANK_2BB, RANK_3BB, RANK_4BB, RANK_5BB, RANK_6BB, RANK_7BB, RANK_8BB];
@@ -98,71 +102,57 @@ pub fn print_bitboard_alt(board: Bitboard){
         board_string += "\n";
     }
 
-    println!("{}", board_string);
+    writeln!(w, "{}", board_string)
+}
+
+pub fn print_bitboard_alt(board: Bitboard){
+    write_bitboard_alt(board, &mut io::stdout()).unwrap();
 }
 
+fn get_piece_glyph(piece: Piece, side: Side) -> char{
+    let glyphs = if side == Side::WHITE {
+        [UNICODE_WHITE_PAWN, UNICODE_WHITE_KNIGHT, UNICODE_WHITE_BISHOP, UNICODE_WHITE_ROOK, UNICODE_WHITE_QUEEN, UNICODE_WHITE_KING]
+    } else {
+        [UNICODE_BLACK_PAWN, UNICODE_BLACK_KNIGHT, UNICODE_BLACK_BISHOP, UNICODE_BLACK_ROOK, UNICODE_BLACK_QUEEN, UNICODE_BLACK_KING]
+    };
+    return glyphs[piece];
+}
 
-pub fn print_position(position: &Position){
-    println!("");
-    for rank in (1..9).rev(){
-        println!();
-        print!("{}   ", rank);
-        for file in 1..9{
-            //match rank and file to square
-            let square: u8 = (rank-1)*8+file-1;
-            let square_bb = square.to_bitboard();
-            let side = if square_bb & position.pieces[Side::WHITE.0].occupancy() != 0 {Side::WHITE} else {Side::BLACK};
-            let piece_type = position.pieces[side.0].get_piece_type_at_square(square_bb);
-            if piece_type.is_none(){
-                print!(".  ");
-            }else{
-                let piece_type = piece_type.unwrap();
-
-                if piece_type == PAWN{
-                    if side == Side::WHITE{
-                        print!("{}  ", UNICODE_WHITE_PAWN);
-                    }else{
-                        print!("{}  ", UNICODE_BLACK_PAWN);
-                    }
-                }
-                else if piece_type == KNIGHT{
-                    if side == Side::WHITE{
-                        print!("{}  ", UNICODE_WHITE_KNIGHT);
-                    }else{
-                        print!("{}  ", UNICODE_BLACK_KNIGHT);
-                    }
-                }
-                else if piece_type == BISHOP{
-                    if side == Side::WHITE{
-                        print!("{}  ", UNICODE_WHITE_BISHOP);
-                    }else{
-                        print!("{}  ", UNICODE_BLACK_BISHOP);
-                    }
-                }
-                else if piece_type == ROOK{
-                    if side == Side::WHITE{
-                        print!("{}  ", UNICODE_WHITE_ROOK);
-                    }else{
-                        print!("{}  ", UNICODE_BLACK_ROOK);
-                    }
-                }
-                else if piece_type == QUEEN{
-                    if side == Side::WHITE{
-                        print!("{}  ", UNICODE_WHITE_QUEEN);
-                    }else{
-                        print!("{}  ", UNICODE_BLACK_QUEEN);
-                    }
-                }
-                else if piece_type == KING{
-                    if side == Side::WHITE{
-                        print!("{}  ", UNICODE_WHITE_KING);
-                    }else{
-                        print!("{}  ", UNICODE_BLACK_KING);
-                    }
-                }
+//board diagram with rank 8 on top, rank numbers on the left and file letters underneath
+pub fn position_to_ascii(position: &Position) -> String{
+    return position_to_ascii_oriented(position, Side::WHITE);
+}
+
+//board diagram as seen by `perspective`, black gets rank 1 on top and the files running h to a
+pub fn position_to_ascii_oriented(position: &Position, perspective: Side) -> String{
+    let mut board_string = String::new();
+
+    let ranks: Vec<usize> = if perspective == Side::WHITE { (0..8).rev().collect() } else { (0..8).collect() };
+    let files: Vec<usize> = if perspective == Side::WHITE { (0..8).collect() } else { (0..8).rev().collect() };
+
+    for &rank in ranks.iter(){
+        board_string += format!("{}   ", rank + 1).as_str();
+        for &file in files.iter(){
+            match position.piece_at(Square::from_rank_and_file(rank, file)){
+                Some((piece, side)) => board_string.push(get_piece_glyph(piece, side)),
+                None => board_string.push('.'),
             }
+            board_string += "  ";
         }
+        board_string += "\n";
+    }
+    board_string += "\n   ";
+    for &file in files.iter(){
+        board_string += format!(" {} ", (b'A' + file as u8) as char).as_str();
     }
-    println!("\n\n    A  B  C  D  E  F  G  H");
-    println!("")
-}
\ No newline at end of file
+
+    return board_string;
+}
+
+pub fn write_position<W: Write>(position: &Position, w: &mut W) -> io::Result<()>{
+    writeln!(w, "{}", position)
+}
+
+pub fn print_position(position: &Position){
+    write_position(position, &mut io::stdout()).unwrap();
+}
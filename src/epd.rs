@@ -0,0 +1,123 @@
+use std::fmt::{Display, Formatter, Result};
+use crate::position::{Position, Move, FenError};
+
+//one line of a test suite like WAC: a position without clocks followed by `opcode operands;` operations
+pub struct Epd{
+    pub position: Position,
+    pub id: Option<String>,
+    pub best_moves: Vec<Move>,
+    pub avoid_moves: Vec<Move>,
+}
+
+#[derive(PartialEq)]
+#[derive(Debug)]
+#[derive(Clone)]
+pub enum EpdError{
+    WrongFieldCount,
+    BadPosition(FenError),
+    UnterminatedString,
+    BadMove(String),
+}
+
+impl Display for EpdError{
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self{
+            EpdError::WrongFieldCount => write!(f, "EPD must start with 4 space separated position fields"),
+            EpdError::BadPosition(error) => write!(f, "Invalid position: {}", error),
+            EpdError::UnterminatedString => write!(f, "Unterminated string operand"),
+            EpdError::BadMove(san) => write!(f, "'{}' is not a legal move in this position", san),
+        }
+    }
+}
+
+pub fn parse_epd(line: &str) -> std::result::Result<Epd, EpdError>{
+    let fields: Vec<&str> = line.split_whitespace().take(4).collect();
+    if fields.len() != 4{
+        return Err(EpdError::WrongFieldCount);
+    }
+    let position = Position::try_from_fen(&fields.join(" ")).map_err(EpdError::BadPosition)?;
+
+    let mut epd = Epd{
+        position,
+        id: None,
+        best_moves: Vec::new(),
+        avoid_moves: Vec::new(),
+    };
+
+    //the operations start after the fourth field
+    let mut rest = line.trim_start();
+    for _ in 0..4{
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        rest = rest[end..].trim_start();
+    }
+
+    for (opcode, operands) in split_operations(rest)?{
+        match opcode.as_str(){
+            "id" => epd.id = operands.into_iter().next(),
+            "bm" | "am" => {
+                let mut moves = Vec::new();
+                for san in operands{
+                    match Move::from_san(&san, &epd.position){
+                        Some(m) => moves.push(m),
+                        None => return Err(EpdError::BadMove(san)),
+                    }
+                }
+                if opcode == "bm" { epd.best_moves = moves; } else { epd.avoid_moves = moves; }
+            },
+            //other opcodes (c0, acd, hmvc...) are not needed to run a suite
+            _ => {}
+        }
+    }
+
+    return Ok(epd);
+}
+
+//split `opcode operand...;` operations, a quoted operand may hold spaces and semicolons
+fn split_operations(s: &str) -> std::result::Result<Vec<(String, Vec<String>)>, EpdError>{
+    let mut operations = Vec::new();
+    let mut tokens: Vec<String> = Vec::new();
+    let mut token = String::new();
+    let mut chars = s.chars();
+
+    while let Some(c) = chars.next(){
+        match c{
+            '"' => {
+                let mut quoted = String::new();
+                loop{
+                    match chars.next(){
+                        Some('"') => break,
+                        Some(c) => quoted.push(c),
+                        None => return Err(EpdError::UnterminatedString),
+                    }
+                }
+                tokens.push(quoted);
+            },
+            ';' => {
+                if !token.is_empty(){
+                    tokens.push(std::mem::take(&mut token));
+                }
+                if !tokens.is_empty(){
+                    let opcode = tokens.remove(0);
+                    operations.push((opcode, std::mem::take(&mut tokens)));
+                }
+            },
+            c if c.is_whitespace() => {
+                if !token.is_empty(){
+                    tokens.push(std::mem::take(&mut token));
+                }
+            },
+            c => token.push(c),
+        }
+    }
+
+    //the last operation may leave off its semicolon
+    if !token.is_empty(){
+        tokens.push(token);
+    }
+    if !tokens.is_empty(){
+        let opcode = tokens.remove(0);
+        operations.push((opcode, tokens));
+    }
+
+    return Ok(operations);
+}
@@ -1,37 +1,147 @@
-use crate::{position::{Position, Move}, tree::{PositionTree, ExpandStyle}, types::{Side, GameState, GameStateConstants, SideConstants}, display::print_position};
+use crate::{position::{Position, Move, MoveError, ZobristMoveStack, MaterialRule}, tree::{PositionTree, ExpandStyle}, search::search_timed_with_history, types::{Side, SideConstants, GameResult, Termination, DrawReason}};
+
+//PGN tag values, "?" marks an unknown value
+pub struct PgnHeaders{
+    pub event: String,
+    pub site: String,
+    pub date: String,
+    pub round: String,
+    pub white: String,
+    pub black: String,
+}
+
+impl PgnHeaders{
+    pub fn new() -> PgnHeaders{
+        PgnHeaders{
+            event: "?".to_string(),
+            site: "?".to_string(),
+            date: "????.??.??".to_string(),
+            round: "?".to_string(),
+            white: "?".to_string(),
+            black: "?".to_string(),
+        }
+    }
+}
 
 pub struct Game{
     position: Position,
     player_side: Option<Side>,
     move_history: Vec<String>,
+    played_moves: Vec<Move>,
+    history: ZobristMoveStack,
+    start_position: Position,
+    starting_side: Side,
     max_depth: u8,
+    time_per_move: Option<u64>,
+    opening_plies: usize,
+    opening_depth: u8,
+    result: Option<GameResult>,
+    termination: Option<Termination>,
+}
+
+impl Default for Game{
+    fn default() -> Game{
+        return Game::new();
+    }
 }
 
 impl Game{
     pub fn new() -> Game{
-        let position = Position::new_game();
-        Game{
-            position,
-            player_side: None,
-            move_history: Vec::new(),
-            max_depth: 20,
-        }
+        return Game::from_position(Position::new_game());
     }
 
     pub fn from_fen(fen: &str) -> Game{
-        let position = Position::from_fen(fen);
-        Game{
+        return Game::from_position(Position::from_fen(fen));
+    }
+
+    //a starting position that is already mate or drawn gives a finished game
+    fn from_position(position: Position) -> Game{
+        let mut game = Game{
             position,
             player_side: None,
             move_history: Vec::new(),
+            played_moves: Vec::new(),
+            history: ZobristMoveStack::new(),
+            start_position: position,
+            starting_side: position.side_to_move,
             max_depth: 20,
+            time_per_move: None,
+            opening_plies: 0,
+            opening_depth: 1,
+            result: None,
+            termination: None,
+        };
+        game.update_result();
+        game
+    }
+
+    //import a game, moves are played up to the first illegal one and all of them are kept for validate_history
+    pub fn from_moves(fen: &str, moves: Vec<Move>) -> Game{
+        let mut game = Game::from_fen(fen);
+        let mut legal = true;
+        for m in moves{
+            if legal{
+                match game.position.find_legal_move(&m){
+                    Some(legal_move) => game.record_move(legal_move),
+                    None => legal = false,
+                }
+            }
+            if !legal{
+                game.played_moves.push(m);
+            }
         }
+        game.update_result();
+        game
+    }
+
+    //replay the played moves from the starting position, Err holds the index and move of the first illegal one
+    pub fn validate_history(&self) -> Result<(), (usize, Move)>{
+        let mut position = self.start_position;
+        for (index, m) in self.played_moves.iter().enumerate(){
+            let next = position.find_legal_move(m).and_then(|legal_move| position.make_move(legal_move));
+            match next{
+                Some(next_position) => position = next_position,
+                None => return Err((index, *m)),
+            }
+        }
+        Ok(())
     }
 
     pub fn set_max_depth(&mut self, depth: u8){
         self.max_depth = depth;
     }
 
+    //milliseconds the computer may think per move with iterative deepening, None searches to the fixed depth
+    pub fn set_time_per_move(&mut self, millis: Option<u64>){
+        self.time_per_move = millis;
+    }
+
+    pub fn get_time_per_move(&self) -> Option<u64>{
+        self.time_per_move
+    }
+
+    //search the first `plies` half moves at a fixed shallow depth before switching to max_depth
+    pub fn set_opening(&mut self, plies: usize, depth: u8){
+        self.opening_plies = plies;
+        self.opening_depth = depth;
+    }
+
+    pub fn get_opening_plies(&self) -> usize{
+        self.opening_plies
+    }
+
+    pub fn get_opening_depth(&self) -> u8{
+        self.opening_depth
+    }
+
+    //depth the computer searches the current move to
+    pub fn get_search_depth(&self) -> u8{
+        if self.move_history.len() < self.opening_plies{
+            return self.opening_depth;
+        }
+        self.max_depth
+    }
+
     pub fn clear(&self){
         print!("\x1B[2J\x1B[1;1H");
     }
@@ -48,31 +158,96 @@ impl Game{
         &self.move_history
     }
 
-    fn make_move(&mut self, m: Move){
-        println!("Move played: {} ", m);
+    pub fn get_history(&self) -> &ZobristMoveStack{
+        &self.history
+    }
+
+    pub fn get_played_moves(&self) -> &Vec<Move>{
+        &self.played_moves
+    }
+
+    fn record_move(&mut self, m: Move){
         let new_position = self.position.make_move(m);
 
         if new_position.is_some(){
             let formatted_move = self.position.get_formatted_move(m);
-            self.position = new_position.unwrap();
+            let new_position = new_position.unwrap();
+            self.history.record(&self.position, &new_position);
+            self.position = new_position;
             self.move_history.push(formatted_move);
+            self.played_moves.push(m);
         }
         else{
             panic!("Invalid move! {}", m);
         }
-        print_position(&self.position);
+    }
+
+    //play a SAN or long algebraic move for the side to move without printing anything, the result is updated
+    //so a mate or draw ends the game and later moves are refused
+    pub fn play_move(&mut self, s: &str) -> Result<(), MoveError>{
+        if self.result.is_some(){
+            return Err(MoveError::GameOver);
+        }
+        let m = self.parse_move(s).ok_or(MoveError::Illegal)?;
+        self.record_move(m);
+        self.update_result();
+        Ok(())
+    }
+
+    //take back the last move played, false when there is none. the game is replayed from its starting position since
+    //the repetition window can't be rewound, an illegal tail kept by from_moves goes too
+    pub fn undo_move(&mut self) -> bool{
+        if self.move_history.is_empty(){
+            return false;
+        }
+
+        let mut moves = std::mem::take(&mut self.played_moves);
+        moves.truncate(self.move_history.len() - 1);
+
+        self.position = self.start_position;
+        self.history.clear();
+        self.move_history.clear();
+        for m in moves{
+            self.record_move(m);
+        }
+
+        self.result = None;
+        self.termination = None;
+        self.update_result();
+        true
+    }
+
+    //from the player's side of the board, white's when the engine plays itself
+    fn print_board(&self){
+        println!("{}", self.position.to_ascii_oriented(self.player_side.unwrap_or(Side::WHITE)));
+    }
+
+    fn make_move(&mut self, m: Move){
+        println!("Move played: {} ", m);
+        self.record_move(m);
+        self.print_board();
         println!("");
     }
 
+    //SAN first, long coordinates as a fallback
     fn parse_move(&self, m: &str) -> Option<Move>{
-        let mut moves = self.position.evaluate().moves;
-        moves.sort_by(|a, b| a.get_tstring().cmp(&b.get_tstring()));
-        for mov in moves{
-            if mov.get_tstring() == m{
-                return Some(mov);
+        self.parse_san(m).or_else(|| Move::from_uci(m, &self.position))
+    }
+
+    //SAN against the current position, check and mate suffixes are optional
+    pub fn parse_san(&self, s: &str) -> Option<Move>{
+        return Move::from_san(s, &self.position);
+    }
+
+    fn get_computer_move(&self) -> Move{
+        if let Some(millis) = self.time_per_move{
+            if let Some((best_move, _, _)) = search_timed_with_history(&self.position, &self.history, millis){
+                return best_move;
             }
         }
-        None
+        let mut tree = PositionTree::with_history(self.position, self.history.clone());
+        let best_moves = tree.expand_to_depth(self.get_search_depth(), ExpandStyle::DEFAULT, self.position.side_to_move);
+        best_moves[0]
     }
 
     fn get_player_move(&self) -> Move{
@@ -84,8 +259,7 @@ impl Game{
 
             //parse input
             let input = input.trim();
-            let input = input.to_lowercase();
-            let m = self.parse_move(&input);
+            let m = self.parse_move(input);
 
             if m.is_some(){
                 return m.unwrap();
@@ -96,50 +270,142 @@ impl Game{
         }
     }
 
-    pub fn get_pgn(&self, winner: Side) -> String{
+    //mate, a draw, a resignation or a flag fall has ended the game
+    pub fn is_game_over(&self) -> bool{
+        self.result.is_some()
+    }
+
+    //None while the game is still going, termination() tells how it ended
+    pub fn result(&self) -> Option<GameResult>{
+        self.result
+    }
+
+    pub fn termination(&self) -> Option<Termination>{
+        self.termination
+    }
+
+    fn finish(&mut self, result: GameResult, termination: Termination){
+        self.result = Some(result);
+        self.termination = Some(termination);
+    }
+
+    //record the result if the position on the board has ended the game
+    fn update_result(&mut self){
+        let eval = self.position.evaluate_with_history(&self.history);
+        let result = match GameResult::from_game_state(&eval.game_state, self.position.side_to_move, eval.draw_reason){
+            Some(result) => result,
+            None => return,
+        };
+
+        let termination = match result{
+            GameResult::Draw(reason) => Termination::from(reason),
+            _ => Termination::Checkmate,
+        };
+
+        self.finish(result, termination);
+    }
+
+    //a finished game keeps its result, like play_move
+    pub fn resign(&mut self, side: Side) -> Result<(), MoveError>{
+        if self.result.is_some(){
+            return Err(MoveError::GameOver);
+        }
+        let result = if side == Side::WHITE { GameResult::BlackWins } else { GameResult::WhiteWins };
+        self.finish(result, Termination::Resignation);
+        Ok(())
+    }
+
+    //flag falls for `side`, drawn when the opponent has nothing left to mate with
+    pub fn lose_on_time(&mut self, side: Side) -> Result<(), MoveError>{
+        if self.result.is_some(){
+            return Err(MoveError::GameOver);
+        }
+        let result = if !self.position.has_mating_material(!side, MaterialRule::STRICT){
+            GameResult::Draw(DrawReason::InsufficientMaterial)
+        }
+        else if side == Side::WHITE{
+            GameResult::BlackWins
+        }
+        else{
+            GameResult::WhiteWins
+        };
+        self.finish(result, Termination::Timeout);
+        Ok(())
+    }
+
+    //movetext in SAN numbered from the starting position, ending in the result or * while the game is going
+    pub fn get_pgn(&self) -> String{
         let mut pgn = String::new();
-        let mut move_count = 1;
-        let mut white_plays = true;
-        
-        for m in &self.move_history{
+        let mut move_count = self.start_position.fullmove_number;
+        let mut white_plays = self.starting_side == Side::WHITE;
+
+        if !white_plays && !self.move_history.is_empty(){
+            pgn += &format!("{}... ", move_count);
+        }
 
+        for m in &self.move_history{
             if white_plays{
                 pgn += &format!("{}. ", move_count);
+            }
+            else{
                 move_count += 1;
             }
 
-            pgn.push_str(format!("{}", m).as_str());
-            pgn.push_str(" ");
+            pgn.push_str(m);
+            pgn.push(' ');
             white_plays = !white_plays;
         }
 
-        if winner == Side::WHITE{
-            pgn += "1-0";
-        }
-        else if winner == Side::BLACK{
-            pgn += "0-1";
+        pgn += &self.result_token();
+
+        pgn
+    }
+
+    //seven tag roster, plus SetUp and FEN when the game didn't start from the initial position
+    pub fn get_pgn_with_headers(&self, headers: &PgnHeaders) -> String{
+        let mut pgn = String::new();
+        let tags = [
+            ("Event", headers.event.as_str()),
+            ("Site", headers.site.as_str()),
+            ("Date", headers.date.as_str()),
+            ("Round", headers.round.as_str()),
+            ("White", headers.white.as_str()),
+            ("Black", headers.black.as_str()),
+        ];
+        for (name, value) in tags{
+            pgn += &format!("[{} \"{}\"]\n", name, value);
         }
-        else{
-            pgn += "1/2-1/2";
+        pgn += &format!("[Result \"{}\"]\n", self.result_token());
+
+        if self.start_position != Position::new_game(){
+            pgn += "[SetUp \"1\"]\n";
+            pgn += &format!("[FEN \"{}\"]\n", self.start_position.to_fen());
         }
 
+        pgn += "\n";
+        pgn += &self.get_pgn();
+
         pgn
     }
 
-    pub fn play(&mut self, player: Option<Side>){
-        self.player_side = player;
+    fn result_token(&self) -> String{
+        match self.result{
+            Some(result) => format!("{}", result),
+            None => "*".to_string(),
+        }
+    }
 
-        let side_to_move = self.position.side_to_move;
+    pub fn play(&mut self, player: Option<Side>) -> GameResult{
+        self.player_side = player;
 
         println!("New game: ");
 
-        print_position(&self.position);
+        self.print_board();
 
-        if self.player_side.is_some(){
-            let eval = self.position.evaluate();
-            let mut game_state = eval.game_state;
+        self.update_result();
 
-            while game_state == GameState::ONGOING || game_state == GameState::CHECK{
+        if self.player_side.is_some(){
+            while self.result.is_none(){
                 if self.player_side.unwrap() == self.position.side_to_move{
                     println!("Player's turn: ");
                     let m = self.get_player_move();
@@ -147,39 +413,32 @@ impl Game{
                 }
                 else{
                     println!("Computer is thinking...");
-                    let mut tree = PositionTree::new(self.position);
-                    let best_moves = tree.expand_to_depth(self.max_depth, ExpandStyle::DEFAULT, self.position.side_to_move);
-                    let best_move = best_moves[0];
+                    let best_move = self.get_computer_move();
                     self.make_move(best_move);
                 }
-                game_state = self.position.evaluate().game_state;
+                self.update_result();
             }
         }
         else{
-            let eval = self.position.evaluate();
-            let mut game_state = eval.game_state;
-            while game_state == GameState::ONGOING || game_state == GameState::CHECK{
+            while self.result.is_none(){
                 println!("{} is thinking...", self.position.side_to_move);
-                let mut tree = PositionTree::new(self.position);
-                let best_moves = tree.expand_to_depth(self.max_depth, ExpandStyle::DEFAULT, self.position.side_to_move);
-                let best_move = best_moves[0];
+                let best_move = self.get_computer_move();
                 self.make_move(best_move);
-                game_state = self.position.evaluate().game_state;
+                self.update_result();
             }
         }
 
-        let eval = self.position.evaluate();
-        let game_state = eval.game_state;
-        let state_note = if eval.state_note.is_some() { eval.state_note.unwrap() } else { "None".to_string() };
-        if game_state == GameState::CHECKMATE{
-            println!("Checkmate! {} wins!", !side_to_move);
-        }
-        else{
-            println!("Draw! Reason: {}", state_note);
+        let result = self.result.unwrap();
+        let termination = self.termination.unwrap();
+        match result{
+            GameResult::WhiteWins => println!("{}! White wins!", termination),
+            GameResult::BlackWins => println!("{}! Black wins!", termination),
+            GameResult::Draw(reason) => println!("Draw! Reason: {}", reason),
         }
 
-        println!("PGN: {}", self.get_pgn(!side_to_move));
+        println!("PGN: {}", self.get_pgn());
 
+        return result;
     }
 
 }
\ No newline at end of file
@@ -8,6 +8,12 @@ pub mod display;
 pub mod types;
 pub mod masks;
 pub mod maps;
+pub mod cache;
+pub mod pst;
+pub mod search;
+pub mod uci;
+pub mod epd;
+pub mod book;
 
 #[cfg(test)]
 pub mod tests;
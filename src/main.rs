@@ -1,5 +1,6 @@
 
 use siegfried::game::Game;
+use siegfried::uci::run_uci;
 use siegfried::types::{Side, SideConstants};
 
 fn get_player_side() -> Option<Side>{
@@ -37,6 +38,12 @@ fn get_player_side() -> Option<Side>{
 
 fn  main() {
 
+    //`siegfried uci` talks to a gui instead of playing in the console
+    if std::env::args().any(|arg| arg == "uci"){
+        run_uci();
+        return;
+    }
+
     let player_side: Option<Side> = get_player_side();
     
     let mut game = Game::new();
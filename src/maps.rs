@@ -1,6 +1,7 @@
 use crate::masks::*;
 use crate::bitboard::*;
 use crate::types::Magic;
+use crate::types::MagicTable;
 use crate::types::MagicIndex;
 use crate::types::Side;
 use crate::types::SideConstants;
@@ -8,33 +9,22 @@ use crate::types::Square;
 use crate::types::SquareConstants;
 use crate::types::SquareMethods;
 use crate::types::Squares;
-use bitintr::Pext;
 
 use crate::lazy_static::lazy_static;
 
+//leaper attacks only depend on the square, so they're built at compile time
+static WHITE_PAWN_ATTACK_MAP: [Bitboard; 64] = get_pawn_attack_map(Side::WHITE);
+static BLACK_PAWN_ATTACK_MAP: [Bitboard; 64] = get_pawn_attack_map(Side::BLACK);
+static KNIGHT_ATTACK_MAP: [Bitboard; 64] = get_knight_attack_map();
+static KING_ATTACK_MAP: [Bitboard; 64] = get_king_attack_map();
+
 lazy_static! {
-    static ref WHITE_PAWN_ATTACK_MAP: [Bitboard; 64] = {
-        let m = get_pawn_attack_map(Side::WHITE);
-        m
-    };
-    static ref BLACK_PAWN_ATTACK_MAP: [Bitboard; 64] = {
-        let m = get_pawn_attack_map(Side::BLACK);
-        m
-    };
-    static ref KNIGHT_ATTACK_MAP: [Bitboard; 64] = {
-        let m = get_knight_attack_map();
-        m
-    };
-    static ref ROOK_MAGICS: Box<[Magic]> = {
-        let m = get_rook_magics().into_boxed_slice();
-        m
-    };
-    static ref BISHOP_MAGICS: Box<[Magic]> = {
-        let m = get_bishop_magics().into_boxed_slice();
+    static ref ROOK_MAGICS: MagicTable = {
+        let m = get_rook_magics();
         m
     };
-    static ref KING_ATTACK_MAP: [Bitboard; 64] = {
-        let m = get_king_attack_map();
+    static ref BISHOP_MAGICS: MagicTable = {
+        let m = get_bishop_magics();
         m
     };
     pub static ref DIRECTIONAL_MAP_RANK: [Bitboard; 64] = {
@@ -53,25 +43,70 @@ lazy_static! {
         let m = get_diagonal_descending_map();
         m
     };
+    static ref RAY_BETWEEN: [[Bitboard; 64]; 64] = {
+        let m = get_ray_between_map();
+        m
+    };
+    static ref LINE_THROUGH: [[Bitboard; 64]; 64] = {
+        let m = get_line_through_map();
+        m
+    };
 
 }
 
 pub fn load_maps() {
     let square = Square::D5;
     let occupancy = Bitboard::EMPTY;
-    //lazy load all the maps
+    //lazy load all the maps, the leaper attacks are already built at compile time
     let _rook_magic_init = get_rook_attacks(square, occupancy);
     let _bishop_magic_init = get_bishop_attacks(square, occupancy);   
-    let _knight_attack_init = get_knight_attacks(square);
-    let _king_attack_init = get_king_attacks(square);
 
     let _file_map_init = DIRECTIONAL_MAP_FILE[square as usize];
     let _rank_map_init = DIRECTIONAL_MAP_RANK[square as usize];
     let _dd_map_init = DIRECTIONAL_MAP_DD[square as usize];
     let _da_map_init = DIRECTIONAL_MAP_DA[square as usize];
+    let _ray_between_init = get_ray_between_squares(square, Square::A1);
+    let _line_through_init = get_line_through_squares(square, Square::A1);
 }
 
+//squares strictly between two squares on a shared rank, file or diagonal
 pub fn get_ray_between_squares(from: Square, to: Square) -> Bitboard{
+    return RAY_BETWEEN[from as usize][to as usize];
+}
+
+//the whole edge to edge line through both squares, empty if they don't share one
+pub fn get_line_through_squares(a: Square, b: Square) -> Bitboard{
+    return LINE_THROUGH[a as usize][b as usize];
+}
+
+fn get_ray_between_map() -> [[Bitboard; 64]; 64] {
+    let mut map: [[Bitboard; 64]; 64] = [[0; 64]; 64];
+    for from in Squares {
+        for to in Squares {
+            map[from as usize][to as usize] = compute_ray_between_squares(from, to);
+        }
+    }
+    return map;
+}
+
+fn get_line_through_map() -> [[Bitboard; 64]; 64] {
+    let mut map: [[Bitboard; 64]; 64] = [[0; 64]; 64];
+    let lines = [get_rank_map(), get_file_map(), get_diagonal_ascending_map(), get_diagonal_descending_map()];
+    for a in Squares {
+        for b in Squares {
+            for line in lines.iter(){
+                //the direction masks leave out their own square
+                if line[a as usize] & b.to_bitboard() != 0{
+                    map[a as usize][b as usize] = line[a as usize] | a.to_bitboard();
+                }
+            }
+        }
+    }
+    return map;
+}
+
+//loop version, only used to fill RAY_BETWEEN
+pub fn compute_ray_between_squares(from: Square, to: Square) -> Bitboard{
     let mut squares_between: Bitboard = 0;
 
     if from == to {
@@ -198,11 +233,13 @@ pub fn get_pawn_moves(side: Side, square: Square, occupancy: Bitboard) -> Bitboa
 }
 
 
-fn get_pawn_attack_map(side: Side) -> [Bitboard; 64] {
+//while instead of iterating Squares, const fns can't use for loops
+const fn get_pawn_attack_map(side: Side) -> [Bitboard; 64] {
     let mut attack_map: [Bitboard; 64] = [0; 64];
-    for square in Squares {
-        let attacks = mask_pawn_attacks(side, square);
-        attack_map[square as usize] = attacks;
+    let mut square = 0;
+    while square < 64 {
+        attack_map[square] = mask_pawn_attacks(side, square as Square);
+        square += 1;
     }
     return attack_map;
 }
@@ -216,13 +253,13 @@ pub fn get_pawn_attacks(side: Side, square: Square) -> Bitboard{
 }
 
 //KNIGHT
-fn get_knight_attack_map() -> [Bitboard; 64]{
+const fn get_knight_attack_map() -> [Bitboard; 64]{
     let mut attack_map: [Bitboard; 64] = [0; 64];
-
-    for square in Squares{
-        attack_map[square as usize] = mask_knight_attacks(square); 
+    let mut square = 0;
+    while square < 64 {
+        attack_map[square] = mask_knight_attacks(square as Square);
+        square += 1;
     }
-
     return attack_map;
 }
 
@@ -241,7 +278,7 @@ pub fn get_bishop_attack_rays() -> [Bitboard; 64]{
     return ray_map;
 }
 
-fn get_bishop_blockers() -> [Bitboard; 64]{
+pub fn get_bishop_blockers() -> [Bitboard; 64]{
     let mut block_map: [Bitboard; 64] = [0; 64];
     let attack_rays = get_bishop_attack_rays();
 
@@ -253,58 +290,17 @@ fn get_bishop_blockers() -> [Bitboard; 64]{
     return block_map;
 }
 
-fn get_bishop_magics() -> Vec<Magic> {
-    let mut bishop_magic: Vec<Magic> = Vec::with_capacity(64);
-
-    let bishop_blockmap = get_bishop_blockers();
-
-    let mut occupancy: [Bitboard; 4096] = [0; 4096];
-    let mut reference: [Bitboard; 4096] = [0; 4096];
-    let mut b: Bitboard;
-
-    let mut size: usize;
-
-    for square in Squares{
-        let bishop_mask = bishop_blockmap[square as usize];
-
-        let mut magic = Magic{
-            mask: bishop_mask,
-            magic: 0,
-            attacks: [0; 4096],
-            shift: bishop_mask.count_ones() as usize,
-        };
-
-        b = 0;
-        size = 0;
-
-        occupancy[size] = b;
-        reference[size] = mask_bishop_attacks(square, b);
-
-        magic.attacks[Pext::pext(b, magic.mask) as usize] = reference[size];
-
-        size+=1;
-        b = ((b | !magic.mask).overflowing_add(1).0) & magic.mask;
-
-        while b > 0 {
-            occupancy[size] = b;
-            reference[size] = mask_bishop_attacks(square, b);
-
-            magic.attacks[Pext::pext(b, magic.mask) as usize] = reference[size];
-
-            size+=1;
-            b = ((b | !magic.mask).wrapping_add(1)) & magic.mask;
-        }
-        bishop_magic.insert(square as usize, magic);
-    }
-
-    return bishop_magic;
+fn get_bishop_magics() -> MagicTable {
+    return build_bishop_magics(<Magic as MagicIndex>::get_index);
+}
 
+//attack table laid out for the given index method
+pub fn build_bishop_magics(index: fn(&Magic, Bitboard) -> usize) -> MagicTable {
+    return build_magics(&get_bishop_blockers(), &BISHOP_MAGIC_NUMBERS, mask_bishop_attacks, index);
 }
 
 pub fn get_bishop_attacks(square: Square, occupancy: Bitboard) -> Bitboard{
-    let magic = BISHOP_MAGICS[square as usize];
-    let index = magic.get_index(occupancy);
-    return magic.attacks[index];
+    return BISHOP_MAGICS.get_attacks(square, occupancy);
 }
 //ROOK 
 pub fn get_rook_attack_rays() -> [Bitboard; 64]{
@@ -317,75 +313,110 @@ pub fn get_rook_attack_rays() -> [Bitboard; 64]{
     return ray_map;
 }
 
-fn get_rook_blockers() -> [Bitboard; 64]{
+//relevant occupancy for each square, the last square of every ray never blocks anything
+pub fn get_rook_blockers() -> [Bitboard; 64]{
     let mut block_map: [Bitboard; 64] = [0; 64];
 
     let attack_rays = get_rook_attack_rays();
 
     for square in Squares{
         let mut attack_map = attack_rays[square as usize];
-        if (attack_map&FILE_ABB).count_ones() == 1 { attack_map &= NOT_FILE_ABB }
-        if (attack_map&FILE_HBB).count_ones() == 1 { attack_map &= NOT_FILE_HBB }
-        if (attack_map&RANK_1BB).count_ones() == 1 { attack_map &= NOT_RANK_1BB }
-        if (attack_map&RANK_8BB).count_ones() == 1 { attack_map &= NOT_RANK_8BB }
+        //drop the board edges the rook is not standing on
+        if square.get_file() != 0 { attack_map &= NOT_FILE_ABB }
+        if square.get_file() != 7 { attack_map &= NOT_FILE_HBB }
+        if square.get_rank() != 0 { attack_map &= NOT_RANK_1BB }
+        if square.get_rank() != 7 { attack_map &= NOT_RANK_8BB }
         block_map[square as usize] = attack_map;
     }
 
     return block_map;
 }
 
-fn get_rook_magics() -> Vec<Magic>{
-    let mut rook_magics: Vec<Magic> = Vec::with_capacity(64);
+fn get_rook_magics() -> MagicTable{
+    return build_rook_magics(<Magic as MagicIndex>::get_index);
+}
 
-    let bishop_blockmap = get_rook_blockers();
+//attack table laid out for the given index method
+pub fn build_rook_magics(index: fn(&Magic, Bitboard) -> usize) -> MagicTable {
+    return build_magics(&get_rook_blockers(), &ROOK_MAGIC_NUMBERS, mask_rook_attacks, index);
+}
 
-    let mut occupancy: [Bitboard; 4096] = [0; 4096];
-    let mut reference: [Bitboard; 4096] = [0; 4096];
-    let mut b: Bitboard;
+pub fn get_rook_attacks(square: Square, occupancy: Bitboard) -> Bitboard {
+    return ROOK_MAGICS.get_attacks(square, occupancy);
+}
 
-    let mut size: usize;
+//fill every square's slice of the shared table by walking all subsets of its blocker mask (carry-rippler),
+//each square gets exactly 1 << shift entries
+fn build_magics(blockers: &[Bitboard; 64], magic_numbers: &[Bitboard; 64], attacks: fn(Square, Bitboard) -> Bitboard, index: fn(&Magic, Bitboard) -> usize) -> MagicTable {
+    let mut table = MagicTable{
+        magics: Vec::with_capacity(64),
+        attacks: Vec::new(),
+    };
 
     for square in Squares{
-        let bishop_mask = bishop_blockmap[square as usize];
+        let mask = blockers[square as usize];
 
-        let mut magic = Magic{
-            mask: bishop_mask,
-            magic: 0,
-            attacks: [0; 4096],
-            shift: bishop_mask.count_ones() as usize,
+        let magic = Magic{
+            mask,
+            magic: magic_numbers[square as usize],
+            offset: table.attacks.len(),
+            shift: mask.count_ones() as usize,
         };
-
-        b = 0;
-        size = 0;
-
-        occupancy[size] = b;
-        reference[size] = mask_rook_attacks(square, b);
-
-        magic.attacks[Pext::pext(b, magic.mask) as usize] = reference[size];
-
-        size+=1;
-        b = ((b | !magic.mask).overflowing_add(1).0) & magic.mask;
-
-        while b > 0 {
-            occupancy[size] = b;
-            reference[size] = mask_rook_attacks(square, b);
-
-            magic.attacks[Pext::pext(b, magic.mask) as usize] = reference[size];
-
-            size+=1;
-            b = ((b | !magic.mask).wrapping_add(1)) & magic.mask;
+        table.attacks.resize(magic.offset + (1 << magic.shift), 0);
+
+        let mut b: Bitboard = 0;
+        loop {
+            table.attacks[magic.offset + index(&magic, b)] = attacks(square, b);
+            b = ((b | !mask).wrapping_add(1)) & mask;
+            if b == 0 {
+                break;
+            }
         }
-        rook_magics.insert(square as usize, magic);
+        table.magics.push(magic);
     }
 
-    return rook_magics;
+    return table;
 }
 
-pub fn get_rook_attacks(square: Square, occupancy: Bitboard) -> Bitboard {
-    let magic = ROOK_MAGICS[square as usize];
-    let index = magic.get_index(occupancy);
-    return magic.attacks[index];
-}
+//multiply-shift magics for the magic-mul index, generated offline for the masks above so that
+//(occupancy & mask) * magic >> (64 - shift) never sends two different attack sets to one slot
+pub const ROOK_MAGIC_NUMBERS: [Bitboard; 64] = [
+    0x00800080D4A04000, 0x08C0001000E00040, 0x348008A003801000, 0x220010400A000420,
+    0x0200201104080200, 0x0200010810040200, 0x0080020001000080, 0x610002803A004300,
+    0x0720800080400020, 0x2040804000802000, 0x0102001022420880, 0x0449002210000902,
+    0x0921001100080204, 0x9813808014000200, 0x000C800100020080, 0x2022800461000080,
+    0x6840018000502080, 0x2100830040010020, 0x0402490020010210, 0x0220808010000800,
+    0x2242110004080100, 0x8081010002040008, 0x0A41240001221008, 0x8040820008984401,
+    0x0010400880008020, 0x5840008100204100, 0x0800100280200080, 0x1000100300210038,
+    0xC000050100080010, 0x8002000200100804, 0x040098540091100A, 0x1400110200208044,
+    0x1340804004800024, 0x1020200040401000, 0x2420200880801000, 0x8E00080084801000,
+    0x102D010801001004, 0x0002000402000810, 0x0001000401000200, 0x1020800040800100,
+    0x000080A140018000, 0x0010002000444000, 0x0400200500450010, 0x0800080010008080,
+    0x8200040008008080, 0x0400040002008080, 0x4010900712440008, 0x0090008465020004,
+    0x3010800030400080, 0x0010004000200840, 0x8C40104100200100, 0x4800800802900280,
+    0x0404008008000480, 0x0040040080020080, 0x0000880102108400, 0x0401000040820100,
+    0x8C20402100800011, 0x01B8410020820812, 0x00020810C2016082, 0x0000A09428500101,
+    0x000A00142148100E, 0x4001000400080201, 0x00910200A810011C, 0x0202040044210882,
+];
+
+pub const BISHOP_MAGIC_NUMBERS: [Bitboard; 64] = [
+    0x08A4282208220210, 0x8208084800A12280, 0x8008820423100151, 0x1404052200020000,
+    0x0004042080002422, 0x080288200A010106, 0x00011822022000A0, 0x0082010092100202,
+    0x0010049050010100, 0x4002208801204080, 0x30A8040410821400, 0x30004C1404840680,
+    0x0800240420044460, 0x8004420802480010, 0x0081048828021010, 0x2000008414010400,
+    0x0492080410100100, 0x0420800208022080, 0x0010020800401020, 0x0104042802400800,
+    0x0021010820082430, 0x1209008A044A0200, 0x0002001100900480, 0x1A82206114880422,
+    0x0904420410420805, 0x80100240108E6618, 0x0022820ED00408D0, 0x0000808028020102,
+    0x0020840200802004, 0x9000820024221001, 0x0028A60824020209, 0x400041000A048A20,
+    0x201008A002840400, 0x0041012050100408, 0x02840C0200090200, 0x0000200800090810,
+    0x000B010400060020, 0x2C10004140860300, 0x8021082100020140, 0x00140400226484A0,
+    0x6002108220038800, 0x110C021242201080, 0x024282010100C084, 0x2000284010410201,
+    0x0804100204900201, 0x0001220810400200, 0x2074300221428200, 0x3404041072002042,
+    0x4840821002604002, 0x2280460210220080, 0x00404054042402A0, 0x4402040042022000,
+    0x0192206015240000, 0x10000A1081020000, 0x002108028084000A, 0x1004300204610400,
+    0x100A220308A01048, 0x0018020908C21000, 0x081000020A010410, 0x2004001220208810,
+    0xA200208008430401, 0x1142024050820080, 0x0088414848008081, 0x0040020082048B00,
+];
 
 //QUEEN
 pub fn get_queen_attack_rays() -> [Bitboard; 64]{
@@ -403,13 +434,13 @@ pub fn get_queen_attacks(square: Square, occupancy: Bitboard) -> Bitboard {
 }
 
 //KING 
-fn get_king_attack_map() -> [Bitboard; 64]{
+const fn get_king_attack_map() -> [Bitboard; 64]{
     let mut attack_map: [Bitboard; 64] = [0; 64];
-    
-    for square in Squares{
-        attack_map[square as usize] = mask_king_attacks(square); 
+    let mut square = 0;
+    while square < 64 {
+        attack_map[square] = mask_king_attacks(square as Square);
+        square += 1;
     }
-
     return attack_map;
 }
 
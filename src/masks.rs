@@ -6,6 +6,21 @@ const NW_CORNER: Bitboard = RANK_8BB|FILE_ABB;
 const SW_CORNER: Bitboard = RANK_1BB|FILE_ABB;
 const SE_CORNER: Bitboard = RANK_1BB|FILE_HBB;
 
+//the whole files either side of the square's file
+pub fn get_adjacent_files_mask(square: Square) -> Bitboard {
+    let file = FILES[square as usize % 8];
+    return ((file << 1) & NOT_FILE_ABB) | ((file >> 1) & NOT_FILE_HBB);
+}
+
+//every rank in front of the square as `side` moves, not including its own
+pub fn get_ranks_ahead_mask(side: Side, square: Square) -> Bitboard {
+    let rank = square as u32 / 8;
+    if side == Side::WHITE {
+        return Bitboard::FULL.checked_shl(8 * (rank + 1)).unwrap_or(0);
+    }
+    return (1u64 << (8 * rank)) - 1;
+}
+
 pub fn get_file_mask(square: Square) -> Bitboard {
     let mut mask: Bitboard = 0;
     let square_bb = square.to_bitboard();
@@ -120,13 +135,14 @@ pub fn get_diagonal_ascending_mask(square: Square) -> Bitboard {
 }
 
 //PAWN MASK
-pub fn mask_pawn_attacks(side: Side, square: Square) -> Bitboard{
+pub const fn mask_pawn_attacks(side: Side, square: Square) -> Bitboard{
 
-    let pawn = 1 << square;
+    let pawn: Bitboard = 1 << square;
 
     let mut attacks: Bitboard = 0;
 
-    if side == Side::WHITE {
+    //matched rather than compared, PartialEq isn't usable in a const fn
+    if let Side::WHITE = side {
         if pawn & FILE_HBB == 0 {attacks |= pawn << 9};
         if pawn & FILE_ABB == 0 {attacks |= pawn << 7};
         return attacks;
@@ -137,10 +153,10 @@ pub fn mask_pawn_attacks(side: Side, square: Square) -> Bitboard{
 }
 
 //KNIGHT MASK
-pub fn mask_knight_attacks(square: Square) -> Bitboard{
+pub const fn mask_knight_attacks(square: Square) -> Bitboard{
     let mut attacks: Bitboard = 0;
 
-    let knight = 1 << square;
+    let knight: Bitboard = 1 << square;
 
     if knight & (FILE_HBB) == 0 { 
         attacks |= knight << 17;
@@ -256,7 +272,7 @@ pub fn mask_rook_attacks(square: Square, occupancy: Bitboard) -> Bitboard{
             let line = 8 * x;
             let ray = rook >> line;
             attacks |= ray;
-            if ray & RANK_8BB != 0 || ray & occupancy != 0{
+            if ray & RANK_1BB != 0 || ray & occupancy != 0{
                 break;
             }
         }
@@ -277,10 +293,10 @@ pub fn mask_rook_attacks(square: Square, occupancy: Bitboard) -> Bitboard{
 }
 
 //KING MASK
-pub fn mask_king_attacks(square: Square) -> Bitboard{
+pub const fn mask_king_attacks(square: Square) -> Bitboard{
     let mut attacks: Bitboard = 0;
 
-    let king = 1 << square;
+    let king: Bitboard = 1 << square;
 
     //Left Shift
     if king & FILE_HBB == 0{
@@ -1,6 +1,9 @@
 use core::panic;
-use std::{fmt::{Display, Formatter, Result}};
+use std::{fmt::{Display, Formatter, Result}, ops::{Index, IndexMut}};
 use rayon::prelude::*;
+use rand::{Rng, SeedableRng};
+use rand_pcg::Pcg64;
+use lazy_static::lazy_static;
 
 use crate::{
     bitboard::*, 
@@ -16,18 +19,53 @@ use crate::{
         DIRECTIONAL_MAP_RANK,
         DIRECTIONAL_MAP_DD, 
         DIRECTIONAL_MAP_DA, get_ray_between_squares, get_pawn_moves, 
-        }, display::{print_position}
+        }, display::{print_position, position_to_ascii, position_to_ascii_oriented},
+    cache::EvalCache,
+    masks::{get_adjacent_files_mask, get_ranks_ahead_mask},
+    pst::{get_pst_values, taper, PHASE_MAX, PHASE_WEIGHTS},
+    tree::{PositionTree, ExpandStyle}
     };
 
 pub struct PositionEvaluation{
     pub moves: Vec<Move>,
     pub game_state: GameState,
     pub state_note: Option<String>,
+    //set exactly when game_state is DRAW
+    pub draw_reason: Option<DrawReason>,
     pub score: Option<f32>
 }
 
-const PIN_MULTIPLIER: f32 = 10.0;
-const SQUARE_MULTIPLIER: f32 = 5.0;
+//STRICT follows FIDE, KNN vs K can still be mated with help so the game goes on.
+//PRACTICAL treats two bare knights as unable to mate, for adjudicating engine games.
+#[derive(PartialEq, Clone, Copy)]
+pub struct MaterialRule(pub u8);
+
+impl MaterialRule{
+    pub const STRICT: MaterialRule = MaterialRule(0);
+    pub const PRACTICAL: MaterialRule = MaterialRule(1);
+}
+
+//what make_move_mut can't recover from the position after the move
+#[derive(Copy)]
+#[derive(Clone)]
+pub struct MoveUndo{
+    m: Move,
+    castling_rights: Castling,
+    en_passant_square: Option<Square>,
+    halfmove_clock: u32,
+    zobrist_hash: u64,
+}
+
+//best_move is None when the searched position is already over, score is from white's point of view
+pub struct SearchResult{
+    pub best_move: Option<Move>,
+    pub score: f32,
+    pub game_state: GameState,
+}
+
+const TRAPPED_PIECE_MAX_SQUARES: u32 = 1;
+//lifts every capture and promotion above the quiet moves when ordering
+const MVV_LVA_TACTICAL: i32 = 1000;
 
 const SCORE_WHITE_WINS: f32 = 1000000.0;
 const SCORE_BLACK_WINS: f32 = -1000000.0;
@@ -41,8 +79,69 @@ const PIECE_VALUES: [f32; 6] = [
     0.0
 ];
 
+//weights of the evaluation terms, evaluate uses the defaults and evaluate_with takes tuned ones
+#[derive(Copy)]
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct EvalParams{
+    pub piece_values: [f32; 6],
+    pub pin_multiplier: f32,
+    //per attacked square
+    pub mobility_multiplier: f32,
+    pub trapped_piece_penalty: f32,
+    pub doubled_pawn_penalty: f32,
+    pub isolated_pawn_penalty: f32,
+    //by rank counted from the passer's own side, a pawn never stands on the first or last
+    pub passed_pawn_bonus: [f32; 8],
+    //per file next to or in front of the king without one of our pawns one or two ranks ahead of it
+    pub pawn_shield_penalty: f32,
+    //per enemy piece hitting the king or the squares around it
+    pub king_attacker_penalty: f32,
+    //rooks on files without pawns of either colour, or without only our own
+    pub rook_open_file_bonus: f32,
+    pub rook_half_open_file_bonus: f32,
+    //two rooks seeing each other along a rank or file
+    pub connected_rooks_bonus: f32,
+    pub bishop_pair_bonus: f32,
+}
+
+impl Default for EvalParams{
+    fn default() -> EvalParams{
+        return EvalParams{
+            piece_values: PIECE_VALUES,
+            pin_multiplier: 10.0,
+            mobility_multiplier: 5.0,
+            trapped_piece_penalty: 50.0,
+            doubled_pawn_penalty: 15.0,
+            isolated_pawn_penalty: 15.0,
+            passed_pawn_bonus: [0.0, 5.0, 10.0, 20.0, 35.0, 60.0, 100.0, 0.0],
+            pawn_shield_penalty: 15.0,
+            king_attacker_penalty: 10.0,
+            rook_open_file_bonus: 20.0,
+            rook_half_open_file_bonus: 10.0,
+            connected_rooks_bonus: 10.0,
+            bishop_pair_bonus: 30.0,
+        };
+    }
+}
+
 pub type SidePieces = [Bitboard; 6];
 
+//index both sides' pieces by Side instead of its raw usize
+impl Index<Side> for [SidePieces; 2]{
+    type Output = SidePieces;
+
+    fn index(&self, side: Side) -> &SidePieces{
+        return &self[side.index()];
+    }
+}
+
+impl IndexMut<Side> for [SidePieces; 2]{
+    fn index_mut(&mut self, side: Side) -> &mut SidePieces{
+        return &mut self[side.index()];
+    }
+}
+
 pub trait SidePiecesMethods{
     fn new() -> SidePieces;
     fn new_game(side: Side) -> SidePieces;
@@ -136,8 +235,16 @@ pub struct ZobristHasher{
     pub side_to_move_hash: u64
 }
 
+lazy_static! {
+    //one set of keys shared by every position so hashes can be compared across positions and games
+    pub static ref ZOBRIST: ZobristHasher = ZobristHasher::new();
+}
+
+const ZOBRIST_SEED: u64 = 0x5EE6F21ED;
+
 impl ZobristHasher{
     pub fn new() -> ZobristHasher{
+        let mut rng = Pcg64::seed_from_u64(ZOBRIST_SEED);
         let mut piece_hashes: [[[u64; 64]; 6]; 2] = [[[0; 64]; 6]; 2];
         let mut castling_hashes: [u64; 16] = [0; 16];
         let mut en_passant_hashes: [u64; 64] = [0; 64];
@@ -146,20 +253,20 @@ impl ZobristHasher{
         for side in 0..2{
             for piece in 0..6{
                 for square in 0..64{
-                    piece_hashes[side][piece][square] = rand::random::<u64>();
+                    piece_hashes[side][piece][square] = rng.gen::<u64>();
                 }
             }
         }
 
         for i in 0..16{
-            castling_hashes[i] = rand::random::<u64>();
+            castling_hashes[i] = rng.gen::<u64>();
         }
 
         for i in 0..64{
-            en_passant_hashes[i] = rand::random::<u64>();
+            en_passant_hashes[i] = rng.gen::<u64>();
         }
 
-        side_to_move_hash = rand::random::<u64>();
+        side_to_move_hash = rng.gen::<u64>();
 
         return ZobristHasher{
             piece_hashes,
@@ -195,41 +302,84 @@ impl ZobristHasher{
         return hash;
     }
 
-}
+    //incrementally update a hash by xoring out the keys of `before` that changed in `after`
+    pub fn update_hash(&self, hash: u64, before: &Position, after: &Position) -> u64{
+        let mut hash = hash;
+
+        for side in 0..2{
+            for piece in 0..6{
+                let changed = before.pieces[side][piece] ^ after.pieces[side][piece];
+                for square in changed.squares(){
+                    hash ^= self.piece_hashes[side][piece][square as usize];
+                }
+            }
+        }
+
+        //castling rights can change from king moves, rook moves, rook captures and castling itself
+        if before.castling_rights != after.castling_rights{
+            hash ^= self.castling_hashes[before.castling_rights.get_zobrist_index()];
+            hash ^= self.castling_hashes[after.castling_rights.get_zobrist_index()];
+        }
+
+        if before.en_passant_square != after.en_passant_square{
+            if let Some(square) = before.en_passant_square{
+                hash ^= self.en_passant_hashes[square as usize];
+            }
+            if let Some(square) = after.en_passant_square{
+                hash ^= self.en_passant_hashes[square as usize];
+            }
+        }
+
+        if before.side_to_move != after.side_to_move{
+            hash ^= self.side_to_move_hash;
+        }
+
+        return hash;
+    }
 
-const MAX_ZOBRIST_ARRAY_SIZE: usize = 100;
+}
 
+//hashes of the positions since the last irreversible move, older positions can never repeat
 #[derive(PartialEq)]
-#[derive(Copy)]
 #[derive(Clone)]
 pub struct ZobristMoveStack{
-    pub zobrist_array: [u64; MAX_ZOBRIST_ARRAY_SIZE],
-    pub zobrist_array_index: usize
+    pub zobrist_hashes: Vec<u64>,
 }
 
 impl ZobristMoveStack{
     pub fn new() -> ZobristMoveStack{
         return ZobristMoveStack{
-            zobrist_array: [0; MAX_ZOBRIST_ARRAY_SIZE],
-            zobrist_array_index: 0
+            zobrist_hashes: Vec::new(),
         }
     }
 
     pub fn get_repetitions(&self, zobrist_hash: u64) -> usize{
-        return self.zobrist_array.par_iter().filter(|&&x| x == zobrist_hash).count();
+        return self.zobrist_hashes.iter().filter(|&&x| x == zobrist_hash).count();
     }
 
     pub fn add(&mut self, zobrist_hash: u64){
-        //if we are at the end of the array, we need to shift everything down
-        if self.zobrist_array_index == MAX_ZOBRIST_ARRAY_SIZE - 1{
-            for i in 0..MAX_ZOBRIST_ARRAY_SIZE - 1{
-                self.zobrist_array[i] = self.zobrist_array[i + 1];
-            }
-            self.zobrist_array[MAX_ZOBRIST_ARRAY_SIZE - 1] = zobrist_hash;
+        self.zobrist_hashes.push(zobrist_hash);
+    }
+
+    pub fn clear(&mut self){
+        self.zobrist_hashes.clear();
+    }
+
+    pub fn len(&self) -> usize{
+        return self.zobrist_hashes.len();
+    }
+
+    pub fn is_empty(&self) -> bool{
+        return self.zobrist_hashes.is_empty();
+    }
+
+    //pawn moves, captures and anything that loses castling rights start a new window, otherwise `before` is kept
+    pub fn record(&mut self, before: &Position, after: &Position){
+        if after.halfmove_clock == 0 || after.castling_rights != before.castling_rights{
+            self.clear();
         }
         else{
-            self.zobrist_array[self.zobrist_array_index] = zobrist_hash;
-            self.zobrist_array_index += 1;
+            self.add(before.zobrist_hash);
         }
     }
 }
@@ -243,6 +393,9 @@ pub struct Castling {
     pub white_queen_side: bool,
     pub black_king_side: bool,
     pub black_queen_side: bool,
+    //files the king and the rooks castle from, shared by both sides, e, h and a unless the game is Chess960
+    pub king_file: usize,
+    pub rook_files: [usize; 2],
 }
 
 #[derive(Copy)]
@@ -300,6 +453,8 @@ impl Castling {
             white_queen_side: false,
             black_king_side: false,
             black_queen_side: false,
+            king_file: 4,
+            rook_files: [7, 0],
         }
     }
 
@@ -309,9 +464,41 @@ impl Castling {
             white_queen_side: true,
             black_king_side: true,
             black_queen_side: true,
+            king_file: 4,
+            rook_files: [7, 0],
+        }
+    }
+
+    pub fn has_right(&self, side: Side, direction: CastlingDirection) -> bool{
+        return match (side, direction){
+            (Side::WHITE, KING_SIDE) => self.white_king_side,
+            (Side::WHITE, _) => self.white_queen_side,
+            (_, KING_SIDE) => self.black_king_side,
+            _ => self.black_queen_side,
+        };
+    }
+
+    pub fn set_right(&mut self, side: Side, direction: CastlingDirection, right: bool){
+        match (side, direction){
+            (Side::WHITE, KING_SIDE) => self.white_king_side = right,
+            (Side::WHITE, _) => self.white_queen_side = right,
+            (_, KING_SIDE) => self.black_king_side = right,
+            _ => self.black_queen_side = right,
         }
     }
 
+    //king from, king to, rook from and rook to, the king and rook always land on the g and f or c and d files
+    pub fn squares(&self, side: Side, direction: CastlingDirection) -> (Square, Square, Square, Square){
+        let rank = if side == Side::WHITE { 0 } else { 7 };
+        let (king_to, rook_to) = if direction == KING_SIDE { (6, 5) } else { (2, 3) };
+        return (
+            Square::from_rank_and_file(rank, self.king_file),
+            Square::from_rank_and_file(rank, king_to),
+            Square::from_rank_and_file(rank, self.rook_files[direction]),
+            Square::from_rank_and_file(rank, rook_to),
+        );
+    }
+
     pub fn get_zobrist_index(self) -> usize{
         let mut index: usize = 0;
 
@@ -330,6 +517,46 @@ impl Castling {
 
         return index;
     }
+
+    //a rook leaving or being captured on its home square loses that castling right
+    pub fn revoke_for_rook_square(&mut self, square: Square){
+        let side = match square.get_rank(){
+            0 => Side::WHITE,
+            7 => Side::BLACK,
+            _ => return,
+        };
+        for direction in [KING_SIDE, QUEEN_SIDE]{
+            if square.get_file() == self.rook_files[direction]{
+                self.set_right(side, direction, false);
+            }
+        }
+    }
+
+    //rights left after `us` plays `m` with `from_piece`, castling counts as a king move
+    pub fn after_move(&self, us: Side, m: Move, from_piece: Piece) -> Castling{
+        let mut rights = *self;
+        let translation = m.translation.unwrap();
+
+        if from_piece == KING{
+            if us == Side::WHITE{
+                rights.white_king_side = false;
+                rights.white_queen_side = false;
+            }
+            else{
+                rights.black_king_side = false;
+                rights.black_queen_side = false;
+            }
+        }
+        else if from_piece == ROOK{
+            rights.revoke_for_rook_square(translation.from);
+        }
+
+        if m.capture == Some(ROOK){
+            rights.revoke_for_rook_square(translation.to);
+        }
+
+        return rights;
+    }
 }
 
 #[derive(PartialEq)]
@@ -352,6 +579,39 @@ pub struct Move{
 }
 
 impl Move{
+    //a pass that moves no piece, only Position::make_null_move can play it
+    pub fn null() -> Move{
+        return Move{
+            translation: None,
+            promotion: None,
+            capture: None,
+            castling: None,
+            en_passant: None,
+        };
+    }
+
+    //castling is always the king's two square step with the direction set, so castling moves for a side compare equal
+    //however they were made, chess960 positions write it king to rook instead, see Position::castle_move
+    pub fn castle(side: Side, direction: CastlingDirection) -> Move{
+        let (from, to) = match (side, direction){
+            (Side::WHITE, KING_SIDE) => (Square::E1, Square::G1),
+            (Side::WHITE, _) => (Square::E1, Square::C1),
+            (_, KING_SIDE) => (Square::E8, Square::G8),
+            _ => (Square::E8, Square::C8),
+        };
+        return Move{
+            translation: Some(Translation{from, to}),
+            promotion: None,
+            capture: None,
+            castling: Some(direction),
+            en_passant: None,
+        };
+    }
+
+    pub fn is_null(&self) -> bool{
+        return *self == Move::null();
+    }
+
     pub fn get_tstring(&self) -> String{
         let mut tstring: String = String::new();
 
@@ -376,30 +636,167 @@ impl Move{
         return tstring;
     }
 
+    //parse long algebraic notation like e2e4, e7e8q or e1g1 against the legal moves of `pos`,
+    //so the capture, castling and en passant fields come from the move generator
+    pub fn from_uci(s: &str, pos: &Position) -> Option<Move>{
+        let uci = s.trim().to_lowercase();
+        let chars: Vec<char> = uci.chars().collect();
+        if chars.len() != 4 && chars.len() != 5{
+            return None;
+        }
+
+        let valid_square = |file: char, rank: char| ('a'..='h').contains(&file) && ('1'..='8').contains(&rank);
+        if !valid_square(chars[0], chars[1]) || !valid_square(chars[2], chars[3]){
+            return None;
+        }
+        if chars.len() == 5 && !['n', 'b', 'r', 'q'].contains(&chars[4]){
+            return None;
+        }
+
+        return pos.legal_moves().into_iter().find(|m| m.get_tstring() == uci);
+    }
+
+    //match `s` against the SAN of every legal move of `pos`, check, mate and annotation suffixes are optional
+    pub fn from_san(s: &str, pos: &Position) -> Option<Move>{
+        let strip = |san: &str| san.trim().trim_end_matches(|c| c == '+' || c == '#' || c == '!' || c == '?').replace('0', "O");
+        let san = strip(s);
+        if san.is_empty(){
+            return None;
+        }
+
+        return pos.legal_moves().into_iter().find(|m| strip(&m.to_san(pos)) == san);
+    }
+
+    //most valuable victim first, least valuable attacker breaks ties, piece indices rank the pieces (pawn lowest, king highest)
+    //`pos` is the position the move is played from, quiet moves score 0
+    pub fn mvv_lva_score(&self, pos: &Position) -> i32{
+        let mut score = 0;
+
+        if let Some(promotion) = self.promotion{
+            score = MVV_LVA_TACTICAL + promotion as i32 * 8;
+        }
+        if let (Some(capture), Some(translation)) = (self.capture, self.translation){
+            let attacker = pos.pieces[pos.side_to_move].get_piece_type_at_square(translation.from.to_bitboard()).unwrap_or(PAWN);
+            score = score.max(MVV_LVA_TACTICAL) + capture as i32 * 8 - attacker as i32;
+        }
+
+        return score;
+    }
+
+    //standard algebraic notation, `pos` is the position the move is played from
+    pub fn to_san(&self, pos: &Position) -> String{
+        let mut san = String::new();
+        let translation = self.translation.unwrap();
+
+        if let Some(direction) = self.castling{
+            san += if direction == KING_SIDE { "O-O" } else { "O-O-O" };
+        }
+        else{
+            let us = pos.side_to_move;
+            let piece = pos.pieces[us].get_piece_type_at_square(translation.from.to_bitboard()).unwrap();
+            let from = translation.from.as_string();
+
+            if piece == PAWN{
+                if self.capture.is_some(){
+                    san.push(from.chars().next().unwrap());
+                    san.push('x');
+                }
+                san += &translation.to.as_string();
+                if let Some(promotion) = self.promotion{
+                    san += &format!("={}", promotion.to_notation());
+                }
+            }
+            else{
+                san += piece.to_notation();
+
+                //other pieces of the same type that can reach the square
+                let rivals: Vec<Square> = pos.legal_moves().iter()
+                    .filter(|m| m.castling.is_none())
+                    .map(|m| m.translation.unwrap())
+                    .filter(|t| t.to == translation.to && t.from != translation.from)
+                    .filter(|t| pos.pieces[us][piece] & t.from.to_bitboard() != 0)
+                    .map(|t| t.from)
+                    .collect();
+
+                if !rivals.is_empty(){
+                    let same_file = rivals.iter().any(|square| square.get_file() == translation.from.get_file());
+                    let same_rank = rivals.iter().any(|square| square.get_rank() == translation.from.get_rank());
+                    if !same_file{
+                        san.push(from.chars().next().unwrap());
+                    }
+                    else if !same_rank{
+                        san.push(from.chars().nth(1).unwrap());
+                    }
+                    else{
+                        san += &from;
+                    }
+                }
+
+                if self.capture.is_some(){
+                    san.push('x');
+                }
+                san += &translation.to.as_string();
+            }
+        }
+
+        if let Some(after) = pos.make_move(*self){
+            if after.is_in_check(){
+                san.push(if after.legal_moves().is_empty() { '#' } else { '+' });
+            }
+        }
+
+        return san;
+    }
+
+    //en passant moves carry a pawn capture too, but check both so hand built moves classify the same
+    pub fn is_capture(&self) -> bool{
+        return self.capture.is_some() || self.en_passant.is_some();
+    }
+
+    pub fn is_promotion(&self) -> bool{
+        return self.promotion.is_some();
+    }
+
+    pub fn is_castle(&self) -> bool{
+        return self.castling.is_some();
+    }
+
+    pub fn is_en_passant(&self) -> bool{
+        return self.en_passant.is_some();
+    }
+
+    //neither captures nor promotes, castling counts as quiet
+    pub fn is_quiet(&self) -> bool{
+        return !self.is_capture() && !self.is_promotion();
+    }
+
+    //same board transition, the capture and en passant fields are derived data and not compared
+    pub fn same_transition(&self, other: &Move) -> bool{
+        let from_to = |m: &Move| m.translation.map(|t| (t.from, t.to));
+        return from_to(self) == from_to(other) && self.promotion == other.promotion && self.castling == other.castling;
+    }
+
 }
 
 impl Display for Move {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
-        if self.translation.is_some(){
+        if self.is_null(){
+            write!(f, "0000")?;
+        }
+        //castling carries the king's translation too, so it is checked first
+        else if let Some(direction) = self.castling{
+            write!(f, "{}", if direction == KING_SIDE { "O-O" } else { "O-O-O" })?;
+        }
+        //en passant goes through here like any other capture
+        else if self.translation.is_some(){
             let mut capture_string: String = String::new();
-            if self.capture.is_some(){
+            if self.is_capture(){
                 capture_string = "x".to_string();
             }
             let from_square: Square = self.translation.as_ref().unwrap().from;
             let to_square: Square = self.translation.as_ref().unwrap().to;
             write!(f, "{}{}{}", from_square.as_string(), capture_string, to_square.as_string())?;
         }
-        else if self.castling.is_some(){
-            if self.castling.unwrap() == KING_SIDE{
-                write!(f, "O-O")?;
-            }
-            else{
-                write!(f, "O-O-O")?;
-            }
-        }
-        else if self.en_passant.is_some(){
-            write!(f, "{}{}", self.en_passant.unwrap() - 8, self.en_passant.unwrap())?;
-        }
 
         if self.promotion.is_some(){
             write!(f, "={}", self.promotion.unwrap().to_notation())?;
@@ -409,7 +806,106 @@ impl Display for Move {
     }
 }
 
+//parse long algebraic notation like e2e4 or e7e8q without a position, so only the squares and the promotion are known
+//and the capture, castling and en passant fields are left None, use Move::from_uci to get a fully flagged legal move
+impl std::str::FromStr for Move{
+    type Err = MoveParseError;
+
+    fn from_str(s: &str) -> std::result::Result<Move, MoveParseError>{
+        let uci = s.trim().to_lowercase();
+        if !uci.is_ascii() || (uci.len() != 4 && uci.len() != 5){
+            return Err(MoveParseError::WrongLength);
+        }
+
+        let from = Square::try_from_string(&uci[0..2]).map_err(MoveParseError::BadSquare)?;
+        let to = Square::try_from_string(&uci[2..4]).map_err(MoveParseError::BadSquare)?;
+        let promotion = match uci.chars().nth(4){
+            None => None,
+            Some('n') => Some(KNIGHT),
+            Some('b') => Some(BISHOP),
+            Some('r') => Some(ROOK),
+            Some('q') => Some(QUEEN),
+            Some(_) => return Err(MoveParseError::BadPromotion),
+        };
+
+        return Ok(Move{
+            translation: Some(Translation{from, to}),
+            promotion,
+            capture: None,
+            castling: None,
+            en_passant: None,
+        });
+    }
+}
+
+#[derive(PartialEq)]
+#[derive(Debug)]
+#[derive(Copy)]
+#[derive(Clone)]
+pub enum MoveError{
+    Illegal,
+    GameOver,
+}
+
+impl Display for MoveError{
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self{
+            MoveError::Illegal => write!(f, "Illegal move"),
+            MoveError::GameOver => write!(f, "The game is already over"),
+        }
+    }
+}
+
+#[derive(PartialEq)]
+#[derive(Debug)]
+#[derive(Copy)]
+#[derive(Clone)]
+pub enum MoveParseError{
+    WrongLength,
+    BadSquare(SquareParseError),
+    BadPromotion,
+}
+
+impl Display for MoveParseError{
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self{
+            MoveParseError::WrongLength => write!(f, "A move is two squares and an optional promotion piece"),
+            MoveParseError::BadSquare(error) => write!(f, "Invalid square: {}", error),
+            MoveParseError::BadPromotion => write!(f, "Promotion piece must be one of n, b, r, q"),
+        }
+    }
+}
+
 #[derive(PartialEq)]
+#[derive(Debug)]
+#[derive(Copy)]
+#[derive(Clone)]
+pub enum FenError{
+    WrongFieldCount,
+    BadPiecePlacement,
+    BadKingCount,
+    PawnOnBackRank,
+    BadSideToMove,
+    BadCastling,
+    BadEnPassant,
+    BadClock,
+}
+
+impl Display for FenError{
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self{
+            FenError::WrongFieldCount => write!(f, "FEN must have 6 space separated fields, or 4 without the clocks"),
+            FenError::BadPiecePlacement => write!(f, "Invalid piece placement"),
+            FenError::BadKingCount => write!(f, "Each side must have exactly one king"),
+            FenError::PawnOnBackRank => write!(f, "Pawns can't stand on the first or eighth rank"),
+            FenError::BadSideToMove => write!(f, "Invalid side to move"),
+            FenError::BadCastling => write!(f, "Invalid castling rights"),
+            FenError::BadEnPassant => write!(f, "Invalid en passant square"),
+            FenError::BadClock => write!(f, "Invalid halfmove clock or fullmove number"),
+        }
+    }
+}
+
 #[derive(Copy)]
 #[derive(Clone)]
 pub struct Position{
@@ -419,23 +915,59 @@ pub struct Position{
     pub side_to_move: Side,
     pub castling_rights: Castling,
     pub en_passant_square: Option<Square>,
-    pub hasher : ZobristHasher,
-    pub zobrist_stack: ZobristMoveStack
+    pub zobrist_hash: u64,
+    //castling from the files in castling_rights, with castling moves written king to rook in uci
+    pub chess960: bool,
+}
+
+//the same board, side to move, castling rights and en passant square, the clocks don't change what can happen next
+//so transpositions reached at different move numbers compare equal
+impl PartialEq for Position{
+    fn eq(&self, other: &Position) -> bool{
+        return self.pieces == other.pieces
+            && self.side_to_move == other.side_to_move
+            && self.castling_rights == other.castling_rights
+            && self.en_passant_square == other.en_passant_square;
+    }
+}
+
+impl Eq for Position{}
+
+//the starting position, an empty board is Position::new()
+impl Default for Position{
+    fn default() -> Position{
+        return Position::new_game();
+    }
+}
+
+impl Display for Position{
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{}", self.to_ascii())
+    }
+}
+
+//equal positions share a zobrist hash, so it is all a HashMap needs
+impl std::hash::Hash for Position{
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H){
+        state.write_u64(self.zobrist_hash);
+    }
 }
 
 impl Position{
 
     pub fn new() -> Position{
-        Position{
+        let mut position = Position{
             pieces: [SidePieces::new(), SidePieces::new()],
             halfmove_clock: 0,
             fullmove_number: 1,
             side_to_move: Side::WHITE,
             castling_rights: Castling::new(),
             en_passant_square: None,
-            hasher: ZobristHasher::new(),
-            zobrist_stack: ZobristMoveStack::new(),
-        }
+            zobrist_hash: 0,
+            chess960: false,
+        };
+        position.zobrist_hash = ZOBRIST.hash_position(&position);
+        position
     }
 
     pub fn new_game() -> Position{
@@ -445,36 +977,51 @@ impl Position{
         let side_to_move = Side::WHITE;
         let castling_rights = Castling::new_game();
         let en_passant_square: Option<Square> = None;
-        let hasher = ZobristHasher::new();
-        let zobrist_stack = ZobristMoveStack::new();
 
-        Position{
+        let mut position = Position{
             pieces,
             halfmove_clock,
             fullmove_number,
             side_to_move,
             castling_rights,
             en_passant_square,
-            hasher,
-            zobrist_stack
-        }
+            zobrist_hash: 0,
+            chess960: false,
+        };
+        position.zobrist_hash = ZOBRIST.hash_position(&position);
+        position
+    }
+
+    //zobrist hash built from the shared ZOBRIST keys, equal for equal positions
+    pub fn hash(&self) -> u64{
+        return self.zobrist_hash;
+    }
+
+    //the board diagram print_position shows, as a string
+    pub fn to_ascii(&self) -> String{
+        return position_to_ascii(self);
+    }
+
+    //the board diagram from `perspective`'s side of the board
+    pub fn to_ascii_oriented(&self, perspective: Side) -> String{
+        return position_to_ascii_oriented(self, perspective);
     }
 
     pub fn piece_at(&self, square: Square) -> Option<(Piece, Side)>{
         let square_bb = square.to_bitboard();
-        let white_pieces = self.pieces[Side::WHITE.0].occupancy();
-        let black_pieces = self.pieces[Side::BLACK.0].occupancy();
+        let white_pieces = self.pieces[Side::WHITE].occupancy();
+        let black_pieces = self.pieces[Side::BLACK].occupancy();
 
         if square_bb & white_pieces != 0{
             for piece in 0..6{
-                if square_bb & self.pieces[Side::WHITE.0][piece] != 0{
+                if square_bb & self.pieces[Side::WHITE][piece] != 0{
                     return Some((piece, Side::WHITE));
                 }
             }
         }
         else if square_bb & black_pieces != 0{
             for piece in 0..6{
-                if square_bb & self.pieces[Side::WHITE.0][piece] != 0{
+                if square_bb & self.pieces[Side::BLACK][piece] != 0{
                     return Some((piece, Side::BLACK));
                 }
             }
@@ -486,70 +1033,168 @@ impl Position{
         return None;
     }
 
-    //parse a FEN string into a position
+    //set the castling rights and files from a FEN castling field, KQkq are the standard e, h and a files
+    //and the rook file letters of Shredder-FEN (HAha) make the position chess960
+    fn parse_castling(&mut self, field: &str) -> std::result::Result<(), FenError>{
+        let mut king_file: Option<usize> = None;
+        let mut rook_files: [Option<usize>; 2] = [None, None];
+
+        for c in field.chars(){
+            let side = if c.is_ascii_uppercase() { Side::WHITE } else { Side::BLACK };
+            let (direction, castling_king_file, rook_file) = match c.to_ascii_lowercase(){
+                'k' => (KING_SIDE, 4, 7),
+                'q' => (QUEEN_SIDE, 4, 0),
+                file @ 'a'..='h' => {
+                    //the king has to be on its back rank to tell which side the rook is on
+                    let king = self.pieces[side][KING].to_square();
+                    let rank = if side == Side::WHITE { 0 } else { 7 };
+                    let file = file as usize - 'a' as usize;
+                    if king.get_rank() != rank || file == king.get_file(){
+                        return Err(FenError::BadCastling);
+                    }
+                    self.chess960 = true;
+                    (if file > king.get_file() { KING_SIDE } else { QUEEN_SIDE }, king.get_file(), file)
+                }
+                _ => return Err(FenError::BadCastling)
+            };
+
+            //both sides castle from the same files
+            if king_file.map_or(false, |file| file != castling_king_file) || rook_files[direction].map_or(false, |file| file != rook_file){
+                return Err(FenError::BadCastling);
+            }
+            king_file = Some(castling_king_file);
+            rook_files[direction] = Some(rook_file);
+            self.castling_rights.set_right(side, direction, true);
+        }
+
+        self.castling_rights.king_file = king_file.unwrap_or(4);
+        self.castling_rights.rook_files = [rook_files[KING_SIDE].unwrap_or(7), rook_files[QUEEN_SIDE].unwrap_or(0)];
+
+        return Ok(());
+    }
+
+    //put `side`'s `piece` on the square, replacing whatever stood there, the hash follows
+    //castling rights and the en passant square are left as they are
+    pub fn set_piece(&mut self, square: Square, piece: Piece, side: Side){
+        self.remove_piece(square);
+        self.toggle_piece(side, piece, square);
+    }
+
+    //empty the square, returning the piece that was on it
+    pub fn remove_piece(&mut self, square: Square) -> Option<(Piece, Side)>{
+        let removed = self.piece_at(square);
+        if let Some((piece, side)) = removed{
+            self.toggle_piece(side, piece, square);
+        }
+        return removed;
+    }
+
+    //parse a FEN string into a position, panicking on malformed input
     pub fn from_fen(fen: &str) -> Position{
+        return match Position::try_from_fen(fen){
+            Ok(position) => position,
+            Err(error) => panic!("Invalid FEN string '{}': {}", fen, error)
+        };
+    }
+
+    //parse a FEN string into a position
+    pub fn try_from_fen(fen: &str) -> std::result::Result<Position, FenError>{
         let mut position = Position::new();
 
         //split the FEN string into its components
-        let fen_split: Vec<&str> = fen.split(" ").collect();
+        //the two clocks may be left off together, as EPD and many tools do
+        let fen_split: Vec<&str> = fen.split_whitespace().collect();
+        if fen_split.len() != 6 && fen_split.len() != 4{
+            return Err(FenError::WrongFieldCount);
+        }
         
         //get the piece placement
-        let piece_placement: Vec<&str> = fen_split[0].split("/").collect();
+        let piece_placement: Vec<&str> = fen_split[0].split('/').collect();
+        if piece_placement.len() != 8{
+            return Err(FenError::BadPiecePlacement);
+        }
 
         for (rank, rank_string) in piece_placement.iter().enumerate(){
             let mut file: usize = 0;
             for c in rank_string.chars(){
-                if c.is_digit(10){
-                    file += c.to_digit(10).unwrap() as usize;
+                if file >= 8{
+                    return Err(FenError::BadPiecePlacement);
+                }
+                if let Some(empty_squares) = c.to_digit(10){
+                    if empty_squares == 0 || file + empty_squares as usize > 8{
+                        return Err(FenError::BadPiecePlacement);
+                    }
+                    file += empty_squares as usize;
                 }
                 else{
-                    let piece_and_side = Piece::from_char_board(c);
-                    if piece_and_side != None{
-                        let piece = piece_and_side.unwrap().0;
-
-                        let side = piece_and_side.unwrap().1;
-                        let square = Square::from_rank_and_file(7-rank, file);
+                    let (piece, side) = Piece::from_char_board(c).ok_or(FenError::BadPiecePlacement)?;
+                    let square = Square::from_rank_and_file(7-rank, file);
 
-                        position.pieces[side.0][piece as usize] |= square.to_bitboard();
-                        file += 1;
-                    }
+                    position.pieces[side][piece] |= square.to_bitboard();
+                    file += 1;
                 }
             }
+            if file != 8{
+                return Err(FenError::BadPiecePlacement);
+            }
+        }
+
+        //a missing or extra king leaves the move generator without a king square
+        for side in Side::iter(){
+            if position.pieces[side][KING].count_ones() != 1{
+                return Err(FenError::BadKingCount);
+            }
+        }
+        if (position.pieces[Side::WHITE][PAWN] | position.pieces[Side::BLACK][PAWN]) & (RANK_1BB | RANK_8BB) != 0{
+            return Err(FenError::PawnOnBackRank);
         }
 
         //get the side to move
         position.side_to_move = match fen_split[1]{
             "w" => Side::WHITE,
             "b" => Side::BLACK,
-            _ => panic!("Invalid side to move in FEN string")
+            _ => return Err(FenError::BadSideToMove)
         };
 
-        //match the castling rights string
-        for c in fen_split[2].chars(){
-            match c{
-                'K' => position.castling_rights.white_king_side = true,
-                'Q' => position.castling_rights.white_queen_side = true,
-                'k' => position.castling_rights.black_king_side = true,
-                'q' => position.castling_rights.black_queen_side = true,
-                '-' => break,
-                _ => panic!("Invalid castling rights in FEN string")
-            }
+        //match the castling rights string, KQkq or the rook files of Shredder-FEN (HAha) for chess960
+        if fen_split[2] != "-"{
+            position.parse_castling(fen_split[2])?;
         }
 
         //get the en passant square
         position.en_passant_square = match fen_split[3]{
             "-" => None,
-            _ => Some(Square::from_string(fen_split[3]))
+            square => Some(Square::try_from_string(square).map_err(|_| FenError::BadEnPassant)?)
         };
-        
-        //get the halfmove clock
-        position.halfmove_clock = fen_split[4].parse::<u32>().unwrap();
 
-        //get the fullmove number
-        position.fullmove_number = fen_split[5].parse::<u32>().unwrap();     
+        //the square has to sit behind a pawn that just double pushed, and is dropped when no pawn can take on it
+        if let Some(square) = position.en_passant_square{
+            let us = position.side_to_move;
+            let expected_rank = if us == Side::WHITE { 5 } else { 2 };
+            if square.get_rank() != expected_rank{
+                return Err(FenError::BadEnPassant);
+            }
+
+            let (pushed_pawn, origin) = if us == Side::WHITE { (square - 8, square + 8) } else { (square + 8, square - 8) };
+            let occupancy = position.pieces[Side::WHITE].occupancy() | position.pieces[Side::BLACK].occupancy();
+            if position.pieces[!us][PAWN] & pushed_pawn.to_bitboard() == 0 || occupancy & (square.to_bitboard() | origin.to_bitboard()) != 0{
+                return Err(FenError::BadEnPassant);
+            }
+
+            if get_pawn_attacks(!us, square) & position.pieces[us][PAWN] == 0{
+                position.en_passant_square = None;
+            }
+        }
+        
+        //get the halfmove clock and fullmove number, 0 and 1 when they are missing
+        if fen_split.len() == 6{
+            position.halfmove_clock = fen_split[4].parse::<u32>().map_err(|_| FenError::BadClock)?;
+            position.fullmove_number = fen_split[5].parse::<u32>().map_err(|_| FenError::BadClock)?;
+        }
 
+        position.zobrist_hash = ZOBRIST.hash_position(&position);
 
-        return position
+        return Ok(position);
     }
 
     //get fen string of the position
@@ -587,19 +1232,18 @@ impl Position{
         fen_string.push(' ');
         fen_string.push(self.side_to_move.to_char());
 
-        //get the castling rights
+        //get the castling rights, chess960 positions name the rook files
         fen_string.push(' ');
-        if self.castling_rights.white_king_side{
-            fen_string.push('K');
-        }
-        if self.castling_rights.white_queen_side{
-            fen_string.push('Q');
-        }
-        if self.castling_rights.black_king_side{
-            fen_string.push('k');
-        }
-        if self.castling_rights.black_queen_side{
-            fen_string.push('q');
+        for side in Side::iter(){
+            for direction in [KING_SIDE, QUEEN_SIDE]{
+                if self.castling_rights.has_right(side, direction){
+                    let c = if self.chess960{
+                        (b'A' + self.castling_rights.rook_files[direction] as u8) as char
+                    }
+                    else if direction == KING_SIDE { 'K' } else { 'Q' };
+                    fen_string.push(if side == Side::WHITE { c } else { c.to_ascii_lowercase() });
+                }
+            }
         }
         if !self.castling_rights.white_king_side && !self.castling_rights.white_queen_side && !self.castling_rights.black_king_side && !self.castling_rights.black_queen_side{
             fen_string.push('-');
@@ -626,7 +1270,16 @@ impl Position{
     }
 
 
-    fn get_side_attacks(self, side: Side, occupancy: Bitboard) -> SideAttacks{
+    //squares attacked by all of `side`'s pawns, one shift per capture direction
+    pub fn pawn_attacks_bb(&self, side: Side) -> Bitboard{
+        let pawns = self.pieces[side][PAWN];
+        if side == Side::WHITE{
+            return (pawns << 7 & NOT_FILE_HBB) | (pawns << 9 & NOT_FILE_ABB);
+        }
+        return (pawns >> 9 & NOT_FILE_HBB) | (pawns >> 7 & NOT_FILE_ABB);
+    }
+
+    pub fn get_side_attacks(self, side: Side, occupancy: Bitboard) -> SideAttacks{
         let mut check: Option<PieceInfo> = None;
         let mut double_check: bool = false;
         let mut nonrays: Bitboard = 0;
@@ -636,28 +1289,23 @@ impl Position{
         let mut rays_da: Bitboard = 0;
 
         let enemy_side: Side = !side;
-        let enemy_king_square_bb = self.pieces[enemy_side.0][KING];
+        let enemy_king_square_bb = self.pieces[enemy_side][KING];
+
+        //pawns are done set-wise, the checking pawn is found by looking back from the king
+        nonrays |= self.pawn_attacks_bb(side);
+        let checking_pawns = if enemy_king_square_bb != 0 { get_pawn_attacks(enemy_side, enemy_king_square_bb.to_square()) & self.pieces[side][PAWN] } else { 0 };
+        if checking_pawns != 0{
+            check = Some(PieceInfo{
+                piece: PAWN,
+                square: checking_pawns.to_square(),
+            });
+        }
 
-        //iterate over all pieces
-        for i in 0..6{
-            let piece_bb = self.pieces[side.0][i];
-            for square in piece_bb.get_squares(){
-                if i == PAWN{
-                    let pawn_attacks = get_pawn_attacks(side, square);
-                    if enemy_king_square_bb & pawn_attacks != 0{
-                        if check.is_some(){
-                            double_check = true;
-                        }
-                        else{
-                            check = Some(PieceInfo{
-                                piece: PAWN,
-                                square: square,
-                            });
-                        }
-                    }
-                    nonrays |= pawn_attacks;
-                }
-                else if i == KNIGHT{
+        //iterate over the remaining pieces
+        for i in KNIGHT..6{
+            let piece_bb = self.pieces[side][i];
+            for square in piece_bb.squares(){
+                if i == KNIGHT{
                     let knight_attacks = get_knight_attacks(square);
                     if enemy_king_square_bb & knight_attacks != 0{
                         if check.is_some(){
@@ -740,37 +1388,387 @@ impl Position{
         };
     }
     
-    pub fn get_formatted_move(self, m: Move) -> String{
-        let mut move_string = String::new();
-
-        if m.translation.is_some(){
-            let from = m.translation.unwrap().from;
-            //get the piece
-            let piece = self.pieces[self.side_to_move.0].get_piece_type_at_square(from.to_bitboard());
-            if piece.is_some(){
-                let piece = piece.unwrap();
-                
-                if piece == KNIGHT{
-                    move_string.push('N');
-                }
-                else if piece == BISHOP{
-                    move_string.push('B');
-                }
-                else if piece == ROOK{
-                    move_string.push('R');
-                }
-                else if piece == QUEEN{
-                    move_string.push('Q');
-                }
-                else if piece == KING{
-                    move_string.push('K');
+    //en passant takes two pawns off the board at once, which the pin detection can't see,
+    //so replay the capture on the occupancy and look for an enemy slider hitting the king
+    fn en_passant_exposes_king(&self, from: Square, en_passant_square: Square, occupancy: Bitboard, king_square: Square) -> bool{
+        let us = self.side_to_move;
+        let them = !us;
+        let captured_square = if us == Side::WHITE { en_passant_square - 8 } else { en_passant_square + 8 };
+        let occupancy_after = (occupancy & !from.to_bitboard() & !captured_square.to_bitboard()) | en_passant_square.to_bitboard();
+
+        let straight = self.pieces[them][ROOK] | self.pieces[them][QUEEN];
+        let diagonal = self.pieces[them][BISHOP] | self.pieces[them][QUEEN];
+
+        return get_rook_attacks(king_square, occupancy_after) & straight != 0 || get_bishop_attacks(king_square, occupancy_after) & diagonal != 0;
+    }
+
+    //castling right held, king and rook on their home squares, every square either of them crosses empty besides
+    //the two of them, and neither the king's square nor its path attacked
+    fn castling_allowed(&self, side: Side, direction: CastlingDirection, occupancy: Bitboard, enemy_attacks: Bitboard) -> bool{
+        if !self.castling_rights.has_right(side, direction){
+            return false;
+        }
+
+        let (king_from, king_to, rook_from, rook_to) = self.castling_rights.squares(side, direction);
+        let king_and_rook = king_from.to_bitboard() | rook_from.to_bitboard();
+        let pieces_home = self.pieces[side][KING] & king_from.to_bitboard() != 0 && self.pieces[side][ROOK] & rook_from.to_bitboard() != 0;
+        if !pieces_home{
+            return false;
+        }
+
+        let span = |a: Square, b: Square| get_ray_between_squares(a, b) | a.to_bitboard() | b.to_bitboard();
+        let crossed = span(king_from, king_to) | span(rook_from, rook_to);
+        if occupancy & crossed & !king_and_rook != 0 || enemy_attacks & span(king_from, king_to) != 0{
+            return false;
+        }
+
+        //in chess960 the castling rook can shield the king's destination from a slider on the back rank
+        return !self.chess960 || !self.is_square_attacked(king_to, !side, occupancy ^ rook_from.to_bitboard());
+    }
+
+    //the castling move as the generator makes it, see Move::castle
+    pub fn castle_move(&self, side: Side, direction: CastlingDirection) -> Move{
+        if !self.chess960{
+            return Move::castle(side, direction);
+        }
+
+        //the king's destination can be its own square, so the move names the rook instead
+        let (king_from, _, rook_from, _) = self.castling_rights.squares(side, direction);
+        return Move{
+            translation: Some(Translation{from: king_from, to: rook_from}),
+            promotion: None,
+            capture: None,
+            castling: Some(direction),
+            en_passant: None,
+        };
+    }
+
+    pub fn can_castle(&self, side: Side, direction: CastlingDirection) -> bool{
+        let occupancy = self.pieces[Side::WHITE].occupancy() | self.pieces[Side::BLACK].occupancy();
+        let enemy_attacks = self.get_side_attacks(!side, occupancy);
+
+        return self.castling_allowed(side, direction, occupancy, enemy_attacks.all());
+    }
+
+    //squares of the pieces of `side` that attack `square` through the given occupancy, attacks are symmetric so
+    //each piece type is looked up from the square outwards (a pawn of `side` attacks it iff a `!side` pawn there would
+    //attack back), pieces removed from the occupancy still count as attackers so mask them off
+    pub fn attackers_to(&self, square: Square, side: Side, occupancy: Bitboard) -> Bitboard{
+        let pieces = self.pieces[side];
+        let diagonal = pieces[BISHOP] | pieces[QUEEN];
+        let straight = pieces[ROOK] | pieces[QUEEN];
+
+        return (get_pawn_attacks(!side, square) & pieces[PAWN])
+            | (get_knight_attacks(square) & pieces[KNIGHT])
+            | (get_bishop_attacks(square, occupancy) & diagonal)
+            | (get_rook_attacks(square, occupancy) & straight)
+            | (get_king_attacks(square) & pieces[KING]);
+    }
+
+    //static exchange evaluation of `m`: material won once both sides have traded everything they want to on its square
+    pub fn see(&self, m: Move) -> f32{
+        if m.castling.is_some(){
+            return 0.0;
+        }
+
+        let us = self.side_to_move;
+        let translation = m.translation.unwrap();
+        let mut occupancy = (self.pieces[Side::WHITE].occupancy() | self.pieces[Side::BLACK].occupancy()) & !translation.from.to_bitboard();
+
+        let mut gain: Vec<f32> = Vec::with_capacity(32);
+        let mut first_gain = m.capture.map_or(0.0, |capture| PIECE_VALUES[capture]);
+        if m.en_passant.is_some(){
+            let their_pawn = if us == Side::WHITE { translation.to - 8 } else { translation.to + 8 };
+            occupancy &= !their_pawn.to_bitboard();
+        }
+        let mut on_square = match m.promotion{
+            Some(promotion) => {
+                first_gain += PIECE_VALUES[promotion] - PIECE_VALUES[PAWN];
+                PIECE_VALUES[promotion]
+            }
+            None => PIECE_VALUES[self.pieces[us].get_piece_type_at_square(translation.from.to_bitboard()).unwrap()],
+        };
+        gain.push(first_gain);
+
+        //each side recaptures with its least valuable attacker, sliders behind it join as it leaves
+        let mut side = !us;
+        loop{
+            let attackers = self.attackers_to(translation.to, side, occupancy) & occupancy;
+            let piece = match (PAWN..=KING).find(|&piece| attackers & self.pieces[side][piece] != 0){
+                Some(piece) => piece,
+                None => break,
+            };
+            let attacker_bb = (attackers & self.pieces[side][piece]).pop_lsb();
+
+            //the king can only take when nothing recaptures
+            if piece == KING && self.attackers_to(translation.to, !side, occupancy & !attacker_bb) & occupancy != 0{
+                break;
+            }
+
+            gain.push(on_square - gain[gain.len() - 1]);
+            on_square = PIECE_VALUES[piece];
+            occupancy &= !attacker_bb;
+            side = !side;
+        }
+
+        //either side can stop trading when going on would lose more
+        while gain.len() > 1{
+            let last = gain.pop().unwrap();
+            let previous = gain.len() - 1;
+            gain[previous] = -(-gain[previous]).max(last);
+        }
+
+        return gain[0];
+    }
+
+    //true when `m` comes out at least `threshold` ahead after the exchanges
+    pub fn see_ge(&self, m: Move, threshold: f32) -> bool{
+        return self.see(m) >= threshold;
+    }
+
+    //pieces of `side`, king excluded, attacked by the enemy and not defended
+    pub fn hanging_pieces(&self, side: Side) -> Bitboard{
+        let occupancy = self.pieces[Side::WHITE].occupancy() | self.pieces[Side::BLACK].occupancy();
+        let our_attacks = self.get_side_attacks(side, occupancy).all();
+        let their_attacks = self.get_side_attacks(!side, occupancy).all();
+        let our_pieces = self.pieces[side].occupancy() & !self.pieces[side][KING];
+
+        return our_pieces & their_attacks & !our_attacks;
+    }
+
+    //king square and the squares around it that the enemy attacks
+    pub fn king_zone_pressure(&self, side: Side) -> Bitboard{
+        let occupancy = self.pieces[Side::WHITE].occupancy() | self.pieces[Side::BLACK].occupancy();
+        let king_bb = self.pieces[side][KING];
+        if king_bb == 0{
+            return 0;
+        }
+        let king_zone = king_bb | get_king_attacks(king_bb.trailing_zeros() as Square);
+
+        return king_zone & self.get_side_attacks(!side, occupancy).all();
+    }
+
+    //knights and bishops in the enemy half with at most TRAPPED_PIECE_MAX_SQUARES squares that are free of our pieces and enemy pawn attacks
+    pub fn trapped_pieces(&self, side: Side) -> Bitboard{
+        let occupancy = self.pieces[Side::WHITE].occupancy() | self.pieces[Side::BLACK].occupancy();
+        let unsafe_squares = self.pieces[side].occupancy() | self.pawn_attacks_bb(!side);
+        let enemy_half = if side == Side::WHITE { RANK_5BB | RANK_6BB | RANK_7BB | RANK_8BB } else { RANK_1BB | RANK_2BB | RANK_3BB | RANK_4BB };
+
+        let mut trapped: Bitboard = 0;
+        for square in (self.pieces[side][KNIGHT] & enemy_half).squares(){
+            if (get_knight_attacks(square) & !unsafe_squares).count_ones() <= TRAPPED_PIECE_MAX_SQUARES{
+                trapped |= square.to_bitboard();
+            }
+        }
+        for square in (self.pieces[side][BISHOP] & enemy_half).squares(){
+            if (get_bishop_attacks(square, occupancy) & !unsafe_squares).count_ones() <= TRAPPED_PIECE_MAX_SQUARES{
+                trapped |= square.to_bitboard();
+            }
+        }
+
+        return trapped;
+    }
+
+    //pawns with another of our pawns behind them on the same file, so a file with n pawns counts n - 1
+    pub fn doubled_pawns(&self, side: Side) -> Bitboard{
+        let pawns = self.pieces[side][PAWN];
+        let mut doubled: Bitboard = 0;
+        for square in pawns.squares(){
+            let behind = get_ranks_ahead_mask(!side, square) & FILES[square as usize % 8];
+            if pawns & behind != 0{
+                doubled |= square.to_bitboard();
+            }
+        }
+        return doubled;
+    }
+
+    //pawns with none of our pawns on the files next to them
+    pub fn isolated_pawns(&self, side: Side) -> Bitboard{
+        let pawns = self.pieces[side][PAWN];
+        let mut isolated: Bitboard = 0;
+        for square in pawns.squares(){
+            if pawns & get_adjacent_files_mask(square) == 0{
+                isolated |= square.to_bitboard();
+            }
+        }
+        return isolated;
+    }
+
+    //pawns no enemy pawn can stop: none ahead of them on their own file or the files next to it
+    pub fn passed_pawns(&self, side: Side) -> Bitboard{
+        let enemy_pawns = self.pieces[!side][PAWN];
+        let mut passed: Bitboard = 0;
+        for square in self.pieces[side][PAWN].squares(){
+            let front_span = get_ranks_ahead_mask(side, square) & (FILES[square as usize % 8] | get_adjacent_files_mask(square));
+            if enemy_pawns & front_span == 0{
+                passed |= square.to_bitboard();
+            }
+        }
+        return passed;
+    }
+
+    //doubled and isolated penalties and passed pawn bonuses from white's point of view
+    pub fn get_pawn_structure_score(&self, params: &EvalParams) -> f32{
+        let mut score = 0.0;
+        for (side, sign) in [(Side::WHITE, 1.0), (Side::BLACK, -1.0)]{
+            score -= sign * self.doubled_pawns(side).count_ones() as f32 * params.doubled_pawn_penalty;
+            score -= sign * self.isolated_pawns(side).count_ones() as f32 * params.isolated_pawn_penalty;
+            for square in self.passed_pawns(side).squares(){
+                let rank = square as usize / 8;
+                let relative_rank = if side == Side::WHITE { rank } else { 7 - rank };
+                score += sign * params.passed_pawn_bonus[relative_rank];
+            }
+        }
+        return score;
+    }
+
+    //rooks on a file with no pawns at all
+    pub fn rooks_on_open_files(&self, side: Side) -> Bitboard{
+        let pawns = self.pieces[Side::WHITE][PAWN] | self.pieces[Side::BLACK][PAWN];
+        return self.rooks_on_files_without(side, pawns);
+    }
+
+    //rooks on a file with only enemy pawns on it
+    pub fn rooks_on_half_open_files(&self, side: Side) -> Bitboard{
+        return self.rooks_on_files_without(side, self.pieces[side][PAWN]) & !self.rooks_on_open_files(side);
+    }
+
+    fn rooks_on_files_without(&self, side: Side, pawns: Bitboard) -> Bitboard{
+        let mut rooks: Bitboard = 0;
+        for square in self.pieces[side][ROOK].squares(){
+            if FILES[square as usize % 8] & pawns == 0{
+                rooks |= square.to_bitboard();
+            }
+        }
+        return rooks;
+    }
+
+    //two of our rooks on one rank or file with nothing between them
+    pub fn connected_rooks(&self, side: Side) -> bool{
+        let occupancy = self.pieces[Side::WHITE].occupancy() | self.pieces[Side::BLACK].occupancy();
+        let rooks: Vec<Square> = self.pieces[side][ROOK].squares().collect();
+        for (i, a) in rooks.iter().enumerate(){
+            for b in rooks.iter().skip(i + 1){
+                let same_line = a / 8 == b / 8 || a % 8 == b % 8;
+                if same_line && get_ray_between_squares(*a, *b) & occupancy == 0{
+                    return true;
                 }
             }
         }
+        return false;
+    }
+
+    //open and half-open file and connected rook bonuses from white's point of view
+    pub fn get_rook_score(&self, params: &EvalParams) -> f32{
+        let mut score = 0.0;
+        for (side, sign) in [(Side::WHITE, 1.0), (Side::BLACK, -1.0)]{
+            score += sign * self.rooks_on_open_files(side).count_ones() as f32 * params.rook_open_file_bonus;
+            score += sign * self.rooks_on_half_open_files(side).count_ones() as f32 * params.rook_half_open_file_bonus;
+            if self.connected_rooks(side){
+                score += sign * params.connected_rooks_bonus;
+            }
+        }
+        return score;
+    }
+
+    pub fn has_bishop_pair(&self, side: Side) -> bool{
+        return self.pieces[side][BISHOP].count_ones() >= 2;
+    }
+
+    //files around the king with no pawn of ours one or two ranks in front of it
+    pub fn missing_shield_pawns(&self, side: Side) -> u32{
+        let king = self.pieces[side][KING];
+        if king == 0{
+            return 0;
+        }
+        let king_square = king.to_square();
+        let rank = king_square as i32 / 8;
+        let ahead = if side == Side::WHITE { [rank + 1, rank + 2] } else { [rank - 1, rank - 2] };
+        let shield_ranks = ahead.iter().filter(|r| (0..8).contains(*r)).fold(0, |ranks, r| ranks | (RANK_1BB << (8 * r)));
+
+        let files = FILES[king_square as usize % 8] | get_adjacent_files_mask(king_square);
+        let pawns = self.pieces[side][PAWN] & shield_ranks;
+        return FILES.iter().filter(|file| *file & files != 0 && *file & pawns == 0).count() as u32;
+    }
+
+    //enemy pieces attacking the king's square or any square next to it, `enemy_attacks` are !side's attacks and
+    //narrow down the squares worth asking about
+    pub fn king_attackers(&self, side: Side, enemy_attacks: Bitboard) -> u32{
+        let king = self.pieces[side][KING];
+        if king == 0{
+            return 0;
+        }
+        let king_square = king.to_square();
+        let occupancy = self.pieces[Side::WHITE].occupancy() | self.pieces[Side::BLACK].occupancy();
 
-        move_string += format!("{}", m).as_str();
+        let mut attackers: Bitboard = 0;
+        for square in ((get_king_attacks(king_square) | king) & enemy_attacks).squares(){
+            attackers |= self.attackers_to(square, !side, occupancy);
+        }
+        return attackers.count_ones();
+    }
+
+    //what an exposed king costs `side`, gone in the endgame when there is little left to attack it with
+    pub fn king_safety_penalty(&self, side: Side, enemy_attacks: Bitboard, params: &EvalParams) -> f32{
+        let penalty = self.missing_shield_pawns(side) as f32 * params.pawn_shield_penalty
+            + self.king_attackers(side, enemy_attacks) as f32 * params.king_attacker_penalty;
+        return penalty * self.game_phase() as f32 / PHASE_MAX as f32;
+    }
+
+    //hanging pieces, attacked king zone and the squares in front of the king nothing but the king defends
+    pub fn weaknesses(&self, side: Side) -> Bitboard{
+        let king_bb = self.pieces[side][KING];
+        if king_bb == 0{
+            return self.hanging_pieces(side);
+        }
+        let king_square = king_bb.trailing_zeros() as Square;
+        let forward_rank = if side == Side::WHITE { king_square.get_rank() + 1 } else { king_square.get_rank().wrapping_sub(1) };
+        let front_squares = if forward_rank < 8 { get_king_attacks(king_square) & (RANK_1BB << (8 * forward_rank)) } else { 0 };
+
+        //defenders other than the king itself
+        let mut without_king = *self;
+        without_king.pieces[side][KING] = 0;
+        let occupancy = self.pieces[Side::WHITE].occupancy() | self.pieces[Side::BLACK].occupancy();
+        let defended = without_king.get_side_attacks(side, occupancy).all();
+        let weak_front = front_squares & !defended & !self.pieces[side][PAWN];
+
+        return self.hanging_pieces(side) | self.king_zone_pressure(side) | weak_front;
+    }
+
+    pub fn get_formatted_move(self, m: Move) -> String{
+        return m.to_san(&self);
+    }
+
+    //the side to move's king is attacked
+    pub fn is_in_check(&self) -> bool{
+        return self.in_check(self.side_to_move);
+    }
 
-        return move_string;
+    //`side`'s king is attacked by the other side, whoever is to move
+    pub fn in_check(&self, side: Side) -> bool{
+        let king = self.pieces[side][KING];
+        let occupancy = self.pieces[Side::WHITE].occupancy() | self.pieces[Side::BLACK].occupancy();
+        return king != 0 && self.is_square_attacked(king.to_square(), !side, occupancy);
+    }
+
+    //any of `by`'s pieces attack the square through the given occupancy, looks from the square outwards
+    //one piece type at a time and stops at the first hit
+    pub fn is_square_attacked(&self, square: Square, by: Side, occupancy: Bitboard) -> bool{
+        let pieces = self.pieces[by];
+
+        if get_pawn_attacks(!by, square) & pieces[PAWN] != 0{
+            return true;
+        }
+        if get_knight_attacks(square) & pieces[KNIGHT] != 0{
+            return true;
+        }
+        if get_king_attacks(square) & pieces[KING] != 0{
+            return true;
+        }
+        if get_bishop_attacks(square, occupancy) & (pieces[BISHOP] | pieces[QUEEN]) != 0{
+            return true;
+        }
+        return get_rook_attacks(square, occupancy) & (pieces[ROOK] | pieces[QUEEN]) != 0;
     }
 
     fn get_absolute_pins_for_side(self, enemy_attacks: SideAttacks, occupancy: Bitboard, defender_occupancy: Bitboard, defender_king_square: Square) -> AbsolutePins{
@@ -830,554 +1828,724 @@ impl Position{
 
     }
 
-    fn get_score(self) -> f32{
-        return (PIECE_VALUES[PAWN] * (self.pieces[Side::WHITE.0][PAWN].count_ones() as f32 - self.pieces[Side::BLACK.0][PAWN].count_ones() as f32))
-               + (PIECE_VALUES[KNIGHT] * (self.pieces[Side::WHITE.0][KNIGHT].count_ones() as f32 - self.pieces[Side::BLACK.0][KNIGHT].count_ones() as f32))
-               + (PIECE_VALUES[BISHOP] * (self.pieces[Side::WHITE.0][BISHOP].count_ones() as f32 - self.pieces[Side::BLACK.0][BISHOP].count_ones() as f32))
-               + (PIECE_VALUES[ROOK] * (self.pieces[Side::WHITE.0][ROOK].count_ones() as f32 - self.pieces[Side::BLACK.0][ROOK].count_ones() as f32))
-               + (PIECE_VALUES[QUEEN] * (self.pieces[Side::WHITE.0][QUEEN].count_ones() as f32 - self.pieces[Side::BLACK.0][QUEEN].count_ones() as f32));
+    fn get_score(&self, params: &EvalParams) -> f32{
+        let bishop_pair = (self.has_bishop_pair(Side::WHITE) as i32 - self.has_bishop_pair(Side::BLACK) as i32) as f32 * params.bishop_pair_bonus;
+        return self.get_material_score(params) + self.get_pst_score() + self.get_pawn_structure_score(params) + self.get_rook_score(params) + bishop_pair;
     }
 
-    fn check_draw(&mut self) -> (bool, String){
+    //PHASE_MAX with all the pieces still on the board down to 0 with only kings and pawns left
+    pub fn game_phase(&self) -> i32{
+        let mut phase = 0;
+        for piece in [KNIGHT, BISHOP, ROOK, QUEEN]{
+            let count = self.pieces[Side::WHITE][piece].count_ones() + self.pieces[Side::BLACK][piece].count_ones();
+            phase += count as i32 * PHASE_WEIGHTS[piece];
+        }
+        //promotions can push it past a full set
+        return phase.min(PHASE_MAX);
+    }
 
-        //check for 3-fold repetition
+    //piece-square bonuses from white's point of view, blended between the midgame and endgame tables by game phase
+    pub fn get_pst_score(&self) -> f32{
+        let mut mg = 0;
+        let mut eg = 0;
+        for (side, sign) in [(Side::WHITE, 1), (Side::BLACK, -1)]{
+            for piece in PAWN..=KING{
+                for square in self.pieces[side][piece].squares(){
+                    let (piece_mg, piece_eg) = get_pst_values(piece, side, square);
+                    mg += sign * piece_mg;
+                    eg += sign * piece_eg;
+                }
+            }
+        }
+        return taper(mg, eg, self.game_phase());
+    }
 
-        let current_position_hash = self.hasher.hash_position(self);
-        self.zobrist_stack.add(current_position_hash);
-        let repetitions = self.zobrist_stack.get_repetitions(current_position_hash);
-        if repetitions >= 3{
-            return (true, "Three-fold, repetition.".to_string());
+    fn get_material_score(&self, params: &EvalParams) -> f32{
+        let mut score = 0.0;
+        for piece in PAWN..KING{
+            score += params.piece_values[piece] * (self.pieces[Side::WHITE][piece].count_ones() as f32 - self.pieces[Side::BLACK][piece].count_ones() as f32);
         }
+        return score;
+    }
 
-        //check for 50 move rule
-        if self.halfmove_clock >= 100{
-            return (true, "Fifty-move rule.".to_string());
+    //pawns and major pieces can always mate, otherwise it takes two minor pieces
+    pub fn has_mating_material(&self, side: Side, rule: MaterialRule) -> bool{
+        let pieces = self.pieces[side];
+        if pieces[PAWN] | pieces[ROOK] | pieces[QUEEN] != 0{
+            return true;
         }
 
-        //check for insufficient material
-        let mut white_insufficient_material = true;
-        let mut black_insufficient_material = true;
+        let knights = pieces[KNIGHT].count_ones();
+        let bishops = pieces[BISHOP].count_ones();
 
-            for piece in 0..6{
-                if piece != KING{
-                    //check pawns
-                    if piece == PAWN{
-                        if self.pieces[Side::WHITE.0][PAWN] != 0{
-                            white_insufficient_material = false;
-                        }
-                        if self.pieces[Side::BLACK.0][PAWN] != 0{
-                            black_insufficient_material = false;
-                        }
-                    }
-                    //check knights
-                    else if piece == KNIGHT{
-                        if self.pieces[Side::WHITE.0][KNIGHT].count_ones() >= 2{
-                            white_insufficient_material = false;
-                        }
-                        if self.pieces[Side::BLACK.0][KNIGHT].count_ones() >= 2{
-                            black_insufficient_material = false;
-                        }
-                    }
-                    //check bishops
-                    else if piece == BISHOP{
-                        if self.pieces[Side::WHITE.0][BISHOP].count_ones() >= 2{
-                            white_insufficient_material = false;
-                        }
-                        if self.pieces[Side::BLACK.0][BISHOP].count_ones() >= 2{
-                            black_insufficient_material = false;
-                        }
-                    }
-                    //check rooks
-                    else if piece == ROOK{
-                        if self.pieces[Side::WHITE.0][ROOK].count_ones() >= 1{
-                            white_insufficient_material = false;
-                        }
-                        if self.pieces[Side::BLACK.0][ROOK].count_ones() >= 1{
-                            black_insufficient_material = false;
-                        }
-                    }
-                    //check queens
-                    else if piece == QUEEN{
-                        if self.pieces[Side::WHITE.0][QUEEN].count_ones() >= 1{
-                            white_insufficient_material = false;
-                        }
-                        if self.pieces[Side::BLACK.0][QUEEN].count_ones() >= 1{
-                            black_insufficient_material = false;
-                        }
-                    }
-                }
-            }
+        //two knights can only mate if the defender blunders into it
+        if rule == MaterialRule::PRACTICAL && knights == 2 && bishops == 0{
+            return false;
+        }
 
-        
+        return knights + bishops >= 2;
+    }
 
-        if white_insufficient_material && black_insufficient_material{
-            return (true, "Insufficient material.".to_string());
+    //no sequence of legal moves can mate: lone kings, a single minor piece, or bishops that all share one colour.
+    //PRACTICAL also counts positions where neither side can force mate, like KNN vs K
+    pub fn is_dead_position(&self, rule: MaterialRule) -> bool{
+        if rule == MaterialRule::PRACTICAL && !self.has_mating_material(Side::WHITE, rule) && !self.has_mating_material(Side::BLACK, rule){
+            return true;
         }
 
-        return (false, "".to_string());
+        let white = self.pieces[Side::WHITE];
+        let black = self.pieces[Side::BLACK];
+        if white[PAWN] | white[ROOK] | white[QUEEN] | black[PAWN] | black[ROOK] | black[QUEEN] != 0{
+            return false;
+        }
+
+        let knights = white[KNIGHT] | black[KNIGHT];
+        let bishops = white[BISHOP] | black[BISHOP];
+
+        if (knights | bishops).count_ones() <= 1{
+            return true;
+        }
+
+        return knights == 0 && (bishops & LIGHT_SQUARES == 0 || bishops & DARK_SQUARES == 0);
     }
 
-    pub fn evaluate(mut self) -> PositionEvaluation{
-        let mut moves: Vec<Move> = Vec::new();
+    //repetitions are tracked by the caller, see evaluate_with_history
+    //draws that don't need the move list or the game's history
+    pub fn check_draw(&self) -> Option<DrawReason>{
 
-        //just return if it's a draw
-        let draw_check = self.check_draw();
-        if draw_check.0{
-            return PositionEvaluation{
-                moves,
-                game_state: GameState::DRAW,
-                state_note: Some(draw_check.1),
-                score: Some(0.0)
-            }
+        //check for 50 move rule
+        if self.halfmove_clock >= 100{
+            return Some(DrawReason::FiftyMoveRule);
         }
 
-        let mut game_state: GameState = GameState::ONGOING;
+        //check for insufficient material
+        if self.is_dead_position(MaterialRule::STRICT){
+            return Some(DrawReason::InsufficientMaterial);
+        }
+
+        return None;
+    }
+
+    fn get_evasion_moves(self, their_attacks: SideAttacks, their_attacks_without_our_king: SideAttacks, our_pins: AbsolutePins, occupancy: Bitboard, our_occupancy: Bitboard, their_occupancy: Bitboard, our_king_square: Square) -> Vec<Move>{
+        let mut moves: Vec<Move> = Vec::new();
 
         let us = self.side_to_move;
         let them = !us;
 
-        let our_occupancy = self.pieces[us.0].occupancy();
-        let their_occupancy = self.pieces[them.0].occupancy();
-        let occupancy = our_occupancy | their_occupancy;
-
-        let our_king: Bitboard = self.pieces[us.0][KING];
-        let our_king_square = our_king.to_square();
+        //double check, only king must move
+        if their_attacks.double_check{
+            let available_squares: Bitboard = (get_king_attacks(our_king_square) & !our_occupancy) & !their_attacks_without_our_king.all();
+            for square in available_squares.squares(){
+                let square_bb = square.to_bitboard();
+                if square_bb & their_occupancy != 0{
+                    //find which piece the king is attacking
+                    let mut piece = 0;
+                    for i in 0..6{
+                        let pieces_bb = self.pieces[them][i];
+                        if pieces_bb & square_bb != 0{
+                            piece = i;
+                            break;
+                        }
+                    }
+                    //add capture move
+                    moves.push(Move{
+                        translation: Some(Translation { from: our_king_square, to: square }),
+                        promotion: None,
+                        capture: Some(piece),
+                        castling:None,
+                        en_passant: None, 
+                    });
+                }
+                else{
+                    moves.push(Move{
+                        translation: Some(Translation { from: our_king_square, to: square }),
+                        promotion: None,
+                        capture: None,
+                        castling:None,
+                        en_passant: None, 
+                    });
+                }
 
-        let their_king = self.pieces[them.0][KING];
-        let their_king_square = their_king.to_square();
+            }
+        }   
+        //single checker
+        else{
+            let checker = their_attacks.check.unwrap();
+            let checker_square = checker.square;
+            let checker_square_bb = checker_square.to_bitboard();
+            let checker_piece = checker.piece;
 
-        let occupancy_without_our_king = occupancy & !our_king;
+            let mut slider_squares: Bitboard = Bitboard::EMPTY;
 
-        let their_attacks = self.get_side_attacks(them, occupancy);
-        let their_attacks_without_our_king = self.get_side_attacks(them, occupancy_without_our_king);
+            if checker_piece == BISHOP || checker_piece == ROOK || checker_piece == QUEEN{
+                //find the squares between the king and the checker
+                slider_squares = get_ray_between_squares(our_king_square, checker_square);
+            }
 
-        let our_attacks = self.get_side_attacks(us, occupancy);
+            let mut pin_path: Bitboard;
 
-        let our_pins = self.get_absolute_pins_for_side(their_attacks, occupancy, our_occupancy, our_king_square);
-        let their_pins = self.get_absolute_pins_for_side(our_attacks, occupancy, their_occupancy, their_king_square);
+            for piece in 0..6{
+                let piece_bb = self.pieces[us][piece];
 
-        let pinscore = (our_pins.all().count_ones() as f32 - their_pins.all().count_ones() as f32) * PIN_MULTIPLIER;
-        let movescore = (their_attacks.all().count_ones() as f32 - our_attacks.all().count_ones() as f32) * SQUARE_MULTIPLIER;
+                for square in piece_bb.squares(){
 
-        let mut score = Some(self.get_score() + pinscore + movescore);
+                    pin_path = Bitboard::FULL;
 
-        //make sure king is not in check
-        if their_attacks.check.is_none(){
-            //generate castling moves
-            if us == Side::WHITE{
-                if self.castling_rights.white_king_side{
-                    //check that the squares between the king and the rook are empty
-                    if occupancy & WHITE_KINGSIDE_CASTLE == 0{
-                        //check that the squares between the king and the rook are not attacked
-                        if their_attacks.all() & WHITE_KINGSIDE_CASTLE == 0{
-                            let destination_square = Square::G1;
-                            moves.push(Move{
-                                translation: Some(Translation{
-                                    from: our_king_square,
-                                    to: destination_square,
-                                }),
-                                promotion: None,
-                                capture: None,
-                                castling: Some(KING_SIDE),
-                                en_passant: None, 
-                            });
+                    let square_bb = square.to_bitboard();
+                    if our_pins.all() & square_bb != 0{
+                        if square_bb & our_pins.pins_h != 0{
+                            pin_path = DIRECTIONAL_MAP_RANK[square as usize];
+                        }
+                        else if square_bb & our_pins.pins_v != 0{
+                            pin_path = DIRECTIONAL_MAP_FILE[square as usize];
                         }
+                        else if square_bb & our_pins.pins_da != 0{
+                            pin_path = DIRECTIONAL_MAP_DA[square as usize];
+                        }
+                        else if square_bb & our_pins.pins_dd != 0{
+                            pin_path = DIRECTIONAL_MAP_DD[square as usize];
+                        }    
                     }
-                }
-                if self.castling_rights.white_queen_side{
-                    //check that the squares between the king and the rook are empty
-                    if occupancy & WHITE_QUEENSIDE_CASTLE == 0{
-                        let white_queenside_squares = Square::C1.to_bitboard() | Square::D1.to_bitboard();
-                        //check that the squares between the king and the rook are not attacked
-                        if their_attacks.all() & white_queenside_squares == 0{
-                            let destination_square = Square::C1;
-                            moves.push(Move{
-                                translation: Some(Translation{
-                                    from: our_king_square,
-                                    to: destination_square,
-                                }),
-                                promotion: None,
-                                capture: None,
-                                castling: Some(QUEEN_SIDE),
-                                en_passant: None, 
-                            });
-                        }
-                    }
-                }
-            }
-            else{
-                if self.castling_rights.black_king_side{
-                    //check that the squares between the king and the rook are empty
-                    if occupancy & BLACK_KINGSIDE_CASTLE == 0{
 
-                        //check that the squares between the king and the rook are not attacked
-                        if their_attacks.all() & BLACK_KINGSIDE_CASTLE == 0{
-                            let destination_square = Square::G8;
+                    if piece == PAWN{
+                        let pawn_attacks = (get_pawn_attacks(us, square) & !our_occupancy) & pin_path;
+                        let pawn_move_bb = (get_pawn_moves(us, square, occupancy) & !our_occupancy) & pin_path;
+                        let pawn_move = (pawn_move_bb & slider_squares).to_square();
+
+                        if pawn_attacks & checker_square_bb != 0{
+                            //pawn capture
                             
-                            moves.push(Move{
-                                translation: Some(Translation{
-                                    from: our_king_square,
-                                    to: destination_square,
-                                }),
-                                promotion: None,
-                                capture: None,
-                                castling: Some(KING_SIDE),
-                                en_passant: None, 
-                            });
-                        }
-                    }
-                }
-                if self.castling_rights.black_queen_side{
-                    //check that the squares between the king and the rook are empty
-
-                    if occupancy & BLACK_QUEENSIDE_CASTLE == 0{
-                        let black_queenside_squares = Square::C8.to_bitboard() | Square::D8.to_bitboard();
-                        //check that the squares between the king and the rook are not attacked
-                        if their_attacks.all() & black_queenside_squares == 0{
-                            let destination_square = Square::C8;
-                            moves.push(Move{
-                                translation: Some(Translation{
-                                    from: our_king_square,
-                                    to: destination_square,
-                                }),
-                                promotion: None,
-                                capture: None,
-                                castling: Some(QUEEN_SIDE),
-                                en_passant: None, 
-                            });
+                            //generate promotion captures
+                            if (pawn_attacks & RANK_1BB != 0) || (pawn_attacks & RANK_8BB != 0){
+                                for promotion in [QUEEN, ROOK, BISHOP, KNIGHT]{
+                                    moves.push(Move{
+                                        translation: Some(Translation{
+                                            from: square,
+                                            to: checker_square,
+                                        }),
+                                        promotion: Some(promotion),
+                                        capture: Some(checker_piece),
+                                        castling: None,
+                                        en_passant: None, 
+                                    });
+                                }
+                            }
+                            else{
+                                moves.push(Move{
+                                    translation: Some(Translation{
+                                        from: square,
+                                        to: checker_square,
+                                    }),
+                                    promotion: None,
+                                    capture: Some(checker_piece),
+                                    castling: None,
+                                    en_passant: None, 
+                                });
+                            }
                         }
-                    }
-                }
-            }
-
-            //generate pawn moves and captures
-            let pawn_bb = self.pieces[us.0][PAWN];
-            let pawn_squares = pawn_bb.get_squares();
-            for square in pawn_squares{
-                let square_bb = square.to_bitboard();
-                //if pawn is not pinned horizontally or diagonally, generate pawn moves
-                if our_pins.pins_h & square_bb == 0 && our_pins.pins_dd & square_bb == 0 && our_pins.pins_da & square_bb == 0{
-                    //generate pawn moves
-                    let pawn_moves = get_pawn_moves(us, square, occupancy);
-                    let destination_squares = pawn_moves.get_squares();
-
-                    for destination_square in destination_squares{
-                        let destination_square_bb = destination_square.to_bitboard();
-                        if us == Side::WHITE && destination_square_bb & RANK_8BB != 0 || us == Side::BLACK && destination_square_bb & RANK_1BB != 0{
+                        if pawn_move != Square::NONE{
                             //generate promotion moves
-                            for promotion_piece in [QUEEN, ROOK, BISHOP, KNIGHT].iter(){
+                            if (pawn_move_bb & RANK_1BB != 0) || (pawn_move_bb & RANK_8BB != 0){
+                                for promotion in [QUEEN, ROOK, BISHOP, KNIGHT]{
+                                    moves.push(Move{
+                                        translation: Some(Translation{
+                                            from: square,
+                                            to: pawn_move,
+                                        }),
+                                        promotion: Some(promotion),
+                                        capture: None,
+                                        castling: None,
+                                        en_passant: None, 
+                                    });
+                                }
+                            }
+                            else{
                                 moves.push(Move{
                                     translation: Some(Translation{
                                         from: square,
-                                        to: destination_square,
+                                        to: pawn_move,
                                     }),
-                                    promotion: Some(*promotion_piece),
+                                    promotion: None,
                                     capture: None,
                                     castling: None,
                                     en_passant: None, 
                                 });
                             }
                         }
-                        else{
-                            //generate non-promotion moves
+                        if self.en_passant_square.is_some(){
+                            //en passant
+                            let en_passant_square = self.en_passant_square.unwrap();
+                            let en_passant_square_bb = en_passant_square.to_bitboard();
+                            let enemy_pawn_square = if us == Side::WHITE { en_passant_square - 8 } else { en_passant_square + 8 };
+                            let enemy_pawn_square_bb = enemy_pawn_square.to_bitboard();
+
+                            if pawn_attacks & en_passant_square_bb != 0{
+                                let en_passant_eats_checker = enemy_pawn_square_bb & checker_square_bb != 0;
+                                let en_passant_blocks_checker = en_passant_square_bb & slider_squares != 0;
+                                if (en_passant_eats_checker || en_passant_blocks_checker) && !self.en_passant_exposes_king(square, en_passant_square, occupancy, our_king_square){
+                                    moves.push(Move{
+                                        translation: Some(Translation { from: square, to: en_passant_square }),
+                                        promotion: None,
+                                        capture: Some(PAWN),
+                                        castling:None,
+                                        en_passant: Some(en_passant_square), 
+                                    });
+                                }
+                            }
+                        }   
+                    }
+                    else if piece == KNIGHT{
+                        let knight_attacks = (get_knight_attacks(square) & !our_occupancy) & pin_path;
+
+
+                        if knight_attacks & checker_square_bb != 0{
+                            //knight captures checker
                             moves.push(Move{
-                                translation: Some(Translation{
-                                    from: square,
-                                    to: destination_square,
-                                }),
+                                translation: Some(Translation { from: square, to: checker_square }),
                                 promotion: None,
-                                capture: None,
-                                castling: None,
+                                capture: Some(checker_piece),
+                                castling:None,
                                 en_passant: None, 
                             });
                         }
-                    }
-                }
-                //if pawn is not pinned horizontally or vertically, generate pawn captures
-                if our_pins.pins_h & square_bb == 0 && our_pins.pins_v & square_bb == 0{
-                    let mut valid_capture_path = Bitboard::FULL;
+                        //check if knight can move to block the check
+                        let valid_moves = (knight_attacks & slider_squares) & pin_path;
 
-                    if our_pins.pins_da & square_bb != 0{
-                        valid_capture_path = valid_capture_path & DIRECTIONAL_MAP_DA[square as usize];
-                    }
-                    if our_pins.pins_dd & square_bb != 0{
-                        valid_capture_path = valid_capture_path & DIRECTIONAL_MAP_DD[square as usize];
-                    }
-
-                    let pawn_attacks = get_pawn_attacks(us, square) & valid_capture_path;
-                    
-                    //generate normal pawn captures first
-                    let pawn_captures = pawn_attacks & their_occupancy;
-                    let pawn_capture_squares = pawn_captures.get_squares();
-
-                    for pawn_capture_square in pawn_capture_squares{
-                        let pawn_capture_square_bb = pawn_capture_square.to_bitboard();
-                        
-                        if us == Side::WHITE && pawn_capture_square_bb & RANK_8BB != 0 || us == Side::BLACK && pawn_capture_square_bb & RANK_1BB != 0{
-                            //generate promotion captures
-                            for promotion_piece in [QUEEN, ROOK, BISHOP, KNIGHT].iter(){
+                        if valid_moves != 0{
+                            for valid_move in valid_moves.squares(){
                                 moves.push(Move{
-                                    translation: Some(Translation{
-                                        from: square,
-                                        to: pawn_capture_square,
-                                    }),
-                                    promotion: Some(*promotion_piece),
-                                    capture: self.pieces[them.0].get_piece_type_at_square(pawn_capture_square_bb),
-                                    castling: None,
+                                    translation: Some(Translation { from: square, to: valid_move }),
+                                    promotion: None,
+                                    capture: None,
+                                    castling:None,
                                     en_passant: None, 
                                 });
                             }
                         }
-                        else{
-                            //generate non-promotion captures
+                    }
+                    else if piece == BISHOP{
+                        let bishop_attacks = (get_bishop_attacks(square, occupancy) & !our_occupancy) & pin_path;
+
+                        if bishop_attacks & checker_square_bb != 0{
+                            //bishop captures checker
                             moves.push(Move{
-                                translation: Some(Translation{
-                                    from: square,
-                                    to: pawn_capture_square,
-                                }),
+                                translation: Some(Translation { from: square, to: checker_square }),
                                 promotion: None,
-                                capture: self.pieces[them.0].get_piece_type_at_square(pawn_capture_square_bb),
-                                castling: None,
+                                capture: Some(checker_piece),
+                                castling:None,
                                 en_passant: None, 
                             });
                         }
-                    }
-                    if self.en_passant_square.is_some(){
-                        //generate en passant captures
-                        let en_passant_square = self.en_passant_square.unwrap();
-                        let en_passant_valid_bb = pawn_attacks & en_passant_square.to_bitboard();
+                        let bishop_moves = (bishop_attacks & slider_squares) & pin_path;
 
-                        if en_passant_valid_bb != 0{
-                            moves.push(Move{
-                                translation: Some(Translation{
-                                    from: square,
-                                    to: en_passant_square,
-                                }),
-                                promotion: None,
-                                capture: Some(PAWN),
-                                castling: None,
-                                en_passant: Some(en_passant_square),
-                            });
+                        if bishop_moves != 0{
+                            for bishop_move in bishop_moves.squares(){
+                                moves.push(Move{
+                                    translation: Some(Translation { from: square, to: bishop_move }),
+                                    promotion: None,
+                                    capture: None,
+                                    castling:None,
+                                    en_passant: None, 
+                                });
+                            }
                         }
                     }
-                }
-            }
-            
-            //generate knight moves
-            let knight_bb = self.pieces[us.0][KNIGHT];
-            let knight_squares = knight_bb.get_squares();
-
-            for knight in knight_squares{
-                let knight_attacks = get_knight_attacks(knight);
-                let current_knight_bb = knight.to_bitboard();
-                let valid_knight_attacks = knight_attacks & !our_occupancy;
-
-                //if knight is pinned at all, skip generating knight moves
-                if our_pins.all() & current_knight_bb == 0{
-                    for valid_knight_attack in valid_knight_attacks.get_squares(){
-                        let valid_knight_attack_bb = valid_knight_attack.to_bitboard();
-                        if valid_knight_attack_bb & their_occupancy != 0{
-                            //generate knight captures
+                    else if piece == ROOK{
+
+                        let rook_attacks = (get_rook_attacks(square, occupancy) & !our_occupancy) & pin_path;
+                        
+                        if rook_attacks & checker_square_bb != 0{
+                            //rook captures checker
                             moves.push(Move{
-                                translation: Some(Translation{
-                                    from: knight,
-                                    to: valid_knight_attack,
-                                }),
+                                translation: Some(Translation { from: square, to: checker_square }),
                                 promotion: None,
-                                capture: self.pieces[them.0].get_piece_type_at_square(valid_knight_attack_bb),
-                                castling: None,
+                                capture: Some(checker_piece),
+                                castling:None,
                                 en_passant: None, 
                             });
                         }
-                        else{
-                            //generate knight moves
+                        let rook_moves = (rook_attacks & slider_squares) & pin_path;
+
+                        if rook_moves != 0{
+                            for rook_move in rook_moves.squares(){
+                                moves.push(Move{
+                                    translation: Some(Translation { from: square, to: rook_move }),
+                                    promotion: None,
+                                    capture: None,
+                                    castling:None,
+                                    en_passant: None, 
+                                });
+                            }
+                        }
+                    }
+                    else if piece == QUEEN{
+                        let queen_attacks = (get_queen_attacks(square, occupancy) & !our_occupancy) & pin_path;
+
+                        if queen_attacks & checker_square_bb != 0{
+                            //queen captures checker
                             moves.push(Move{
-                                translation: Some(Translation{
-                                    from: knight,
-                                    to: valid_knight_attack,
-                                }),
+                                translation: Some(Translation { from: square, to: checker_square }),
                                 promotion: None,
-                                capture: None,
-                                castling: None,
+                                capture: Some(checker_piece),
+                                castling:None,
                                 en_passant: None, 
                             });
                         }
+
+                        let queen_moves = (queen_attacks & slider_squares) & pin_path;
+
+                        if queen_moves != 0{
+                            for queen_move in queen_moves.squares(){
+                                moves.push(Move{
+                                    translation: Some(Translation { from: square, to: queen_move }),
+                                    promotion: None,
+                                    capture: None,
+                                    castling:None,
+                                    en_passant: None, 
+                                });
+                            }
+                        }
                     }
+                    else if piece == KING{
+
+                        let mut valid_attacks = get_king_attacks(square) & !our_occupancy;
+                        valid_attacks &= !their_attacks_without_our_king.all();
+
+                        for attack in valid_attacks.squares(){
+                            let attack_bb = attack.to_bitboard();
+                            if attack_bb & checker_square_bb != 0{
+                                moves.push(Move{
+                                    translation: Some(Translation { from: square, to: attack }),
+                                    promotion: None,
+                                    capture: Some(checker_piece),
+                                    castling:None,
+                                    en_passant: None, 
+                                });
+                            }
+                            else if attack_bb & their_occupancy != 0{
+                                //find which piece the king is attacking
+                                let piece = self.pieces[them].get_piece_type_at_square(attack_bb);
+                                //king eats the piece
+                                moves.push(Move{
+                                    translation: Some(Translation { from: square, to: attack }),
+                                    promotion: None,
+                                    capture: piece,
+                                    castling:None,
+                                    en_passant: None, 
+                                });
+                            }
+                            else{
+                                //normal king move
+                                moves.push(Move{
+                                    translation: Some(Translation { from: square, to: attack }),
+                                    promotion: None,
+                                    capture: None,
+                                    castling:None,
+                                    en_passant: None, 
+                                });
+                            }
+                        }
+                    }   
                 }
+            }    
+        }
+
+        //a move that both captures and blocks, or is reached from two generators, is kept once
+        let mut unique: Vec<Move> = Vec::with_capacity(moves.len());
+        for m in moves{
+            if !unique.iter().any(|seen| seen.same_transition(&m)){
+                unique.push(m);
             }
+        }
+
+        return unique;
+    }
 
-            //generate bishop moves
-            let bishop_bb = self.pieces[us.0][BISHOP];
-            let bishop_squares = bishop_bb.get_squares();
+    //legal moves when the side to move is not in check, see get_evasion_moves for the rest
+    //captures_only keeps captures, en passant and promotions
+    fn get_non_evasion_moves(&self, their_attacks: SideAttacks, their_attacks_without_our_king: SideAttacks, our_pins: AbsolutePins, occupancy: Bitboard, our_occupancy: Bitboard, their_occupancy: Bitboard, our_king_square: Square, captures_only: bool) -> Vec<Move>{
+        let mut moves: Vec<Move> = Vec::new();
 
-            for bishop_square in bishop_squares{
-                let bishop_attacks = get_bishop_attacks(bishop_square, occupancy) & !our_occupancy;
-                let current_bishop_bb = bishop_square.to_bitboard();
+        let us = self.side_to_move;
+        let them = !us;
 
-                //if bishop is pinned horizontally or vertically, skip generating bishop moves
-                if our_pins.pins_h & current_bishop_bb == 0 && our_pins.pins_v & current_bishop_bb == 0{
-                    let mut valid_bishop_attacks: Bitboard;
-                    
-                    //if bishop is pinned diagonally, filter out moves that are not along the pin
-                    if our_pins.pins_dd & current_bishop_bb != 0{
-                        let bishop_path = DIRECTIONAL_MAP_DD[bishop_square as usize];
-                        valid_bishop_attacks = bishop_attacks & bishop_path;
-                    }
-                    else if our_pins.pins_da & current_bishop_bb != 0{
-                        let bishop_path = DIRECTIONAL_MAP_DA[bishop_square as usize];
-                        valid_bishop_attacks = bishop_attacks & bishop_path;
-                    }
-                    //bishop is not pinned
-                    else{
-                        valid_bishop_attacks = bishop_attacks;
-                    }
+        //squares pieces may move to and squares pawns may push to
+        let targets = if captures_only { their_occupancy } else { Bitboard::FULL };
+        let push_targets = if captures_only { RANK_1BB | RANK_8BB } else { Bitboard::FULL };
 
-                    valid_bishop_attacks &= !our_occupancy;
+        //generate castling moves
+        for direction in [KING_SIDE, QUEEN_SIDE]{
+            if !captures_only && self.castling_allowed(us, direction, occupancy, their_attacks.all()){
+                moves.push(self.castle_move(us, direction));
+            }
+        }
 
-                    for valid_bishop_attack in valid_bishop_attacks.get_squares(){
-                        let valid_bishop_attack_bb = valid_bishop_attack.to_bitboard();
-                        if valid_bishop_attack_bb & their_occupancy != 0{
-                            //generate bishop captures
-                            moves.push(Move{
-                                translation: Some(Translation{
-                                    from: bishop_square,
-                                    to: valid_bishop_attack,
-                                }),
-                                promotion: None,
-                                capture: self.pieces[them.0].get_piece_type_at_square(valid_bishop_attack_bb),
-                                castling: None,
-                                en_passant: None, 
-                            });
-                        }
-                        else{
-                            //generate bishop moves
+        //generate pawn moves and captures
+        let pawn_bb = self.pieces[us][PAWN];
+        let pawn_squares = pawn_bb.squares();
+        for square in pawn_squares{
+            let square_bb = square.to_bitboard();
+            //if pawn is not pinned horizontally or diagonally, generate pawn moves
+            if our_pins.pins_h & square_bb == 0 && our_pins.pins_dd & square_bb == 0 && our_pins.pins_da & square_bb == 0{
+                //generate pawn moves
+                let pawn_moves = get_pawn_moves(us, square, occupancy) & push_targets;
+                let destination_squares = pawn_moves.squares();
+
+                for destination_square in destination_squares{
+                    let destination_square_bb = destination_square.to_bitboard();
+                    if us == Side::WHITE && destination_square_bb & RANK_8BB != 0 || us == Side::BLACK && destination_square_bb & RANK_1BB != 0{
+                        //generate promotion moves
+                        for promotion_piece in [QUEEN, ROOK, BISHOP, KNIGHT].iter(){
                             moves.push(Move{
                                 translation: Some(Translation{
-                                    from: bishop_square,
-                                    to: valid_bishop_attack,
+                                    from: square,
+                                    to: destination_square,
                                 }),
-                                promotion: None,
+                                promotion: Some(*promotion_piece),
                                 capture: None,
                                 castling: None,
                                 en_passant: None, 
                             });
                         }
                     }
+                    else{
+                        //generate non-promotion moves
+                        moves.push(Move{
+                            translation: Some(Translation{
+                                from: square,
+                                to: destination_square,
+                            }),
+                            promotion: None,
+                            capture: None,
+                            castling: None,
+                            en_passant: None, 
+                        });
+                    }
                 }
             }
+            //if pawn is not pinned horizontally or vertically, generate pawn captures
+            if our_pins.pins_h & square_bb == 0 && our_pins.pins_v & square_bb == 0{
+                let mut valid_capture_path = Bitboard::FULL;
 
-            //generate rook moves
-            let rook_bb = self.pieces[us.0][ROOK];
-
-            let rook_squares = rook_bb.get_squares();
-
-            for rook_square in rook_squares{
-                let rook_attacks = get_rook_attacks(rook_square, occupancy) & !our_occupancy;
+                if our_pins.pins_da & square_bb != 0{
+                    valid_capture_path = valid_capture_path & DIRECTIONAL_MAP_DA[square as usize];
+                }
+                if our_pins.pins_dd & square_bb != 0{
+                    valid_capture_path = valid_capture_path & DIRECTIONAL_MAP_DD[square as usize];
+                }
 
-                let current_rook_bb = rook_square.to_bitboard();
+                let pawn_attacks = get_pawn_attacks(us, square) & valid_capture_path;
+                
+                //generate normal pawn captures first
+                let pawn_captures = pawn_attacks & their_occupancy;
+                let pawn_capture_squares = pawn_captures.squares();
 
-                //if rook is pinned diagonally, skip generating rook moves
-                if our_pins.pins_dd & current_rook_bb == 0 && our_pins.pins_da & current_rook_bb == 0{
-                    let valid_rook_attacks: Bitboard;
+                for pawn_capture_square in pawn_capture_squares{
+                    let pawn_capture_square_bb = pawn_capture_square.to_bitboard();
                     
-                    //if rook is pinned horizontally or vertically, filter out moves that are not along the pin
-                    if our_pins.pins_h & current_rook_bb != 0{
-                        let rook_path = DIRECTIONAL_MAP_RANK[rook_square as usize];
-                        valid_rook_attacks = rook_attacks & rook_path;
-                    }
-                    else if our_pins.pins_v & current_rook_bb != 0{
-                        let rook_path = DIRECTIONAL_MAP_FILE[rook_square as usize];
-                        valid_rook_attacks = rook_attacks & rook_path;
-                    }
-                    //rook is not pinned
-                    else{
-                        valid_rook_attacks = rook_attacks;
-                    }
-
-                    for valid_rook_attack in valid_rook_attacks.get_squares(){
-                        let valid_rook_attack_bb = valid_rook_attack.to_bitboard();
-
-                        if valid_rook_attack_bb & their_occupancy != 0{
-                            //generate rook captures
+                    if us == Side::WHITE && pawn_capture_square_bb & RANK_8BB != 0 || us == Side::BLACK && pawn_capture_square_bb & RANK_1BB != 0{
+                        //generate promotion captures
+                        for promotion_piece in [QUEEN, ROOK, BISHOP, KNIGHT].iter(){
                             moves.push(Move{
                                 translation: Some(Translation{
-                                    from: rook_square,
-                                    to: valid_rook_attack,
-                                }),
-                                promotion: None,
-                                capture: self.pieces[them.0].get_piece_type_at_square(valid_rook_attack_bb),
-                                castling: None,
-                                en_passant: None, 
-                            });
-                        }
-                        else{
-                            //generate rook moves
-                            moves.push(Move{
-                                translation: Some(Translation{
-                                    from: rook_square,
-                                    to: valid_rook_attack,
+                                    from: square,
+                                    to: pawn_capture_square,
                                 }),
-                                promotion: None,
-                                capture: None,
+                                promotion: Some(*promotion_piece),
+                                capture: self.pieces[them].get_piece_type_at_square(pawn_capture_square_bb),
                                 castling: None,
                                 en_passant: None, 
                             });
                         }
                     }
+                    else{
+                        //generate non-promotion captures
+                        moves.push(Move{
+                            translation: Some(Translation{
+                                from: square,
+                                to: pawn_capture_square,
+                            }),
+                            promotion: None,
+                            capture: self.pieces[them].get_piece_type_at_square(pawn_capture_square_bb),
+                            castling: None,
+                            en_passant: None, 
+                        });
+                    }
+                }
+                if self.en_passant_square.is_some(){
+                    //generate en passant captures
+                    let en_passant_square = self.en_passant_square.unwrap();
+                    let en_passant_valid_bb = pawn_attacks & en_passant_square.to_bitboard();
+
+                    if en_passant_valid_bb != 0 && !self.en_passant_exposes_king(square, en_passant_square, occupancy, our_king_square){
+                        moves.push(Move{
+                            translation: Some(Translation{
+                                from: square,
+                                to: en_passant_square,
+                            }),
+                            promotion: None,
+                            capture: Some(PAWN),
+                            castling: None,
+                            en_passant: Some(en_passant_square),
+                        });
+                    }
                 }
             }
+        }
+        
+        //generate knight moves
+        let knight_bb = self.pieces[us][KNIGHT];
+        let knight_squares = knight_bb.squares();
+
+        for knight in knight_squares{
+            let knight_attacks = get_knight_attacks(knight);
+            let current_knight_bb = knight.to_bitboard();
+            let valid_knight_attacks = knight_attacks & !our_occupancy & targets;
+
+            //if knight is pinned at all, skip generating knight moves
+            if our_pins.all() & current_knight_bb == 0{
+                for valid_knight_attack in valid_knight_attacks.squares(){
+                    let valid_knight_attack_bb = valid_knight_attack.to_bitboard();
+                    if valid_knight_attack_bb & their_occupancy != 0{
+                        //generate knight captures
+                        moves.push(Move{
+                            translation: Some(Translation{
+                                from: knight,
+                                to: valid_knight_attack,
+                            }),
+                            promotion: None,
+                            capture: self.pieces[them].get_piece_type_at_square(valid_knight_attack_bb),
+                            castling: None,
+                            en_passant: None, 
+                        });
+                    }
+                    else{
+                        //generate knight moves
+                        moves.push(Move{
+                            translation: Some(Translation{
+                                from: knight,
+                                to: valid_knight_attack,
+                            }),
+                            promotion: None,
+                            capture: None,
+                            castling: None,
+                            en_passant: None, 
+                        });
+                    }
+                }
+            }
+        }
 
-            //generate queen moves
-            let queen_bb = self.pieces[us.0][QUEEN];
-            let queen_squares = queen_bb.get_squares();
+        //generate bishop moves
+        let bishop_bb = self.pieces[us][BISHOP];
+        let bishop_squares = bishop_bb.squares();
 
-            for queen_square in queen_squares{
-                let queen_attacks = get_queen_attacks(queen_square, occupancy) & !our_occupancy;
-                let valid_queen_attacks: Bitboard;
+        for bishop_square in bishop_squares{
+            let bishop_attacks = get_bishop_attacks(bishop_square, occupancy) & !our_occupancy;
+            let current_bishop_bb = bishop_square.to_bitboard();
+
+            //if bishop is pinned horizontally or vertically, skip generating bishop moves
+            if our_pins.pins_h & current_bishop_bb == 0 && our_pins.pins_v & current_bishop_bb == 0{
+                let mut valid_bishop_attacks: Bitboard;
                 
-                //if queen is pinned in any direction, filter out moves that are not along the pin
-                if our_pins.pins_h & queen_bb != 0{
-                    let queen_path = DIRECTIONAL_MAP_RANK[queen_square as usize];
-                    valid_queen_attacks = queen_attacks & queen_path;
+                //if bishop is pinned diagonally, filter out moves that are not along the pin
+                if our_pins.pins_dd & current_bishop_bb != 0{
+                    let bishop_path = DIRECTIONAL_MAP_DD[bishop_square as usize];
+                    valid_bishop_attacks = bishop_attacks & bishop_path;
+                }
+                else if our_pins.pins_da & current_bishop_bb != 0{
+                    let bishop_path = DIRECTIONAL_MAP_DA[bishop_square as usize];
+                    valid_bishop_attacks = bishop_attacks & bishop_path;
                 }
-                else if our_pins.pins_v & queen_bb != 0{
-                    let queen_path = DIRECTIONAL_MAP_FILE[queen_square as usize];
-                    valid_queen_attacks = queen_attacks & queen_path;
+                //bishop is not pinned
+                else{
+                    valid_bishop_attacks = bishop_attacks;
                 }
-                else if our_pins.pins_dd & queen_bb != 0{
-                    let queen_path = DIRECTIONAL_MAP_DD[queen_square as usize];
-                    valid_queen_attacks = queen_attacks & queen_path;
+
+                valid_bishop_attacks &= !our_occupancy & targets;
+
+                for valid_bishop_attack in valid_bishop_attacks.squares(){
+                    let valid_bishop_attack_bb = valid_bishop_attack.to_bitboard();
+                    if valid_bishop_attack_bb & their_occupancy != 0{
+                        //generate bishop captures
+                        moves.push(Move{
+                            translation: Some(Translation{
+                                from: bishop_square,
+                                to: valid_bishop_attack,
+                            }),
+                            promotion: None,
+                            capture: self.pieces[them].get_piece_type_at_square(valid_bishop_attack_bb),
+                            castling: None,
+                            en_passant: None, 
+                        });
+                    }
+                    else{
+                        //generate bishop moves
+                        moves.push(Move{
+                            translation: Some(Translation{
+                                from: bishop_square,
+                                to: valid_bishop_attack,
+                            }),
+                            promotion: None,
+                            capture: None,
+                            castling: None,
+                            en_passant: None, 
+                        });
+                    }
+                }
+            }
+        }
+
+        //generate rook moves
+        let rook_bb = self.pieces[us][ROOK];
+
+        let rook_squares = rook_bb.squares();
+
+        for rook_square in rook_squares{
+            let rook_attacks = get_rook_attacks(rook_square, occupancy) & !our_occupancy;
+
+            let current_rook_bb = rook_square.to_bitboard();
+
+            //if rook is pinned diagonally, skip generating rook moves
+            if our_pins.pins_dd & current_rook_bb == 0 && our_pins.pins_da & current_rook_bb == 0{
+                let valid_rook_attacks: Bitboard;
+                
+                //if rook is pinned horizontally or vertically, filter out moves that are not along the pin
+                if our_pins.pins_h & current_rook_bb != 0{
+                    let rook_path = DIRECTIONAL_MAP_RANK[rook_square as usize];
+                    valid_rook_attacks = rook_attacks & rook_path;
                 }
-                else if our_pins.pins_da & queen_bb != 0{
-                    let queen_path = DIRECTIONAL_MAP_DA[queen_square as usize];
-                    valid_queen_attacks = queen_attacks & queen_path;
+                else if our_pins.pins_v & current_rook_bb != 0{
+                    let rook_path = DIRECTIONAL_MAP_FILE[rook_square as usize];
+                    valid_rook_attacks = rook_attacks & rook_path;
                 }
+                //rook is not pinned
                 else{
-                    valid_queen_attacks = queen_attacks;
+                    valid_rook_attacks = rook_attacks;
                 }
 
-                for valid_queen_attack in valid_queen_attacks.get_squares(){
-                    let valid_queen_attack_bb = valid_queen_attack.to_bitboard();
+                for valid_rook_attack in (valid_rook_attacks & targets).squares(){
+                    let valid_rook_attack_bb = valid_rook_attack.to_bitboard();
 
-                    if valid_queen_attack_bb & their_occupancy != 0{
-                        //generate queen captures
+                    if valid_rook_attack_bb & their_occupancy != 0{
+                        //generate rook captures
                         moves.push(Move{
                             translation: Some(Translation{
-                                from: queen_square,
-                                to: valid_queen_attack,
+                                from: rook_square,
+                                to: valid_rook_attack,
                             }),
                             promotion: None,
-                            capture: self.pieces[them.0].get_piece_type_at_square(valid_queen_attack_bb),
+                            capture: self.pieces[them].get_piece_type_at_square(valid_rook_attack_bb),
                             castling: None,
                             en_passant: None, 
                         });
                     }
                     else{
-                        //generate queen moves
+                        //generate rook moves
                         moves.push(Move{
                             translation: Some(Translation{
-                                from: queen_square,
-                                to: valid_queen_attack,
+                                from: rook_square,
+                                to: valid_rook_attack,
                             }),
                             promotion: None,
                             capture: None,
@@ -1387,36 +2555,60 @@ impl Position{
                     }
                 }
             }
+        }
+
+        //generate queen moves
+        let queen_bb = self.pieces[us][QUEEN];
+        let queen_squares = queen_bb.squares();
+
+        for queen_square in queen_squares{
+            let current_queen_bb = queen_square.to_bitboard();
+            let queen_attacks = get_queen_attacks(queen_square, occupancy) & !our_occupancy;
+            let valid_queen_attacks: Bitboard;
             
-            //generate king moves
-            let king_bb = self.pieces[us.0][KING];
-            let king_square = king_bb.get_squares()[0];
-
-            let king_attacks = get_king_attacks(king_square) & !our_occupancy;
-            let valid_king_attacks: Bitboard;
-            valid_king_attacks = king_attacks & !their_attacks_without_our_king.all();
-
-            for valid_king_attack in valid_king_attacks.get_squares(){
-                let valid_king_attack_bb = valid_king_attack.to_bitboard();
-                if valid_king_attack_bb & their_occupancy != 0{
-                    //generate king captures
+            //if queen is pinned in any direction, filter out moves that are not along the pin
+            if our_pins.pins_h & current_queen_bb != 0{
+                let queen_path = DIRECTIONAL_MAP_RANK[queen_square as usize];
+                valid_queen_attacks = queen_attacks & queen_path;
+            }
+            else if our_pins.pins_v & current_queen_bb != 0{
+                let queen_path = DIRECTIONAL_MAP_FILE[queen_square as usize];
+                valid_queen_attacks = queen_attacks & queen_path;
+            }
+            else if our_pins.pins_dd & current_queen_bb != 0{
+                let queen_path = DIRECTIONAL_MAP_DD[queen_square as usize];
+                valid_queen_attacks = queen_attacks & queen_path;
+            }
+            else if our_pins.pins_da & current_queen_bb != 0{
+                let queen_path = DIRECTIONAL_MAP_DA[queen_square as usize];
+                valid_queen_attacks = queen_attacks & queen_path;
+            }
+            else{
+                valid_queen_attacks = queen_attacks;
+            }
+
+            for valid_queen_attack in (valid_queen_attacks & targets).squares(){
+                let valid_queen_attack_bb = valid_queen_attack.to_bitboard();
+
+                if valid_queen_attack_bb & their_occupancy != 0{
+                    //generate queen captures
                     moves.push(Move{
                         translation: Some(Translation{
-                            from: king_square,
-                            to: valid_king_attack,
+                            from: queen_square,
+                            to: valid_queen_attack,
                         }),
                         promotion: None,
-                        capture: self.pieces[them.0].get_piece_type_at_square(valid_king_attack_bb),
+                        capture: self.pieces[them].get_piece_type_at_square(valid_queen_attack_bb),
                         castling: None,
                         en_passant: None, 
                     });
                 }
                 else{
-                    //generate king moves
+                    //generate queen moves
                     moves.push(Move{
                         translation: Some(Translation{
-                            from: king_square,
-                            to: valid_king_attack,
+                            from: queen_square,
+                            to: valid_queen_attack,
                         }),
                         promotion: None,
                         capture: None,
@@ -1425,354 +2617,341 @@ impl Position{
                     });
                 }
             }
-            if moves.len() == 0{
-                let note = format!("No moves found for {}", us);
-                return PositionEvaluation{
-                    game_state: GameState::DRAW,
-                    state_note: Some(note),
-                    moves,
-                    score
-                }
+        }
+        
+        //generate king moves
+        let king_bb = self.pieces[us][KING];
+        let king_square = king_bb.to_square();
+
+        let king_attacks = get_king_attacks(king_square) & !our_occupancy;
+        let valid_king_attacks: Bitboard;
+        valid_king_attacks = king_attacks & !their_attacks_without_our_king.all() & targets;
+
+        for valid_king_attack in valid_king_attacks.squares(){
+            let valid_king_attack_bb = valid_king_attack.to_bitboard();
+            if valid_king_attack_bb & their_occupancy != 0{
+                //generate king captures
+                moves.push(Move{
+                    translation: Some(Translation{
+                        from: king_square,
+                        to: valid_king_attack,
+                    }),
+                    promotion: None,
+                    capture: self.pieces[them].get_piece_type_at_square(valid_king_attack_bb),
+                    castling: None,
+                    en_passant: None, 
+                });
+            }
+            else{
+                //generate king moves
+                moves.push(Move{
+                    translation: Some(Translation{
+                        from: king_square,
+                        to: valid_king_attack,
+                    }),
+                    promotion: None,
+                    capture: None,
+                    castling: None,
+                    en_passant: None, 
+                });
             }
         }
-        else{
-            game_state = GameState::CHECK;
 
-            //double check, only king must move
-            if their_attacks.double_check{
-                let available_squares: Bitboard = (get_king_attacks(our_king_square) & !our_occupancy) & !their_attacks_without_our_king.all();
-                //checkmate?
-                if available_squares == 0{
-                    score = if us == Side::WHITE { Some(SCORE_BLACK_WINS) } else { Some(SCORE_WHITE_WINS) };
-                    return PositionEvaluation{
-                        game_state: GameState::CHECKMATE,
-                        state_note: Some("No moves after check.".to_string()),
-                        moves,
-                        score
-                    }
-                }
-                //we can still play for one more move at least
-                for square in available_squares.get_squares(){
-                    let square_bb = square.to_bitboard();
-                    if square_bb & their_occupancy != 0{
-                        //find which piece the king is attacking
-                        let mut piece = 0;
-                        for i in 0..6{
-                            let pieces_bb = self.pieces[them.0][i];
-                            if pieces_bb & square_bb != 0{
-                                piece = i;
-                                break;
-                            }
-                        }
-                        //add capture move
-                        moves.push(Move{
-                            translation: Some(Translation { from: our_king_square, to: square }),
-                            promotion: None,
-                            capture: Some(piece),
-                            castling:None,
-                            en_passant: None, 
-                        });
-                    }
-                    else{
-                        moves.push(Move{
-                            translation: Some(Translation { from: our_king_square, to: square }),
-                            promotion: None,
-                            capture: None,
-                            castling:None,
-                            en_passant: None, 
-                        });
-                    }
+        return moves;
+    }
 
-                }
-            }   
-            //single checker
-            else{
-                let checker = their_attacks.check.unwrap();
-                let checker_square = checker.square;
-                let checker_square_bb = checker_square.to_bitboard();
-                let checker_piece = checker.piece;
+    //all legal moves for the side to move, without the scoring and draw checks evaluate does
+    pub fn legal_moves(&self) -> Vec<Move>{
+        return self.generate_moves(false);
+    }
 
-                let mut slider_squares: Bitboard = Bitboard::EMPTY;
+    //leaf count of the legal move tree to `depth`, checked against published counts to validate the move generator
+    pub fn perft(&self, depth: u32) -> u64{
+        if depth == 0{
+            return 1;
+        }
+        let moves = self.legal_moves();
+        if depth == 1{
+            return moves.len() as u64;
+        }
 
-                if checker_piece == BISHOP || checker_piece == ROOK || checker_piece == QUEEN{
-                    //find the squares between the king and the checker
-                    slider_squares = get_ray_between_squares(our_king_square, checker_square);
-                }
+        let mut position = *self;
+        let mut nodes = 0;
+        for m in moves{
+            let undo = position.make_move_mut(m).unwrap();
+            nodes += position.perft(depth - 1);
+            position.unmake_move(undo);
+        }
+        return nodes;
+    }
 
-                let mut pin_path: Bitboard;
+    //perft with the root moves spread over the rayon pool, each subtree is counted sequentially
+    pub fn perft_parallel(&self, depth: u32) -> u64{
+        if depth == 0{
+            return 1;
+        }
+        return self.legal_moves().into_par_iter().map(|m| self.make_move(m).unwrap().perft(depth - 1)).sum();
+    }
 
-                for piece in 0..6{
-                    let piece_bb = self.pieces[us.0][piece];
+    //legal captures, en passant and promotions, the tactical moves quiescence looks at
+    pub fn capture_moves(&self) -> Vec<Move>{
+        return self.generate_moves(true);
+    }
 
-                    for square in piece_bb.get_squares(){
+    //stops at the first legal move it finds, king moves first, then unpinned pieces, then full generation
+    pub fn has_legal_move(&self) -> bool{
+        let us = self.side_to_move;
+        let them = !us;
 
-                        pin_path = Bitboard::FULL;
+        let our_occupancy = self.pieces[us].occupancy();
+        let their_occupancy = self.pieces[them].occupancy();
+        let occupancy = our_occupancy | their_occupancy;
 
-                        if our_pins.all() & square.to_bitboard() != 0{
-                            if piece_bb & our_pins.pins_h != 0{
-                                pin_path = DIRECTIONAL_MAP_RANK[square as usize];
-                            }
-                            else if piece_bb & our_pins.pins_v != 0{
-                                pin_path = DIRECTIONAL_MAP_RANK[square as usize];
-                            }
-                            else if piece_bb & our_pins.pins_da != 0{
-                                pin_path = DIRECTIONAL_MAP_DA[square as usize];
-                            }
-                            else if piece_bb & our_pins.pins_dd != 0{
-                                pin_path = DIRECTIONAL_MAP_DD[square as usize];
-                            }    
-                        }
+        let our_king: Bitboard = self.pieces[us][KING];
+        let our_king_square = our_king.to_square();
 
-                        if piece == PAWN{
-                            let pawn_attacks = (get_pawn_attacks(us, square) & !our_occupancy) & pin_path;
-                            let pawn_move_bb = (get_pawn_moves(us, square, occupancy) & !our_occupancy) & pin_path;
-                            let pawn_move = (pawn_move_bb & slider_squares).to_square();
-
-                            if pawn_attacks & checker_square_bb != 0{
-                                //pawn capture
-                                
-                                //generate promotion captures
-                                if (pawn_attacks & RANK_1BB != 0) || (pawn_attacks & RANK_8BB != 0){
-                                    for promotion in [QUEEN, ROOK, BISHOP, KNIGHT]{
-                                        moves.push(Move{
-                                            translation: Some(Translation{
-                                                from: square,
-                                                to: checker_square,
-                                            }),
-                                            promotion: Some(promotion),
-                                            capture: Some(checker_piece),
-                                            castling: None,
-                                            en_passant: None, 
-                                        });
-                                    }
-                                }
-                                else{
-                                    moves.push(Move{
-                                        translation: Some(Translation{
-                                            from: square,
-                                            to: checker_square,
-                                        }),
-                                        promotion: None,
-                                        capture: Some(checker_piece),
-                                        castling: None,
-                                        en_passant: None, 
-                                    });
-                                }
-                            }
-                            if pawn_move != Square::NONE{
-                                //generate promotion moves
-                                if (pawn_move_bb & RANK_1BB != 0) || (pawn_move_bb & RANK_8BB != 0){
-                                    for promotion in [QUEEN, ROOK, BISHOP, KNIGHT]{
-                                        moves.push(Move{
-                                            translation: Some(Translation{
-                                                from: square,
-                                                to: pawn_move,
-                                            }),
-                                            promotion: Some(promotion),
-                                            capture: None,
-                                            castling: None,
-                                            en_passant: None, 
-                                        });
-                                    }
-                                }
-                                else{
-                                    moves.push(Move{
-                                        translation: Some(Translation{
-                                            from: square,
-                                            to: pawn_move,
-                                        }),
-                                        promotion: None,
-                                        capture: None,
-                                        castling: None,
-                                        en_passant: None, 
-                                    });
-                                }
-                            }
-                            if self.en_passant_square.is_some(){
-                                //en passant
-                                let en_passant_square = self.en_passant_square.unwrap();
-                                let en_passant_square_bb = en_passant_square.to_bitboard();
-                                let enemy_pawn_square = if us == Side::WHITE { en_passant_square - 8 } else { en_passant_square + 8 };
-                                let enemy_pawn_square_bb = enemy_pawn_square.to_bitboard();
-
-                                if pawn_attacks & en_passant_square_bb != 0{
-                                    let en_passant_eats_checker = enemy_pawn_square_bb & checker_square_bb != 0;
-                                    let en_passant_blocks_checker = en_passant_square_bb & slider_squares != 0;
-                                    if en_passant_eats_checker || en_passant_blocks_checker{
-                                        moves.push(Move{
-                                            translation: Some(Translation { from: square, to: en_passant_square }),
-                                            promotion: None,
-                                            capture: Some(PAWN),
-                                            castling:None,
-                                            en_passant: Some(en_passant_square), 
-                                        });
-                                    }
-                                }
-                            }   
-                        }
-                        else if piece == KNIGHT{
-                            let knight_attacks = (get_knight_attacks(square) & !our_occupancy) & pin_path;
+        let their_attacks_without_our_king = self.get_side_attacks(them, occupancy & !our_king);
+        if get_king_attacks(our_king_square) & !our_occupancy & !their_attacks_without_our_king.all() != 0{
+            return true;
+        }
+
+        let their_attacks = self.get_side_attacks(them, occupancy);
+        let our_pins = self.get_absolute_pins_for_side(their_attacks, occupancy, our_occupancy, our_king_square);
+        if their_attacks.check.is_some(){
+            return !self.get_evasion_moves(their_attacks, their_attacks_without_our_king, our_pins, occupancy, our_occupancy, their_occupancy, our_king_square).is_empty();
+        }
+
+        //out of check any pseudo legal move of an unpinned piece is legal, en passant is left to the generator
+        let free = !our_pins.all();
+        for square in (self.pieces[us][PAWN] & free).squares(){
+            if get_pawn_moves(us, square, occupancy) | (get_pawn_attacks(us, square) & their_occupancy) != 0{
+                return true;
+            }
+        }
+        for square in (self.pieces[us][KNIGHT] & free).squares(){
+            if get_knight_attacks(square) & !our_occupancy != 0{
+                return true;
+            }
+        }
+        for square in ((self.pieces[us][BISHOP] | self.pieces[us][QUEEN]) & free).squares(){
+            if get_bishop_attacks(square, occupancy) & !our_occupancy != 0{
+                return true;
+            }
+        }
+        for square in ((self.pieces[us][ROOK] | self.pieces[us][QUEEN]) & free).squares(){
+            if get_rook_attacks(square, occupancy) & !our_occupancy != 0{
+                return true;
+            }
+        }
+
+        return !self.get_non_evasion_moves(their_attacks, their_attacks_without_our_king, our_pins, occupancy, our_occupancy, their_occupancy, our_king_square, false).is_empty();
+    }
+
+    fn generate_moves(&self, captures_only: bool) -> Vec<Move>{
+        let us = self.side_to_move;
+        let them = !us;
+
+        let our_occupancy = self.pieces[us].occupancy();
+        let their_occupancy = self.pieces[them].occupancy();
+        let occupancy = our_occupancy | their_occupancy;
+
+        let our_king: Bitboard = self.pieces[us][KING];
+        let our_king_square = our_king.to_square();
+
+        let their_attacks = self.get_side_attacks(them, occupancy);
+        let their_attacks_without_our_king = self.get_side_attacks(them, occupancy & !our_king);
+        let our_pins = self.get_absolute_pins_for_side(their_attacks, occupancy, our_occupancy, our_king_square);
+
+        if their_attacks.check.is_none(){
+            return self.get_non_evasion_moves(their_attacks, their_attacks_without_our_king, our_pins, occupancy, our_occupancy, their_occupancy, our_king_square, captures_only);
+        }
+
+        //evasions are few, filter them rather than generating twice
+        let evasions = self.get_evasion_moves(their_attacks, their_attacks_without_our_king, our_pins, occupancy, our_occupancy, their_occupancy, our_king_square);
+        if captures_only{
+            return evasions.into_iter().filter(|m| !m.is_quiet()).collect();
+        }
+        return evasions;
+    }
+
+    //legal check evasions for the side to move, empty when the side to move is not in check
+    pub fn evasion_moves(&self) -> Vec<Move>{
+        let us = self.side_to_move;
+        let them = !us;
+
+        let our_occupancy = self.pieces[us].occupancy();
+        let their_occupancy = self.pieces[them].occupancy();
+        let occupancy = our_occupancy | their_occupancy;
+
+        let our_king: Bitboard = self.pieces[us][KING];
+        let our_king_square = our_king.to_square();
+
+        let their_attacks = self.get_side_attacks(them, occupancy);
+        if their_attacks.check.is_none(){
+            return Vec::new();
+        }
+
+        let their_attacks_without_our_king = self.get_side_attacks(them, occupancy & !our_king);
+        let our_pins = self.get_absolute_pins_for_side(their_attacks, occupancy, our_occupancy, our_king_square);
+
+        return self.get_evasion_moves(their_attacks, their_attacks_without_our_king, our_pins, occupancy, our_occupancy, their_occupancy, our_king_square);
+    }
+
+    //evaluate with the hashes of the positions played before this one, the current position is counted once
+    pub fn evaluate_with_history(self, history: &ZobristMoveStack) -> PositionEvaluation{
+        //check for 3-fold repetition
+        if history.get_repetitions(self.zobrist_hash) + 1 >= 3{
+            return PositionEvaluation{
+                moves: Vec::new(),
+                game_state: GameState::DRAW,
+                state_note: Some("Three-fold, repetition.".to_string()),
+                draw_reason: Some(DrawReason::ThreefoldRepetition),
+                score: Some(0.0)
+            }
+        }
+
+        return self.evaluate();
+    }
+
+    //evaluate's score from the side to move's point of view, draws, mate and stalemate are checked every time
+    //and only the static score comes from the cache
+    pub fn evaluate_relative(&self, cache: &mut EvalCache) -> f32{
+        if self.check_draw().is_some(){
+            return 0.0;
+        }
+        if self.legal_moves().is_empty(){
+            return if self.is_in_check() { -SCORE_WHITE_WINS } else { 0.0 };
+        }
+        return self.cached_static_eval(cache);
+    }
+
+    //static_eval from the side to move's point of view, looked up in the cache by zobrist hash. the hash doesn't cover
+    //the halfmove clock, so nothing decided by the draw rules can be stored
+    pub fn cached_static_eval(&self, cache: &mut EvalCache) -> f32{
+        if let Some(score) = cache.probe(self.zobrist_hash){
+            return score;
+        }
+
+        let score = self.static_eval();
+        let relative_score = if self.side_to_move == Side::WHITE { score } else { -score };
+        cache.store(self.zobrist_hash, relative_score);
+
+        return relative_score;
+    }
+
+    //the score evaluate gives a position that isn't over, without the draw checks or generating any moves
+    pub fn static_eval(&self) -> f32{
+        return self.static_eval_with(&EvalParams::default());
+    }
+
+    pub fn static_eval_with(&self, params: &EvalParams) -> f32{
+        let us = self.side_to_move;
+        let them = !us;
+
+        let our_occupancy = self.pieces[us].occupancy();
+        let their_occupancy = self.pieces[them].occupancy();
+        let occupancy = our_occupancy | their_occupancy;
+
+        let our_attacks = self.get_side_attacks(us, occupancy);
+        let their_attacks = self.get_side_attacks(them, occupancy);
+
+        let our_pins = self.get_absolute_pins_for_side(their_attacks, occupancy, our_occupancy, self.pieces[us][KING].to_square());
+        let their_pins = self.get_absolute_pins_for_side(our_attacks, occupancy, their_occupancy, self.pieces[them][KING].to_square());
+
+        return self.score_terms(params, our_attacks, their_attacks, our_pins, their_pins);
+    }
+
+    //material, piece-square and pawn terms plus the pin, mobility and trapped piece terms from the attack sweep
+    fn score_terms(&self, params: &EvalParams, our_attacks: SideAttacks, their_attacks: SideAttacks, our_pins: AbsolutePins, their_pins: AbsolutePins) -> f32{
+        //the score is from white's point of view whoever is to move, so sort the sweep results by colour first
+        let (white_attacks, black_attacks) = if self.side_to_move == Side::WHITE { (our_attacks, their_attacks) } else { (their_attacks, our_attacks) };
+        let (white_pins, black_pins) = if self.side_to_move == Side::WHITE { (our_pins, their_pins) } else { (their_pins, our_pins) };
+
+        let pinscore = (black_pins.all().count_ones() as f32 - white_pins.all().count_ones() as f32) * params.pin_multiplier;
+        let movescore = (white_attacks.all().count_ones() as f32 - black_attacks.all().count_ones() as f32) * params.mobility_multiplier;
+
+        let trappedscore = (self.trapped_pieces(Side::BLACK).count_ones() as f32 - self.trapped_pieces(Side::WHITE).count_ones() as f32) * params.trapped_piece_penalty;
+
+        let kingscore = self.king_safety_penalty(Side::BLACK, white_attacks.all(), params) - self.king_safety_penalty(Side::WHITE, black_attacks.all(), params);
+
+        return self.get_score(params) + pinscore + movescore + trappedscore + kingscore;
+    }
+
+    pub fn evaluate(self) -> PositionEvaluation{
+        return self.evaluate_with(&EvalParams::default());
+    }
+
+    pub fn evaluate_with(&self, params: &EvalParams) -> PositionEvaluation{
+        let mut moves: Vec<Move> = Vec::new();
+
+        //just return if it's a draw
+        if let Some(reason) = self.check_draw(){
+            return PositionEvaluation{
+                moves,
+                game_state: GameState::DRAW,
+                state_note: Some(format!("{}.", reason)),
+                draw_reason: Some(reason),
+                score: Some(0.0)
+            }
+        }
 
+        let mut game_state: GameState = GameState::ONGOING;
 
-                            if knight_attacks & checker_square_bb != 0{
-                                //knight captures checker
-                                moves.push(Move{
-                                    translation: Some(Translation { from: square, to: checker_square }),
-                                    promotion: None,
-                                    capture: Some(checker_piece),
-                                    castling:None,
-                                    en_passant: None, 
-                                });
-                            }
-                            //check if knight can move to block the check
-                            let valid_moves = (knight_attacks & slider_squares) & pin_path;
+        let us = self.side_to_move;
+        let them = !us;
 
-                            if valid_moves != 0{
-                                for valid_move in valid_moves.get_squares(){
-                                    moves.push(Move{
-                                        translation: Some(Translation { from: square, to: valid_move }),
-                                        promotion: None,
-                                        capture: None,
-                                        castling:None,
-                                        en_passant: None, 
-                                    });
-                                }
-                            }
-                        }
-                        else if piece == BISHOP{
-                            let bishop_attacks = (get_bishop_attacks(square, occupancy) & !our_occupancy) & pin_path;
+        let our_occupancy = self.pieces[us].occupancy();
+        let their_occupancy = self.pieces[them].occupancy();
+        let occupancy = our_occupancy | their_occupancy;
 
-                            if bishop_attacks & checker_square_bb != 0{
-                                //bishop captures checker
-                                moves.push(Move{
-                                    translation: Some(Translation { from: square, to: checker_square }),
-                                    promotion: None,
-                                    capture: Some(checker_piece),
-                                    castling:None,
-                                    en_passant: None, 
-                                });
-                            }
-                            let bishop_moves = (bishop_attacks & slider_squares) & pin_path;
+        let our_king: Bitboard = self.pieces[us][KING];
+        let our_king_square = our_king.to_square();
 
-                            if bishop_moves != 0{
-                                for bishop_move in bishop_moves.get_squares(){
-                                    moves.push(Move{
-                                        translation: Some(Translation { from: square, to: bishop_move }),
-                                        promotion: None,
-                                        capture: None,
-                                        castling:None,
-                                        en_passant: None, 
-                                    });
-                                }
-                            }
-                        }
-                        else if piece == ROOK{
+        let their_king = self.pieces[them][KING];
+        let their_king_square = their_king.to_square();
 
-                            let rook_attacks = (get_rook_attacks(square, occupancy) & !our_occupancy) & pin_path;
-                            
-                            if rook_attacks & checker_square_bb != 0{
-                                //rook captures checker
-                                moves.push(Move{
-                                    translation: Some(Translation { from: square, to: checker_square }),
-                                    promotion: None,
-                                    capture: Some(checker_piece),
-                                    castling:None,
-                                    en_passant: None, 
-                                });
-                            }
-                            let rook_moves = (rook_attacks & slider_squares) & pin_path;
+        let occupancy_without_our_king = occupancy & !our_king;
 
-                            if rook_moves != 0{
-                                for rook_move in rook_moves.get_squares(){
-                                    moves.push(Move{
-                                        translation: Some(Translation { from: square, to: rook_move }),
-                                        promotion: None,
-                                        capture: None,
-                                        castling:None,
-                                        en_passant: None, 
-                                    });
-                                }
-                            }
-                        }
-                        else if piece == QUEEN{
-                            let queen_attacks = (get_queen_attacks(square, occupancy) & !our_occupancy) & pin_path;
+        let their_attacks = self.get_side_attacks(them, occupancy);
+        let their_attacks_without_our_king = self.get_side_attacks(them, occupancy_without_our_king);
 
-                            if queen_attacks & checker_square_bb != 0{
-                                //queen captures checker
-                                moves.push(Move{
-                                    translation: Some(Translation { from: square, to: checker_square }),
-                                    promotion: None,
-                                    capture: Some(checker_piece),
-                                    castling:None,
-                                    en_passant: None, 
-                                });
-                            }
+        let our_attacks = self.get_side_attacks(us, occupancy);
 
-                            let queen_moves = (queen_attacks & slider_squares) & pin_path;
+        let our_pins = self.get_absolute_pins_for_side(their_attacks, occupancy, our_occupancy, our_king_square);
+        let their_pins = self.get_absolute_pins_for_side(our_attacks, occupancy, their_occupancy, their_king_square);
 
-                            if queen_moves != 0{
-                                for queen_move in queen_moves.get_squares(){
-                                    moves.push(Move{
-                                        translation: Some(Translation { from: square, to: queen_move }),
-                                        promotion: None,
-                                        capture: None,
-                                        castling:None,
-                                        en_passant: None, 
-                                    });
-                                }
-                            }
-                        }
-                        else if piece == KING{
+        let mut score = Some(self.score_terms(params, our_attacks, their_attacks, our_pins, their_pins));
 
-                            let mut valid_attacks = get_king_attacks(square) & !our_occupancy;
-                            valid_attacks &= !their_attacks_without_our_king.all();
+        //make sure king is not in check
+        if their_attacks.check.is_none(){
+            moves = self.get_non_evasion_moves(their_attacks, their_attacks_without_our_king, our_pins, occupancy, our_occupancy, their_occupancy, our_king_square, false);
 
-                            for attack in valid_attacks.get_squares(){
-                                let attack_bb = attack.to_bitboard();
-                                if attack_bb & checker_square_bb != 0{
-                                    moves.push(Move{
-                                        translation: Some(Translation { from: square, to: attack }),
-                                        promotion: None,
-                                        capture: Some(checker_piece),
-                                        castling:None,
-                                        en_passant: None, 
-                                    });
-                                }
-                                else if attack_bb & their_occupancy != 0{
-                                    //find which piece the king is attacking
-                                    let piece = self.pieces[them.0].get_piece_type_at_square(attack_bb);
-                                    //king eats the piece
-                                    moves.push(Move{
-                                        translation: Some(Translation { from: square, to: attack }),
-                                        promotion: None,
-                                        capture: piece,
-                                        castling:None,
-                                        en_passant: None, 
-                                    });
-                                }
-                                else{
-                                    //normal king move
-                                    moves.push(Move{
-                                        translation: Some(Translation { from: square, to: attack }),
-                                        promotion: None,
-                                        capture: None,
-                                        castling:None,
-                                        en_passant: None, 
-                                    });
-                                }
-                            }
-                        }   
-                    }
-                }    
-                //no moves available after check
-                if moves.is_empty(){
-                    score = if us == Side::WHITE { Some(SCORE_BLACK_WINS) } else { Some(SCORE_WHITE_WINS) };
-                    return PositionEvaluation{
-                        game_state: GameState::CHECKMATE,
-                        state_note: Some("No moves after check.".to_string()),
-                        moves,
-                        score
-                    }
+            if moves.len() == 0{
+                //stalemate is a draw whatever the material says
+                let note = format!("No moves found for {}", us);
+                return PositionEvaluation{
+                    game_state: GameState::DRAW,
+                    state_note: Some(note),
+                    draw_reason: Some(DrawReason::Stalemate),
+                    moves,
+                    score: Some(0.0)
+                }
+            }
+        }
+        else{
+            game_state = GameState::CHECK;
+
+            moves = self.get_evasion_moves(their_attacks, their_attacks_without_our_king, our_pins, occupancy, our_occupancy, their_occupancy, our_king_square);
+
+            //no moves available after check
+            if moves.is_empty(){
+                score = if us == Side::WHITE { Some(SCORE_BLACK_WINS) } else { Some(SCORE_WHITE_WINS) };
+                return PositionEvaluation{
+                    game_state: GameState::CHECKMATE,
+                    state_note: Some("No moves after check.".to_string()),
+                    draw_reason: None,
+                    moves,
+                    score
                 }
             }
         }
@@ -1780,40 +2959,193 @@ impl Position{
         return PositionEvaluation{
             game_state,
             state_note: None,
+            draw_reason: None,
             moves,
             score
         };
     }
 
-    pub fn print_position_pieces(&self){
-        println!("White Pieces:");
-        for piece in 0..6{
-            let piece_type = piece;
-            let piece_bb = self.pieces[0][piece_type];
-            let piece_num = piece_bb.count_ones();
-            println!("{}: {}", PIECES[piece_type], piece_num);
+    //search the tree to `depth` plies, a finished root reports its own result without a move
+    pub fn search(&self, depth: u8) -> SearchResult{
+        let eval = self.evaluate();
+        if eval.game_state == GameState::CHECKMATE || eval.game_state == GameState::DRAW{
+            return SearchResult{
+                best_move: None,
+                score: eval.score.unwrap_or(0.0),
+                game_state: eval.game_state,
+            };
         }
-        println!("Black Pieces:");
-        for piece in 0..6{
-            let piece_type = piece;
-            let piece_bb = self.pieces[1][piece_type];
-            let piece_num = piece_bb.count_ones();
-            println!("{}: {}", PIECES[piece_type], piece_num);
+
+        let mut tree = PositionTree::new(*self);
+        let best_moves = tree.expand_to_depth(depth.max(1), ExpandStyle::DEFAULT, self.side_to_move);
+
+        return SearchResult{
+            best_move: best_moves.first().cloned(),
+            score: tree.get_score(tree.root).unwrap_or(0.0),
+            game_state: eval.game_state,
+        };
+    }
+
+    //find the legal move between two squares and play it, castling, en passant and captures are resolved from the move list
+    pub fn make_move_coords(&self, from: Square, to: Square, promotion: Option<Piece>) -> std::result::Result<Position, MoveError>{
+        let legal_move = self.legal_moves().into_iter().find(|m| {
+            let translation = m.translation.unwrap();
+            translation.from == from && translation.to == to && m.promotion == promotion
+        });
+
+        return match legal_move{
+            Some(m) => self.make_move(m).ok_or(MoveError::Illegal),
+            None => Err(MoveError::Illegal),
+        };
+    }
+
+    //the legal move making the same transition as `m`, with its capture and en passant fields filled in
+    pub fn find_legal_move(&self, m: &Move) -> Option<Move>{
+        return self.legal_moves().into_iter().find(|legal| legal.same_transition(m));
+    }
+
+    pub fn print_position_pieces(&self){
+        for side in Side::iter(){
+            println!("{} Pieces:", side);
+            for piece in 0..6{
+                let piece_type = piece;
+                let piece_bb = self.pieces[side][piece_type];
+                let piece_num = piece_bb.count_ones();
+                println!("{}: {}", PIECES[piece_type], piece_num);
+            }
         }
     }
 
     pub fn make_move(&self, m: Move) -> Option<Position>{
-        let mut new_position = self.clone();
-        
+        let mut new_position = *self;
+        new_position.make_move_mut(m)?;
+        return Some(new_position);
+    }
+
+    //flip a piece on or off and keep the zobrist hash in step
+    fn toggle_piece(&mut self, side: Side, piece: Piece, square: Square){
+        self.pieces[side][piece] ^= square.to_bitboard();
+        self.zobrist_hash ^= ZOBRIST.piece_hashes[side.index()][piece][square as usize];
+    }
+
+    //a double push leaves an en passant square only when an enemy pawn stands next to it
+    fn en_passant_after_push(&self, us: Side, translation: Translation) -> Option<Square>{
+        let double_push = translation.to == translation.from + 16 || translation.to + 16 == translation.from;
+        if !double_push{
+            return None;
+        }
+
+        let to_side_bb = (translation.to.to_bitboard() << 1 & NOT_FILE_ABB) | (translation.to.to_bitboard() >> 1 & NOT_FILE_HBB);
+        if to_side_bb & self.pieces[!us][PAWN] == 0{
+            return None;
+        }
+
+        return if us == Side::WHITE { Some(translation.to - 8) } else { Some(translation.to + 8) };
+    }
+
+    //zobrist hash of the position after `m` without building it, for move ordering and prefetching
+    pub fn key_after(&self, m: Move) -> u64{
+        let us = self.side_to_move;
+        let them = !us;
+        let translation = m.translation.unwrap();
+        let piece_hashes = &ZOBRIST.piece_hashes;
+
+        let mut key = self.zobrist_hash ^ ZOBRIST.side_to_move_hash;
+        if let Some(square) = self.en_passant_square{
+            key ^= ZOBRIST.en_passant_hashes[square as usize];
+        }
+
+        let from_piece = if let Some(direction) = m.castling{
+            let (king_from, king_to, rook_from, rook_to) = self.castling_rights.squares(us, direction);
+            key ^= piece_hashes[us.index()][KING][king_from as usize] ^ piece_hashes[us.index()][KING][king_to as usize];
+            key ^= piece_hashes[us.index()][ROOK][rook_from as usize] ^ piece_hashes[us.index()][ROOK][rook_to as usize];
+            KING
+        }
+        else{
+            let from_piece = self.pieces[us].get_piece_type_at_square(translation.from.to_bitboard()).unwrap();
+            let to_piece = m.promotion.unwrap_or(from_piece);
+            key ^= piece_hashes[us.index()][from_piece][translation.from as usize] ^ piece_hashes[us.index()][to_piece][translation.to as usize];
+            from_piece
+        };
+
+        if m.en_passant.is_some(){
+            let their_pawn = if us == Side::WHITE { translation.to - 8 } else { translation.to + 8 };
+            key ^= piece_hashes[them.index()][PAWN][their_pawn as usize];
+        }
+        else if let Some(capture) = m.capture{
+            key ^= piece_hashes[them.index()][capture][translation.to as usize];
+        }
+        else if from_piece == PAWN{
+            if let Some(square) = self.en_passant_after_push(us, translation){
+                key ^= ZOBRIST.en_passant_hashes[square as usize];
+            }
+        }
+
+        let castling_rights = self.castling_rights.after_move(us, m, from_piece);
+        if castling_rights != self.castling_rights{
+            key ^= ZOBRIST.castling_hashes[self.castling_rights.get_zobrist_index()];
+            key ^= ZOBRIST.castling_hashes[castling_rights.get_zobrist_index()];
+        }
+
+        return key;
+    }
+
+    //pass the turn for null move pruning, the other side moves next and any en passant capture is gone
+    //the clocks are left alone since nothing was played
+    pub fn make_null_move(&self) -> Position{
+        let mut position = *self;
+        if let Some(square) = position.en_passant_square{
+            position.zobrist_hash ^= ZOBRIST.en_passant_hashes[square as usize];
+        }
+        position.en_passant_square = None;
+        position.side_to_move = !position.side_to_move;
+        position.zobrist_hash ^= ZOBRIST.side_to_move_hash;
+        return position;
+    }
+
+    //the same position with the colours swapped and the board flipped rank for rank, white's e4 becomes black's e5
+    //a symmetric evaluation scores it as the exact negation of the original
+    pub fn mirror(&self) -> Position{
+        let mut position = *self;
+        for side in Side::iter(){
+            for piece in PAWN..=KING{
+                position.pieces[!side][piece] = self.pieces[side][piece].swap_bytes();
+            }
+        }
+        position.side_to_move = !self.side_to_move;
+        for direction in [KING_SIDE, QUEEN_SIDE]{
+            position.castling_rights.set_right(Side::WHITE, direction, self.castling_rights.has_right(Side::BLACK, direction));
+            position.castling_rights.set_right(Side::BLACK, direction, self.castling_rights.has_right(Side::WHITE, direction));
+        }
+        position.en_passant_square = self.en_passant_square.map(|square| square ^ 56);
+        position.zobrist_hash = ZOBRIST.hash_position(&position);
+        return position;
+    }
+
+    //play the move in place, the returned undo takes it back with unmake_move
+    //None leaves the position untouched, it happens when a king is missing or would be captured
+    pub fn make_move_mut(&mut self, m: Move) -> Option<MoveUndo>{
         let us = self.side_to_move;
 
-        new_position.en_passant_square = None;
-        new_position.side_to_move = !us;
+        if self.pieces[us][KING] == Bitboard::EMPTY || self.pieces[!us][KING] == Bitboard::EMPTY || m.capture == Some(KING){
+            return None;
+        }
+
+        let undo = MoveUndo{
+            m,
+            castling_rights: self.castling_rights,
+            en_passant_square: self.en_passant_square,
+            halfmove_clock: self.halfmove_clock,
+            zobrist_hash: self.zobrist_hash,
+        };
+
+        self.en_passant_square = None;
+        self.side_to_move = !us;
 
         //if the move is not a castle and includes a translation
         if m.castling.is_none() && m.translation.is_some(){
             let translation = m.translation.unwrap();
-            let from_piece_wrapped = self.pieces[us.0].get_piece_type_at_square(translation.from.to_bitboard());
+            let from_piece_wrapped = self.pieces[us].get_piece_type_at_square(translation.from.to_bitboard());
             if from_piece_wrapped.is_none(){
                 panic!("No piece at the from square!");
             }
@@ -1822,152 +3154,197 @@ impl Position{
             if from_piece == PAWN{
                 //check if en passant is involved
                 if m.en_passant.is_some(){
-                        new_position.pieces[us.0][PAWN] = new_position.pieces[us.0][PAWN].set_bit(translation.to);
-                        //remove the captured pawn
-                        let their_pawn = if us == Side::WHITE { translation.to - 8 } else { translation.to + 8 };
-                        new_position.pieces[(!us).0][PAWN] = new_position.pieces[(!us).0][PAWN].unset_bit(their_pawn);
-                        //remove original pawn
-                        new_position.pieces[us.0][PAWN] = new_position.pieces[us.0][PAWN].unset_bit(translation.from);                        
+                    self.toggle_piece(us, PAWN, translation.to);
+                    //remove the captured pawn
+                    let their_pawn = if us == Side::WHITE { translation.to - 8 } else { translation.to + 8 };
+                    self.toggle_piece(!us, PAWN, their_pawn);
+                    //remove original pawn
+                    self.toggle_piece(us, PAWN, translation.from);
                 }
                 else{
                     //check if en passant is possible
-
-                    if translation.to > 16 && translation.to == translation.from + 16 || translation.to == translation.from.wrapping_sub(16){
-                        //check if pawn has enemy pawn next on the to square
-                        let to_side_bb = translation.to.to_bitboard() << 1 | translation.to.to_bitboard() >> 1;
-                        if to_side_bb & self.pieces[(!us).0][PAWN] != 0{
-                            new_position.en_passant_square = if us == Side::WHITE { Some(translation.to - 8) } else { Some(translation.to + 8) };
-                        }
-                    }
+                    self.en_passant_square = self.en_passant_after_push(us, translation);
 
                     //check if promotion is involved
                     if m.promotion.is_some(){
-                        let promotion = m.promotion.unwrap();
-                        new_position.pieces[us.0][promotion] = new_position.pieces[us.0][promotion].set_bit(translation.to);
+                        self.toggle_piece(us, m.promotion.unwrap(), translation.to);
                     }
                     else{
-                        new_position.pieces[us.0][PAWN] = new_position.pieces[us.0][PAWN].set_bit(translation.to);
+                        self.toggle_piece(us, PAWN, translation.to);
                     }
 
                     //check if a capture is involved
                     if m.capture.is_some(){
                         let capture = m.capture.unwrap();
-                        new_position.pieces[(!us).0][capture] = new_position.pieces[(!us).0][capture].unset_bit(translation.to);
+                        self.toggle_piece(!us, capture, translation.to);
                     }
 
-                    new_position.en_passant_square = None;
-                    new_position.pieces[us.0][PAWN] = new_position.pieces[us.0][PAWN].unset_bit(translation.from);
+                    self.toggle_piece(us, PAWN, translation.from);
                 }
-                new_position.halfmove_clock = 0;
+                self.halfmove_clock = 0;
             }
             else{
-                //check if king or rook is moving
-                if from_piece == KING{
-                    if us == Side::WHITE{
-                        new_position.castling_rights.white_king_side = false;
-                        new_position.castling_rights.white_queen_side = false;
-                    }
-                    else{
-                        new_position.castling_rights.black_king_side = false;
-                        new_position.castling_rights.black_queen_side = false;
-                    }
-                }
-                else if from_piece == ROOK{
-                    if us == Side::WHITE{
-                        if translation.from == 0{
-                            new_position.castling_rights.white_queen_side = false;
-                        }
-                        else if translation.from == 7{
-                            new_position.castling_rights.white_king_side = false;
-                        }
-                    }
-                    else{
-                        if translation.from == 56{
-                            new_position.castling_rights.black_queen_side = false;
-                        }
-                        else if translation.from == 63{
-                            new_position.castling_rights.black_king_side = false;
-                        }
-                    }
-                }
-                
-                new_position.pieces[us.0][from_piece] = new_position.pieces[us.0][from_piece].set_bit(translation.to);
-                new_position.pieces[us.0][from_piece] = new_position.pieces[us.0][from_piece].unset_bit(translation.from);
+                self.toggle_piece(us, from_piece, translation.to);
+                self.toggle_piece(us, from_piece, translation.from);
 
                 //non-pawn move, increment the halfmove clock
-                new_position.halfmove_clock += 1;
+                self.halfmove_clock += 1;
 
                 //check if a capture is involved
                 if m.capture.is_some(){
                     let capture = m.capture.unwrap();
-                    new_position.pieces[(!us).0][capture] = new_position.pieces[(!us).0][capture].unset_bit(translation.to);
-                    new_position.halfmove_clock = 0;
+                    self.toggle_piece(!us, capture, translation.to);
+                    self.halfmove_clock = 0;
                 }
-
-                new_position.en_passant_square = None;
             }
+
+            self.castling_rights = undo.castling_rights.after_move(us, m, from_piece);
         }
         //castling
         else if m.castling.is_some(){
-            new_position.halfmove_clock += 1;
-
-            if us == Side::WHITE{
-                let white_king = new_position.pieces[us.0][KING].to_square();
-
-                if m.castling.unwrap() == KING_SIDE{
-                    new_position.pieces[us.0][KING] = new_position.pieces[us.0][KING].unset_bit(white_king);
-                    new_position.pieces[us.0][KING] = new_position.pieces[us.0][KING].set_bit(white_king + 2);
-                                                                                     
-                    new_position.pieces[us.0][ROOK] = new_position.pieces[us.0][ROOK].unset_bit(white_king + 3);
-                    new_position.pieces[us.0][ROOK] = new_position.pieces[us.0][ROOK].set_bit(white_king + 1);
-                }
-                else if m.castling.unwrap() == QUEEN_SIDE{
-                    new_position.pieces[us.0][KING] = new_position.pieces[us.0][KING].unset_bit(white_king);
-                    new_position.pieces[us.0][KING] = new_position.pieces[us.0][KING].set_bit(white_king - 2);
-                                                                                     
-                    new_position.pieces[us.0][ROOK] = new_position.pieces[us.0][ROOK].unset_bit(white_king - 4);
-                    new_position.pieces[us.0][ROOK] = new_position.pieces[us.0][ROOK].set_bit(white_king - 1);
-                }
-                else{
-                    panic!("Invalid castling move!");
-                }
-            }
-            else{
-                let black_king = new_position.pieces[us.0][KING].to_square();
+            self.halfmove_clock += 1;
+            self.castling_rights = undo.castling_rights.after_move(us, m, KING);
 
-                if m.castling.unwrap() == KING_SIDE{
-                    new_position.pieces[us.0][KING] = new_position.pieces[us.0][KING].unset_bit(black_king);
-                    new_position.pieces[us.0][KING] = new_position.pieces[us.0][KING].set_bit(black_king + 2);
+            //the king and rook bitboards are toggled separately, so it works when a chess960 king or rook stays put
+            //or lands where the other started
+            let (king_from, king_to, rook_from, rook_to) = undo.castling_rights.squares(us, m.castling.unwrap());
 
-                    new_position.pieces[us.0][ROOK] = new_position.pieces[us.0][ROOK].unset_bit(black_king + 3);
-                    new_position.pieces[us.0][ROOK] = new_position.pieces[us.0][ROOK].set_bit(black_king + 1);
-                }
-                else if m.castling.unwrap() == QUEEN_SIDE{
-                    new_position.pieces[us.0][KING] = new_position.pieces[us.0][KING].unset_bit(black_king);
-                    new_position.pieces[us.0][KING] = new_position.pieces[us.0][KING].set_bit(black_king - 2);
-                                                                                     
-                    new_position.pieces[us.0][ROOK] = new_position.pieces[us.0][ROOK].unset_bit(black_king - 4);
-                    new_position.pieces[us.0][ROOK] = new_position.pieces[us.0][ROOK].set_bit(black_king - 1);
-                }
-                else{
-                    panic!("Invalid castling move!");
-                }
-            }
+            self.toggle_piece(us, KING, king_from);
+            self.toggle_piece(us, KING, king_to);
+            self.toggle_piece(us, ROOK, rook_from);
+            self.toggle_piece(us, ROOK, rook_to);
         }
         else{
             panic!("Unidentified move!");
         }
 
         if us == Side::BLACK{
-            new_position.fullmove_number += 1;
+            self.fullmove_number += 1;
         }
 
-        //check if king is missing from new position
-        if new_position.pieces[us.0][KING] == Bitboard::EMPTY || new_position.pieces[(!us).0][KING] == Bitboard::EMPTY{
-            return None;
+        //the pieces are already hashed, add the rest of the state
+        if undo.castling_rights != self.castling_rights{
+            self.zobrist_hash ^= ZOBRIST.castling_hashes[undo.castling_rights.get_zobrist_index()];
+            self.zobrist_hash ^= ZOBRIST.castling_hashes[self.castling_rights.get_zobrist_index()];
+        }
+        if let Some(square) = undo.en_passant_square{
+            self.zobrist_hash ^= ZOBRIST.en_passant_hashes[square as usize];
         }
+        if let Some(square) = self.en_passant_square{
+            self.zobrist_hash ^= ZOBRIST.en_passant_hashes[square as usize];
+        }
+        self.zobrist_hash ^= ZOBRIST.side_to_move_hash;
 
-        return Some(new_position);
+        return Some(undo);
+    }
+
+    //take back the move make_move_mut returned `undo` for
+    pub fn unmake_move(&mut self, undo: MoveUndo){
+        let m = undo.m;
+        let us = !self.side_to_move;
+
+        self.side_to_move = us;
+        if us == Side::BLACK{
+            self.fullmove_number -= 1;
+        }
+
+        let translation = m.translation.unwrap();
+
+        if let Some(direction) = m.castling{
+            let (king_from, king_to, rook_from, rook_to) = undo.castling_rights.squares(us, direction);
+            self.toggle_piece(us, KING, king_to);
+            self.toggle_piece(us, KING, king_from);
+            self.toggle_piece(us, ROOK, rook_to);
+            self.toggle_piece(us, ROOK, rook_from);
+        }
+        else{
+            if m.promotion.is_some(){
+                self.toggle_piece(us, m.promotion.unwrap(), translation.to);
+                self.toggle_piece(us, PAWN, translation.from);
+            }
+            else{
+                let piece = self.pieces[us].get_piece_type_at_square(translation.to.to_bitboard()).unwrap();
+                self.toggle_piece(us, piece, translation.to);
+                self.toggle_piece(us, piece, translation.from);
+            }
+
+            if m.en_passant.is_some(){
+                let their_pawn = if us == Side::WHITE { translation.to - 8 } else { translation.to + 8 };
+                self.toggle_piece(!us, PAWN, their_pawn);
+            }
+            else if m.capture.is_some(){
+                self.toggle_piece(!us, m.capture.unwrap(), translation.to);
+            }
+        }
+
+        self.castling_rights = undo.castling_rights;
+        self.en_passant_square = undo.en_passant_square;
+        self.halfmove_clock = undo.halfmove_clock;
+        self.zobrist_hash = undo.zobrist_hash;
+    }
+}
+
+//builds a position piece by piece instead of from a FEN string, everything not set is as in Position::new()
+//
+//    let position = PositionBuilder::new()
+//        .piece(Square::E1, KING, Side::WHITE)
+//        .piece(Square::E8, KING, Side::BLACK)
+//        .side_to_move(Side::BLACK)
+//        .build();
+pub struct PositionBuilder{
+    position: Position,
+}
+
+impl PositionBuilder{
+    pub fn new() -> PositionBuilder{
+        return PositionBuilder{ position: Position::new() };
+    }
+
+    pub fn piece(mut self, square: Square, piece: Piece, side: Side) -> PositionBuilder{
+        self.position.set_piece(square, piece, side);
+        return self;
+    }
+
+    pub fn side_to_move(mut self, side: Side) -> PositionBuilder{
+        self.position.side_to_move = side;
+        return self;
+    }
+
+    pub fn castling(mut self, castling_rights: Castling) -> PositionBuilder{
+        self.position.castling_rights = castling_rights;
+        return self;
+    }
+
+    pub fn en_passant(mut self, square: Option<Square>) -> PositionBuilder{
+        self.position.en_passant_square = square;
+        return self;
+    }
+
+    pub fn halfmove_clock(mut self, halfmove_clock: u32) -> PositionBuilder{
+        self.position.halfmove_clock = halfmove_clock;
+        return self;
+    }
+
+    pub fn chess960(mut self, chess960: bool) -> PositionBuilder{
+        self.position.chess960 = chess960;
+        return self;
+    }
+
+    pub fn fullmove_number(mut self, fullmove_number: u32) -> PositionBuilder{
+        self.position.fullmove_number = fullmove_number;
+        return self;
+    }
+
+    //the position with its hash computed from everything set above, no legality checks are made
+    pub fn build(self) -> Position{
+        let mut position = self.position;
+        position.zobrist_hash = ZOBRIST.hash_position(&position);
+        return position;
     }
 }
 
+impl Default for PositionBuilder{
+    fn default() -> PositionBuilder{
+        return PositionBuilder::new();
+    }
+}
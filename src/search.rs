@@ -0,0 +1,463 @@
+use std::{sync::{Arc, atomic::{AtomicBool, Ordering}}, time::{Duration, Instant}};
+use crate::{cache::{EvalCache, TranspositionTable, TranspositionEntry, Bound}, position::{Position, Move, ZobristMoveStack}, tree::mate_distance_score, types::{Side, SideConstants, GameState, GameStateConstants, KNIGHT, BISHOP, ROOK, QUEEN}};
+
+//moves that lose material by exchange are skipped this close to the leaves
+const SEE_PRUNING_DEPTH: u8 = 2;
+//quiet moves may give up this much per remaining ply before they are pruned
+const SEE_QUIET_MARGIN: f32 = 50.0;
+//null move pruning needs this much depth left, the reduced search below it is R plies shallower
+const NULL_MOVE_MIN_DEPTH: u8 = 3;
+const NULL_MOVE_REDUCTION: u8 = 2;
+//a line may be extended past its nominal depth by this many checks at most
+const MAX_CHECK_EXTENSIONS: u8 = 6;
+//the clock and stop flag are read once every this many nodes
+const STOP_CHECK_NODES: u64 = 1024;
+//iterative deepening stops here even with time left
+pub const MAX_TIMED_DEPTH: u8 = 64;
+//mate scores sit within a ply count of 1e6, anything past this is a forced mate
+pub const MATE_BOUND: f32 = 999000.0;
+
+//how much work a search did, nodes counts every position evaluated including quiescence
+#[derive(Copy)]
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct SearchStats{
+    pub nodes: u64,
+    pub depth: u8,
+    pub elapsed: Duration,
+}
+
+impl SearchStats{
+    pub fn nodes_per_second(&self) -> u64{
+        let seconds = self.elapsed.as_secs_f64();
+        if seconds == 0.0{
+            return 0;
+        }
+        return (self.nodes as f64 / seconds) as u64;
+    }
+}
+
+pub struct Searcher{
+    pub nodes: u64,
+    pub see_pruning: bool,
+    //let the side to move pass and cut off when even that fails high
+    pub null_move_pruning: bool,
+    //search a ply deeper after a move that gives check
+    pub check_extensions: bool,
+    //exact minimax to the given depth: every pruning switch is ignored and leaves take the static score without quiescence
+    pub analysis: bool,
+    //abandon the search once this passes or the flag is set from another thread,
+    //the result of a stopped search must be thrown away
+    pub deadline: Option<Instant>,
+    pub stop: Option<Arc<AtomicBool>>,
+    pub stopped: bool,
+    //the game's positions before the root, a line that would repeat one of them a third time is a draw
+    pub history: ZobristMoveStack,
+    //hashes of the positions from the root down to the current node's parent
+    path: Vec<u64>,
+    //best line found below each ply, row 0 is the principal variation once a search returns
+    pv_table: Vec<Vec<Move>>,
+    //nominal depth of the current search, a node at `ply` with `depth` left has used ply + depth - root_depth extensions
+    root_depth: u8,
+    //kept between searches so each iteration starts from the last one's best moves
+    pub tt: TranspositionTable,
+    //static scores of quiescence nodes, kept between searches like the transposition table
+    pub eval_cache: EvalCache,
+}
+
+impl Searcher{
+    pub fn new() -> Searcher{
+        return Searcher{
+            nodes: 0,
+            see_pruning: true,
+            null_move_pruning: true,
+            check_extensions: true,
+            analysis: false,
+            deadline: None,
+            stop: None,
+            stopped: false,
+            history: ZobristMoveStack::new(),
+            path: Vec::new(),
+            pv_table: Vec::new(),
+            root_depth: 0,
+            tt: TranspositionTable::new(),
+            eval_cache: EvalCache::new(),
+        };
+    }
+
+    //negamax with alpha-beta cutoffs, the score is from the side to move's point of view
+    //the move is None when the position is already over or depth is 0, leaves are resolved by quiescence
+    pub fn search(&mut self, pos: &Position, depth: u8, alpha: f32, beta: f32) -> (f32, Option<Move>){
+        let mut position = *pos;
+        self.root_depth = depth;
+        return self.negamax(&mut position, depth, 0, alpha, beta, true);
+    }
+
+    //keep searching captures and promotions from a leaf until the position is quiet, the side to move
+    //can always stand pat on the static score instead of capturing
+    pub fn quiescence(&mut self, pos: &Position, alpha: f32, beta: f32) -> f32{
+        let mut position = *pos;
+        return self.quiesce(&mut position, 0, alpha, beta);
+    }
+
+    //iterative deepening from depth 1 to `max_depth` until the deadline passes or the stop flag is set, returns the best
+    //move and side to move score of the deepest completed iteration, the stats carry that depth. depth 1 always finishes
+    //so a legal move comes back however early the search is stopped, None when the position has no legal move
+    pub fn iterative_deepening(&mut self, pos: &Position, max_depth: u8) -> Option<(Move, f32, SearchStats)>{
+        return self.iterative_deepening_with(pos, max_depth, |_, _, _| {});
+    }
+
+    //iterative deepening that calls `report` with the stats, score and principal variation after every completed depth
+    pub fn iterative_deepening_with<F: FnMut(&SearchStats, f32, &[Move])>(&mut self, pos: &Position, max_depth: u8, mut report: F) -> Option<(Move, f32, SearchStats)>{
+        let start = Instant::now();
+        let start_nodes = self.nodes;
+        let deadline = self.deadline.take();
+        let stop = self.stop.take();
+        let (score, best_move) = self.search(pos, 1, f32::NEG_INFINITY, f32::INFINITY);
+        let mut best = match best_move{
+            Some(m) => (m, score, 1),
+            //a position already scored as drawn has no best move but may still have legal ones to play
+            None => match pos.legal_moves().first(){
+                Some(m) => (*m, 0.0, 1),
+                //mated or stalemated, there's nothing to search
+                None => {
+                    self.deadline = deadline;
+                    self.stop = stop;
+                    return None;
+                }
+            },
+        };
+        if best_move.is_none(){
+            self.pv_table[0] = vec![best.0];
+        }
+        let stats = |searcher: &Searcher, depth: u8| SearchStats{ nodes: searcher.nodes - start_nodes, depth, elapsed: start.elapsed() };
+        report(&stats(self, 1), best.1, &self.pv_table[0]);
+        let mut pv = self.pv_table[0].clone();
+        self.deadline = deadline;
+        self.stop = stop;
+
+        for depth in 2..=max_depth{
+            if self.stop_requested(){
+                break;
+            }
+            let (score, best_move) = self.search(pos, depth, f32::NEG_INFINITY, f32::INFINITY);
+            if self.stopped{
+                break;
+            }
+            match best_move{
+                Some(m) => best = (m, score, depth),
+                None => break,
+            }
+            pv = self.pv_table[0].clone();
+            report(&stats(self, depth), score, &pv);
+            //a deeper search can't improve on a forced mate
+            if score.abs() >= MATE_BOUND{
+                break;
+            }
+        }
+
+        //a stopped iteration leaves a partial line behind
+        self.pv_table[0] = pv;
+        return Some((best.0, best.1, stats(self, best.2)));
+    }
+
+    //the line the last search expects, starting with its best move
+    pub fn principal_variation(&self) -> Vec<Move>{
+        return self.pv_table.first().cloned().unwrap_or_default();
+    }
+
+    //a position already on the search path is scored as a draw, the side that could repeat it can do so again,
+    //one from the game needs two earlier occurrences. positions before a capture, pawn move or lost castling right
+    //can't come back, so neither list needs trimming
+    fn is_repetition(&self, zobrist_hash: u64) -> bool{
+        return self.path.contains(&zobrist_hash) || self.history.get_repetitions(zobrist_hash) >= 2;
+    }
+
+    fn stop_requested(&self) -> bool{
+        let timed_out = self.deadline.map_or(false, |deadline| Instant::now() >= deadline);
+        let flagged = self.stop.as_ref().map_or(false, |stop| stop.load(Ordering::Relaxed));
+        return timed_out || flagged;
+    }
+
+    fn should_stop(&mut self) -> bool{
+        if !self.stopped && self.nodes % STOP_CHECK_NODES == 0{
+            self.stopped = self.stop_requested();
+        }
+        return self.stopped;
+    }
+
+    //`allow_null` is false right after a null move so two passes in a row can't happen
+    fn negamax(&mut self, position: &mut Position, depth: u8, ply: u8, alpha: f32, beta: f32, allow_null: bool) -> (f32, Option<Move>){
+        self.nodes += 1;
+        if self.pv_table.len() <= ply as usize{
+            self.pv_table.resize(ply as usize + 1, Vec::new());
+        }
+        self.pv_table[ply as usize].clear();
+        if self.should_stop(){
+            return (0.0, None);
+        }
+        let eval = position.evaluate();
+
+        //evaluate scores from white's point of view
+        let score = eval.score.unwrap_or(0.0);
+        let relative_score = if position.side_to_move == Side::WHITE { score } else { -score };
+
+        if eval.game_state == GameState::CHECKMATE{
+            return (mate_distance_score(relative_score, ply), None);
+        }
+        if eval.game_state == GameState::DRAW || (ply > 0 && self.is_repetition(position.zobrist_hash)){
+            return (0.0, None);
+        }
+
+        //a deep enough earlier result settles the node, except at the root which must give a move and in analysis
+        //where a deeper stored score would differ from exact minimax at this depth
+        let tt_entry = self.tt.probe(position.zobrist_hash);
+        if let Some(entry) = tt_entry{
+            let score = score_from_tt(entry.score, ply);
+            let usable = match entry.bound{
+                Bound::Exact => true,
+                Bound::Lower => score >= beta,
+                Bound::Upper => score <= alpha,
+            };
+            if ply > 0 && !self.analysis && entry.depth >= depth && usable{
+                return (score, entry.best_move);
+            }
+        }
+
+        if depth == 0{
+            if self.analysis{
+                return (relative_score, None);
+            }
+            return (self.quiesce(position, ply, alpha, beta), None);
+        }
+
+        //if passing still fails high a real move will too, skipped in check where passing is illegal and when the side to
+        //move has only pawns left, where zugzwang makes passing better than any move
+        let try_null = self.null_move_pruning && !self.analysis && allow_null && ply > 0 && depth >= NULL_MOVE_MIN_DEPTH
+            && eval.game_state != GameState::CHECK && relative_score >= beta && has_non_pawn_material(position);
+        if try_null{
+            let mut null_position = position.make_null_move();
+            let null_score = -self.negamax(&mut null_position, depth - 1 - NULL_MOVE_REDUCTION, ply + 1, -beta, -beta + 1.0, false).0;
+            if self.stopped{
+                return (0.0, None);
+            }
+            //a mate found by passing isn't a real one, fail high on beta instead
+            if null_score >= beta{
+                return (if null_score >= MATE_BOUND { beta } else { null_score }, None);
+            }
+        }
+
+        //no pruning in check, at the root, or before the first move has been searched
+        let can_prune = self.see_pruning && !self.analysis && ply > 0 && depth <= SEE_PRUNING_DEPTH && eval.game_state != GameState::CHECK;
+
+        let original_alpha = alpha;
+        let mut alpha = alpha;
+        let mut best_score = f32::NEG_INFINITY;
+        let mut best_move: Option<Move> = None;
+
+        let mut moves = eval.moves;
+        order_moves(position, &mut moves);
+        //the stored best move goes first
+        if let Some(tt_move) = tt_entry.and_then(|entry| entry.best_move){
+            if let Some(index) = moves.iter().position(|m| m.same_transition(&tt_move)){
+                moves[..=index].rotate_right(1);
+            }
+        }
+
+        for m in moves{
+            if can_prune && best_move.is_some(){
+                let tactical = !m.is_quiet();
+                let threshold = if tactical { 0.0 } else { -SEE_QUIET_MARGIN * depth as f32 };
+                if !position.see_ge(m, threshold){
+                    continue;
+                }
+            }
+
+            let parent_hash = position.zobrist_hash;
+            let undo = match position.make_move_mut(m){
+                Some(undo) => undo,
+                None => continue,
+            };
+            //checks are forcing, follow them a ply further while the line has extensions left
+            let extensions = (ply as u16 + depth as u16).saturating_sub(self.root_depth as u16);
+            let extend = self.check_extensions && !self.analysis && extensions < MAX_CHECK_EXTENSIONS as u16 && position.is_in_check();
+            let child_depth = if extend { depth } else { depth - 1 };
+            self.path.push(parent_hash);
+            let score = -self.negamax(position, child_depth, ply + 1, -beta, -alpha, true).0;
+            self.path.pop();
+            position.unmake_move(undo);
+
+            if score > best_score{
+                best_score = score;
+                best_move = Some(m);
+
+                let (line, rest) = self.pv_table.split_at_mut(ply as usize + 1);
+                let line = &mut line[ply as usize];
+                line.clear();
+                line.push(m);
+                line.extend_from_slice(&rest[0]);
+            }
+            if score > alpha{
+                alpha = score;
+            }
+            if alpha >= beta{
+                break;
+            }
+        }
+
+        if !self.stopped && best_move.is_some(){
+            let bound = if best_score <= original_alpha { Bound::Upper } else if best_score >= beta { Bound::Lower } else { Bound::Exact };
+            self.tt.store(TranspositionEntry{
+                key: position.zobrist_hash,
+                depth,
+                score: score_to_tt(best_score, ply),
+                bound,
+                best_move,
+            });
+        }
+
+        return (best_score, best_move);
+    }
+
+    fn quiesce(&mut self, position: &mut Position, ply: u8, alpha: f32, beta: f32) -> f32{
+        self.nodes += 1;
+        if self.should_stop(){
+            return 0.0;
+        }
+
+        //in check there's no standing pat, every evasion is searched and having none is mate
+        let in_check = position.is_in_check();
+        let mut alpha = alpha;
+        let mut moves = if in_check{
+            let eval = position.evaluate();
+            if eval.game_state == GameState::CHECKMATE{
+                let score = eval.score.unwrap_or(0.0);
+                return mate_distance_score(if position.side_to_move == Side::WHITE { score } else { -score }, ply);
+            }
+            if eval.game_state == GameState::DRAW{
+                return 0.0;
+            }
+            eval.moves
+        }
+        else{
+            //the draw rules stay outside the cache, its key doesn't cover the halfmove clock
+            if position.check_draw().is_some(){
+                return 0.0;
+            }
+            let stand_pat = position.cached_static_eval(&mut self.eval_cache);
+            if stand_pat >= beta{
+                return stand_pat;
+            }
+            alpha = alpha.max(stand_pat);
+            position.capture_moves()
+        };
+        order_moves(position, &mut moves);
+
+        for m in moves{
+            //a losing capture can't beat standing pat, an evasion has to be searched whatever it gives up
+            if !in_check && self.see_pruning && !self.analysis && !position.see_ge(m, 0.0){
+                continue;
+            }
+
+            let undo = match position.make_move_mut(m){
+                Some(undo) => undo,
+                None => continue,
+            };
+            let score = -self.quiesce(position, ply.saturating_add(1), -beta, -alpha);
+            position.unmake_move(undo);
+
+            if score >= beta{
+                return score;
+            }
+            if score > alpha{
+                alpha = score;
+            }
+        }
+
+        return alpha;
+    }
+}
+
+//anything besides the king and pawns for the side to move
+fn has_non_pawn_material(position: &Position) -> bool{
+    let pieces = &position.pieces[position.side_to_move];
+    return pieces[KNIGHT] | pieces[BISHOP] | pieces[ROOK] | pieces[QUEEN] != 0;
+}
+
+//mate scores count plies from the root, the table keeps them counted from the stored node so they stay right
+//when the node is reached at another ply
+fn score_to_tt(score: f32, ply: u8) -> f32{
+    if score >= MATE_BOUND{
+        return score + ply as f32;
+    }
+    if score <= -MATE_BOUND{
+        return score - ply as f32;
+    }
+    return score;
+}
+
+fn score_from_tt(score: f32, ply: u8) -> f32{
+    if score >= MATE_BOUND{
+        return score - ply as f32;
+    }
+    if score <= -MATE_BOUND{
+        return score + ply as f32;
+    }
+    return score;
+}
+
+//follow the stored best moves from `pos` until an entry is missing, its move isn't legal (a hash collision) or a
+//position repeats, at most `max_len` moves
+pub fn extract_pv(pos: &Position, tt: &TranspositionTable, max_len: usize) -> Vec<Move>{
+    let mut pv: Vec<Move> = Vec::new();
+    let mut position = *pos;
+    let mut seen: Vec<u64> = vec![position.zobrist_hash];
+
+    while pv.len() < max_len{
+        let m = match tt.get(position.zobrist_hash).and_then(|entry| entry.best_move){
+            Some(m) => m,
+            None => break,
+        };
+        if !position.legal_moves().iter().any(|legal| *legal == m){
+            break;
+        }
+        position = match position.make_move(m){
+            Some(next) => next,
+            None => break,
+        };
+        pv.push(m);
+        if seen.contains(&position.zobrist_hash){
+            break;
+        }
+        seen.push(position.zobrist_hash);
+    }
+
+    return pv;
+}
+
+//best captures first so cutoffs come early, the sort is stable so quiet moves keep their generation order
+pub fn order_moves(position: &Position, moves: &mut [Move]){
+    moves.sort_by_cached_key(|m| std::cmp::Reverse(m.mvv_lva_score(position)));
+}
+
+pub fn search(pos: &Position, depth: u8, alpha: f32, beta: f32) -> (f32, Option<Move>){
+    return Searcher::new().search(pos, depth, alpha, beta);
+}
+
+pub fn quiescence(pos: &Position, alpha: f32, beta: f32) -> f32{
+    return Searcher::new().quiescence(pos, alpha, beta);
+}
+
+//iterative deepening until `max_millis` have passed or a mate is found, see Searcher::iterative_deepening
+pub fn search_timed(pos: &Position, max_millis: u64) -> Option<(Move, f32, u8)>{
+    return search_timed_with_history(pos, &ZobristMoveStack::new(), max_millis);
+}
+
+//search_timed from a game in progress, `history` holds the positions since the last irreversible move before `pos`
+pub fn search_timed_with_history(pos: &Position, history: &ZobristMoveStack, max_millis: u64) -> Option<(Move, f32, u8)>{
+    let mut searcher = Searcher::new();
+    searcher.history = history.clone();
+    searcher.deadline = Some(Instant::now() + Duration::from_millis(max_millis));
+    let (best_move, score, stats) = searcher.iterative_deepening(pos, MAX_TIMED_DEPTH)?;
+    return Some((best_move, score, stats.depth));
+}
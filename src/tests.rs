@@ -1,6 +1,9 @@
 use serde_json::*;
 
-use crate::{position::Position, display::print_position, types::{GameState, GameStateConstants}};
+use crate::{book::{Book, polyglot_key}, epd::{parse_epd, EpdError}, pst::{PHASE_MAX, taper}, cache::{EvalCache, TranspositionTable}, uci::{run_uci_with, parse_position, parse_go, format_score, GoLimit}, search::{search, search_timed_with_history, quiescence, Searcher, MATE_BOUND, order_moves, search_timed, SearchStats, extract_pv}, game::{Game, PgnHeaders}, maps::{get_rook_blockers, get_bishop_blockers, get_pawn_attacks, build_rook_magics, build_bishop_magics, get_rook_attacks, get_bishop_attacks, get_ray_between_squares, compute_ray_between_squares, get_line_through_squares, get_knight_attacks, get_king_attacks}, tree::{mate_distance_score, best_score, PositionTree, ExpandStyle}, masks::{mask_rook_attacks, mask_bishop_attacks, mask_pawn_attacks, mask_knight_attacks, mask_king_attacks}, bitboard::*, types::{Square, Squares, SquareConstants, SquareMethods, SquareParseError, Magic}, position::{Position, PositionBuilder, Castling, EvalParams, SidePiecesMethods, SideAttackMethods, MaterialRule, Move, Translation, MoveError, MoveParseError, FenError, ZobristMoveStack, ZOBRIST}, display::{print_position, write_position, write_bitboard}, types::{GameState, GameStateConstants, GameResult, Termination, DrawReason, PieceType, Side, SideConstants, SideMethods, KING_SIDE, QUEEN_SIDE, QUEEN, KNIGHT, KING, ROOK, PAWN, BISHOP}};
+use rand::{Rng, SeedableRng};
+use rand_pcg::Pcg64;
+use std::sync::{Arc, atomic::{AtomicBool, Ordering}};
 
 #[test]
 pub fn move_generation_test(){
@@ -65,4 +68,2513 @@ pub fn move_generation_test(){
             }
         }
     }
-}
\ No newline at end of file
+}
+
+#[test]
+pub fn evasion_moves_test(){
+    let in_check = [
+        "4k3/8/8/8/8/8/3q4/4K3 w - - 0 1",
+        "4k3/8/8/8/1b6/8/8/R3K2R w KQ - 0 1",
+        "rnb1kbnr/pppp1ppp/8/4p3/5PPq/8/PPPPP2P/RNBQKBNR w KQkq - 1 3",
+        "4k3/8/8/8/8/5n2/8/R3K2r w Q - 0 1",
+    ];
+
+    for fen in in_check{
+        let position = Position::from_fen(fen);
+        let mut evasions: Vec<String> = position.evasion_moves().iter().map(|m| m.get_tstring()).collect();
+        let mut moves: Vec<String> = position.evaluate().moves.iter().map(|m| m.get_tstring()).collect();
+        evasions.sort();
+        moves.sort();
+        assert_eq!(evasions, moves, "evasions differ for {}", fen);
+    }
+
+    let quiet = Position::new_game();
+    assert!(quiet.evasion_moves().is_empty());
+}
+
+#[test]
+pub fn zobrist_castling_update_test(){
+    let fens = [
+        "r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R w KQkq - 0 1",
+        "r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R b KQkq - 0 1",
+        "r3k2r/p6p/8/8/8/8/P6P/R3K2R w KQkq - 0 1",
+        "r3k2r/8/8/8/8/8/8/R3K2R b Kq - 0 1",
+    ];
+
+    for fen in fens{
+        let position = Position::from_fen(fen);
+        assert_eq!(position.zobrist_hash, ZOBRIST.hash_position(&position));
+
+        for m in position.evaluate().moves{
+            let new_position = position.make_move(m).unwrap();
+            assert_eq!(new_position.zobrist_hash, ZOBRIST.hash_position(&new_position), "hash mismatch after {} in {}", m.get_tstring(), fen);
+        }
+    }
+
+    //king, rook and castling moves each revoke rights
+    let position = Position::from_fen(fens[0]);
+    let mut line = position;
+    for uci in ["h1g1", "e8g8", "e1d1"]{
+        let m = *line.evaluate().moves.iter().find(|m| m.get_tstring() == uci).unwrap();
+        let castling_before = line.castling_rights;
+        line = line.make_move(m).unwrap();
+        assert_ne!(castling_before, line.castling_rights);
+        assert_eq!(line.zobrist_hash, ZOBRIST.hash_position(&line));
+    }
+    assert_eq!(line.castling_rights, crate::position::Castling::new());
+}
+
+#[test]
+pub fn can_castle_test(){
+    //(fen, white king side, white queen side, black king side, black queen side)
+    let cases = [
+        ("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", [true, true, true, true]),
+        ("rn2k1nr/8/8/8/8/8/8/RN2K1NR w KQkq - 0 1", [false, false, false, false]),
+        ("r3k2r/8/8/8/8/8/5n2/R3K2R w KQkq - 0 1", [true, false, true, true]),
+        ("r3k2r/8/8/8/8/8/8/R3K2R w - - 0 1", [false, false, false, false]),
+        ("r3k2r/8/8/2B5/8/8/8/R3K2R b KQkq - 0 1", [true, true, false, true]),
+        ("r3k2r/8/8/8/8/n7/8/R3K2R w KQkq - 0 1", [true, true, true, true]),
+        ("r3k2r/8/8/8/8/8/1q6/R3K2R w KQkq - 0 1", [true, false, true, true]),
+        ("r3k2r/8/8/8/8/8/8/R3K1qR w KQkq - 0 1", [false, false, true, true]),
+    ];
+
+    for (fen, expected) in cases{
+        let position = Position::from_fen(fen);
+        assert_eq!(position.can_castle(Side::WHITE, KING_SIDE), expected[0], "white king side in {}", fen);
+        assert_eq!(position.can_castle(Side::WHITE, QUEEN_SIDE), expected[1], "white queen side in {}", fen);
+        assert_eq!(position.can_castle(Side::BLACK, KING_SIDE), expected[2], "black king side in {}", fen);
+        assert_eq!(position.can_castle(Side::BLACK, QUEEN_SIDE), expected[3], "black queen side in {}", fen);
+    }
+}
+
+fn find_move(position: &Position, uci: &str) -> Move{
+    return *position.legal_moves().iter().find(|m| m.get_tstring() == uci).unwrap();
+}
+
+#[test]
+pub fn repetition_sibling_lines_test(){
+    let root = Position::new_game();
+    let shuffle = ["g1f3", "g8f6", "f3g1", "f6g8"];
+
+    //line a shuffles the knights twice, returning to the root for the third time
+    let mut line_a = ZobristMoveStack::new();
+    let mut position = root;
+    for _ in 0..2{
+        for uci in shuffle{
+            line_a.add(position.zobrist_hash);
+            position = position.make_move(find_move(&position, uci)).unwrap();
+        }
+    }
+    assert_eq!(position.zobrist_hash, root.zobrist_hash);
+    assert!(position.evaluate_with_history(&line_a).game_state == GameState::DRAW);
+
+    //sibling line b shares the root but shuffles only once
+    let mut line_b = ZobristMoveStack::new();
+    let mut sibling = root;
+    for uci in shuffle{
+        line_b.add(sibling.zobrist_hash);
+        sibling = sibling.make_move(find_move(&sibling, uci)).unwrap();
+    }
+
+    //evaluating the same position repeatedly must not inflate the count
+    for _ in 0..5{
+        assert!(sibling.evaluate_with_history(&line_b).game_state != GameState::DRAW);
+    }
+    assert!(root.evaluate_with_history(&ZobristMoveStack::new()).game_state != GameState::DRAW);
+}
+
+#[test]
+pub fn eval_cache_test(){
+    let mut cache = EvalCache::new();
+
+    //a sample of positions reached by playing out the first few moves of several openings
+    let mut positions: Vec<Position> = Vec::new();
+    for fen in [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4",
+        "1k1r3r/pppqb1pp/1nn1p3/3bPp2/1P1PN3/P2BBN2/5PPP/2RQ1RK1 w - f6 0 15",
+        "8/8/4k3/8/2P5/8/4K3/8 b - - 0 1",
+    ]{
+        let position = Position::from_fen(fen);
+        positions.push(position);
+        for m in position.evaluate().moves{
+            positions.push(position.make_move(m).unwrap());
+        }
+    }
+
+    for position in &positions{
+        let uncached = position.evaluate().score.unwrap();
+        let uncached = if position.side_to_move == Side::WHITE { uncached } else { -uncached };
+        assert_eq!(position.evaluate_relative(&mut cache), uncached);
+        assert_eq!(position.evaluate_relative(&mut cache), uncached);
+    }
+
+    assert!(cache.hits >= positions.len() as u64);
+    assert!(cache.hit_rate() >= 0.5);
+
+    //the hash doesn't cover the halfmove clock, the same board a move before and on the fifty move draw shares a slot
+    let mut cache = EvalCache::new();
+    let live = Position::from_fen("8/8/4k3/8/8/3K4/8/4R3 w - - 99 80");
+    let drawn = Position::from_fen("8/8/4k3/8/8/3K4/8/4R3 w - - 100 80");
+    assert!(live.hash() == drawn.hash());
+    let score = live.evaluate_relative(&mut cache);
+    assert!(score > 0.0);
+    assert_eq!(drawn.evaluate_relative(&mut cache), 0.0);
+    assert_eq!(drawn.evaluate().score, Some(0.0));
+    assert_eq!(live.evaluate_relative(&mut cache), score);
+
+    //quiescence takes its stand-pat scores from the searcher's cache
+    let mut searcher = Searcher::new();
+    searcher.search(&Position::new_game(), 4, f32::NEG_INFINITY, f32::INFINITY);
+    assert!(searcher.eval_cache.hits > 0);
+}
+
+#[test]
+pub fn rook_south_ray_test(){
+    let blocker_on_d2 = Square::D2.to_bitboard();
+    let attacks = mask_rook_attacks(Square::D4, blocker_on_d2);
+
+    assert!(attacks & Square::D3.to_bitboard() != 0);
+    assert!(attacks & Square::D2.to_bitboard() != 0);
+    assert!(attacks & Square::D1.to_bitboard() == 0);
+
+    //without blockers the ray runs to the first rank and stops there
+    let south = mask_rook_attacks(Square::D4, Bitboard::EMPTY) & FILE_DBB & (RANK_1BB | RANK_2BB | RANK_3BB);
+    assert_eq!(south, Square::D1.to_bitboard() | Square::D2.to_bitboard() | Square::D3.to_bitboard());
+}
+
+#[test]
+pub fn make_move_coords_test(){
+    let position = Position::new_game();
+    let after = position.make_move_coords(Square::E2, Square::E4, None).unwrap();
+    assert_eq!(after.pieces[Side::WHITE][PAWN] & (Square::E2.to_bitboard() | Square::E4.to_bitboard()), Square::E4.to_bitboard());
+    assert_eq!(after.en_passant_square, None);
+    assert!(after.side_to_move == Side::BLACK);
+    assert_eq!(position.make_move_coords(Square::E2, Square::E5, None).err(), Some(MoveError::Illegal));
+
+    //castling is resolved from the king's from and to squares
+    let position = Position::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+    let castled = position.make_move_coords(Square::E1, Square::G1, None).unwrap();
+    assert_eq!(castled.pieces[Side::WHITE][ROOK], Square::A1.to_bitboard() | Square::F1.to_bitboard());
+    assert_eq!(castled.pieces[Side::WHITE][KING], Square::G1.to_bitboard());
+
+    //promotions need the promotion piece
+    let position = Position::from_fen("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1");
+    assert!(position.make_move_coords(Square::B7, Square::B8, None).is_err());
+    let promoted = position.make_move_coords(Square::B7, Square::B8, Some(KNIGHT)).unwrap();
+    assert_eq!(promoted.pieces[Side::WHITE][KNIGHT], Square::B8.to_bitboard());
+    assert!(position.make_move_coords(Square::B7, Square::B8, Some(QUEEN)).is_ok());
+
+    //a dead drawn position still has legal moves
+    let position = Position::from_fen("8/8/4k3/8/8/8/8/4K1N1 w - - 0 1");
+    let moved = position.make_move_coords(Square::G1, Square::F3, None).unwrap();
+    assert_eq!(moved.pieces[Side::WHITE][KNIGHT], Square::F3.to_bitboard());
+}
+
+#[test]
+pub fn try_from_fen_test(){
+    for fen in [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "1k1r3r/pppqb1pp/1nn1p3/3bPp2/1P1PN3/P2BBN2/5PPP/2RQ1RK1 w - f6 0 15",
+        "r3k2r/8/8/8/8/8/8/R3K2R b Kq - 12 40",
+    ]{
+        let position = Position::try_from_fen(fen).unwrap();
+        assert_eq!(position.to_fen(), fen);
+    }
+
+    let errors = [
+        ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0", FenError::WrongFieldCount),
+        ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP w KQkq - 0 1", FenError::BadPiecePlacement),
+        ("rnbqkbnr/pppppppp/9/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", FenError::BadPiecePlacement),
+        ("rnbqkbnr/ppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", FenError::BadPiecePlacement),
+        ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNRR w KQkq - 0 1", FenError::BadPiecePlacement),
+        ("rnbqkbnr/pppxpppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", FenError::BadPiecePlacement),
+        ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR x KQkq - 0 1", FenError::BadSideToMove),
+        ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQxq - 0 1", FenError::BadCastling),
+        ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq z9 0 1", FenError::BadEnPassant),
+        ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - a 1", FenError::BadClock),
+        ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 -1", FenError::BadClock),
+    ];
+
+    for (fen, error) in errors{
+        assert_eq!(Position::try_from_fen(fen).err(), Some(error), "{}", fen);
+    }
+}
+
+#[test]
+pub fn queen_odds_game_test(){
+    //stalemate with white a queen up still scores as a dead draw
+    let stalemate = Position::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").evaluate();
+    assert!(stalemate.game_state == GameState::DRAW);
+    assert_eq!(stalemate.score, Some(0.0));
+
+    //mates found deeper in the tree score slightly lower than immediate ones
+    assert!(mate_distance_score(1000000.0, 1) > mate_distance_score(1000000.0, 3));
+    assert!(mate_distance_score(-1000000.0, 1) < mate_distance_score(-1000000.0, 3));
+
+    //black gave queen odds and is left with a bare king, self play has to run to a finished game with a consistent result
+    let mut game = Game::from_fen("k7/8/1K6/8/8/8/8/7Q w - - 0 1");
+    game.set_max_depth(2);
+    let result = game.play(None);
+
+    assert_eq!(game.result(), Some(result));
+    assert_eq!(game.termination(), Some(Termination::Checkmate));
+    let final_eval = game.get_position().evaluate();
+    match result{
+        GameResult::WhiteWins => assert!(final_eval.game_state == GameState::CHECKMATE && game.get_position().side_to_move == Side::BLACK),
+        GameResult::BlackWins => assert!(final_eval.game_state == GameState::CHECKMATE && game.get_position().side_to_move == Side::WHITE),
+        GameResult::Draw(_) => assert!(final_eval.game_state != GameState::CHECKMATE),
+    }
+    assert!(game.get_pgn().ends_with(&format!("{}", result)));
+}
+
+#[test]
+pub fn square_iteration_test(){
+    let squares: Vec<Square> = Square::all().collect();
+    assert_eq!(squares.len(), 64);
+    assert_eq!(squares[0], Square::A1);
+    assert_eq!(squares[63], Square::H8);
+    assert_eq!(squares, (0..64).collect::<Vec<Square>>());
+
+    let mut covered: Bitboard = 0;
+    for (square, bitboard) in Square::all_with_bitboards(){
+        assert_eq!(bitboard, square.to_bitboard());
+        assert_eq!(covered & bitboard, 0);
+        covered |= bitboard;
+    }
+    assert_eq!(covered, u64::MAX);
+}
+
+#[test]
+pub fn slider_blocker_bits_test(){
+    //relevant occupancy bit counts per square, A1 to H8
+    let rook_bits: [u32; 64] = [
+        12, 11, 11, 11, 11, 11, 11, 12,
+        11, 10, 10, 10, 10, 10, 10, 11,
+        11, 10, 10, 10, 10, 10, 10, 11,
+        11, 10, 10, 10, 10, 10, 10, 11,
+        11, 10, 10, 10, 10, 10, 10, 11,
+        11, 10, 10, 10, 10, 10, 10, 11,
+        11, 10, 10, 10, 10, 10, 10, 11,
+        12, 11, 11, 11, 11, 11, 11, 12,
+    ];
+    let bishop_bits: [u32; 64] = [
+        6, 5, 5, 5, 5, 5, 5, 6,
+        5, 5, 5, 5, 5, 5, 5, 5,
+        5, 5, 7, 7, 7, 7, 5, 5,
+        5, 5, 7, 9, 9, 7, 5, 5,
+        5, 5, 7, 9, 9, 7, 5, 5,
+        5, 5, 7, 7, 7, 7, 5, 5,
+        5, 5, 5, 5, 5, 5, 5, 5,
+        6, 5, 5, 5, 5, 5, 5, 6,
+    ];
+
+    let rook_blockers = get_rook_blockers();
+    let bishop_blockers = get_bishop_blockers();
+    for square in Square::all(){
+        assert_eq!(rook_blockers[square as usize].count_ones(), rook_bits[square as usize], "rook blockers on {}", square.as_string());
+        assert_eq!(bishop_blockers[square as usize].count_ones(), bishop_bits[square as usize], "bishop blockers on {}", square.as_string());
+        assert_eq!(rook_blockers[square as usize] & square.to_bitboard(), 0);
+    }
+
+    assert_eq!(rook_blockers[Square::A1 as usize], 0x000101010101017E);
+    assert_eq!(rook_blockers[Square::D4 as usize], 0x0008080876080800);
+    assert_eq!(rook_blockers[Square::H8 as usize], 0x7E80808080808000);
+}
+
+#[test]
+pub fn shared_zobrist_keys_test(){
+    let fens = [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R b Kq - 0 1",
+        "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+    ];
+
+    for fen in fens{
+        assert_eq!(Position::from_fen(fen).hash(), Position::from_fen(fen).hash());
+        assert!(Position::from_fen(fen) == Position::from_fen(fen));
+    }
+    assert_eq!(Position::new_game().hash(), Position::from_fen(fens[0]).hash());
+    assert_ne!(Position::from_fen(fens[0]).hash(), Position::from_fen(fens[1]).hash());
+
+    //the same position reached through different move orders hashes the same
+    let mut a = Position::new_game();
+    for uci in ["g1f3", "g8f6", "b1c3"]{
+        a = a.make_move(find_move(&a, uci)).unwrap();
+    }
+    let mut b = Position::new_game();
+    for uci in ["b1c3", "g8f6", "g1f3"]{
+        b = b.make_move(find_move(&b, uci)).unwrap();
+    }
+    assert_eq!(a.hash(), b.hash());
+}
+
+#[test]
+pub fn weaknesses_test(){
+    //knight attacked by a pawn with no defender, king safely tucked behind its pawns
+    let position = Position::from_fen("6k1/5ppp/8/8/1p6/2N5/5PPP/6K1 w - - 0 1");
+    let knight = Square::C3.to_bitboard();
+    assert_eq!(position.attackers_to(Square::C3, Side::BLACK, position.pieces[Side::WHITE].occupancy() | position.pieces[Side::BLACK].occupancy()), Square::B4.to_bitboard());
+    assert_eq!(position.hanging_pieces(Side::WHITE), knight);
+    assert_eq!(position.king_zone_pressure(Side::WHITE), 0);
+    assert_eq!(position.weaknesses(Side::WHITE), knight);
+    assert_eq!(position.weaknesses(Side::BLACK), 0);
+
+    //the same knight defended by a pawn is no longer hanging
+    let defended = Position::from_fen("6k1/5ppp/8/8/1p6/2N5/1P3PPP/6K1 w - - 0 1");
+    assert_eq!(defended.hanging_pieces(Side::WHITE), 0);
+
+    //bare king with a rook on its second rank
+    let exposed = Position::from_fen("6k1/5ppp/8/8/8/8/r7/6K1 w - - 0 1");
+    let second_rank = Square::F2.to_bitboard() | Square::G2.to_bitboard() | Square::H2.to_bitboard();
+    assert_eq!(exposed.king_zone_pressure(Side::WHITE), second_rank);
+    assert_eq!(exposed.weaknesses(Side::WHITE) & second_rank, second_rank);
+
+    //pawnless king with nothing attacking still has holes in front of it
+    let holes = Position::from_fen("6k1/5ppp/8/8/8/8/8/6K1 w - - 0 1");
+    assert_eq!(holes.king_zone_pressure(Side::WHITE), 0);
+    assert_eq!(holes.weaknesses(Side::WHITE), second_rank);
+}
+
+#[test]
+pub fn tree_minimax_test(){
+    //back rank mate with the rook
+    let position = Position::from_fen("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1");
+    let mut tree = PositionTree::new(position);
+    let best_moves = tree.expand_to_depth(3, ExpandStyle::DEFAULT, Side::WHITE);
+    assert_eq!(best_moves[0].get_tstring(), "a1a8");
+
+    //every expanded node carries the best score of its children for its side to move
+    for (index, node) in tree.values.iter(){
+        if let Some(children) = tree.get_children(*index){
+            let scores: Vec<f32> = children.iter().map(|c| tree.get_score(*c).unwrap()).collect();
+            assert_eq!(node.score, best_score(node.position.side_to_move, &scores));
+        }
+    }
+
+    //black to move mates on the back rank as well
+    let position = Position::from_fen("r5k1/5ppp/8/8/8/8/5PPP/6K1 b - - 0 1");
+    let mut tree = PositionTree::new(position);
+    let best_moves = tree.expand_to_depth(3, ExpandStyle::DEFAULT, Side::BLACK);
+    assert_eq!(best_moves[0].get_tstring(), "a8a1");
+}
+
+#[test]
+pub fn opening_depth_test(){
+    let mut game = Game::new();
+    game.set_max_depth(6);
+    assert_eq!(game.get_opening_plies(), 0);
+    assert_eq!(game.get_search_depth(), 6);
+
+    game.set_opening(8, 2);
+    assert_eq!(game.get_opening_plies(), 8);
+    assert_eq!(game.get_opening_depth(), 2);
+    assert_eq!(game.get_search_depth(), 2);
+
+    game.set_opening(0, 2);
+    assert_eq!(game.get_search_depth(), 6);
+}
+
+#[test]
+pub fn rook_capture_castling_test(){
+    //(fen, capturing move, rights left afterwards as [white king side, white queen side, black king side, black queen side])
+    let cases = [
+        ("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", "a1a8", [true, false, true, false]),
+        ("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", "h1h8", [false, true, false, true]),
+        ("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1", "a8a1", [true, false, true, false]),
+        ("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1", "h8h1", [false, true, false, true]),
+        ("r3k2r/8/1N6/8/8/8/8/4K3 w kq - 0 1", "b6a8", [false, false, true, false]),
+        ("r3k2r/6P1/8/8/8/8/8/4K3 w kq - 0 1", "g7h8q", [false, false, false, true]),
+    ];
+
+    for (fen, uci, expected) in cases{
+        let position = Position::from_fen(fen);
+        let rights = position.make_move(find_move(&position, uci)).unwrap().castling_rights;
+        assert_eq!([rights.white_king_side, rights.white_queen_side, rights.black_king_side, rights.black_queen_side], expected, "{} in {}", uci, fen);
+    }
+}
+
+#[test]
+pub fn same_transition_test(){
+    let position = Position::from_fen("r3k2r/8/8/3p4/4P3/8/8/R3K2R w KQkq - 0 1");
+    let capture = find_move(&position, "e4d5");
+    assert!(capture.capture.is_some());
+
+    //the same capture built without looking up the captured piece
+    let bare = Move{
+        translation: capture.translation,
+        promotion: None,
+        capture: None,
+        castling: None,
+        en_passant: None,
+    };
+    assert!(bare != capture);
+    assert!(bare.same_transition(&capture));
+    assert!(position.find_legal_move(&bare) == Some(capture));
+
+    let push = find_move(&position, "e4e5");
+    assert!(!push.same_transition(&capture));
+
+    //castling differs from a plain king move between the same squares
+    let castle = find_move(&position, "e1g1");
+    let king_walk = Move{ castling: None, ..castle };
+    assert!(!king_walk.same_transition(&castle));
+    assert!(position.find_legal_move(&king_walk).is_none());
+
+    //positions evaluate scores as drawn still have their legal moves
+    let fifty_moves = "8/8/4k3/8/8/3K4/8/4R3 w - - 100 80";
+    let rook_lift = find_move(&Position::from_fen(fifty_moves), "e1e2");
+    assert!(Position::from_fen(fifty_moves).find_legal_move(&Move{ capture: None, ..rook_lift }) == Some(rook_lift));
+    let line = ["g1f3", "e6d5", "f3d4"];
+    let game = Game::from_moves("8/8/4k3/8/8/8/8/4K1N1 w - - 0 1", moves_from_uci("8/8/4k3/8/8/8/8/4K1N1 w - - 0 1", &line));
+    assert_eq!(game.get_position().pieces[Side::WHITE][KNIGHT], Square::D4.to_bitboard());
+    assert!(game.validate_history().is_ok());
+}
+
+#[test]
+pub fn en_passant_edge_file_test(){
+    //a shifted a4 or h4 must not wrap around to a pawn on the other edge
+    let cases = [
+        ("4k3/8/8/8/8/7p/P7/4K3 w - - 0 1", "a2a4", None),
+        ("4k3/8/8/p7/8/8/7P/4K3 w - - 0 1", "h2h4", None),
+        ("4k3/p7/8/8/7P/8/8/4K3 b - - 0 1", "a7a5", None),
+        ("4k3/7p/8/P7/8/8/8/4K3 b - - 0 1", "h7h5", None),
+        ("4k3/8/8/8/1p6/8/P7/4K3 w - - 0 1", "a2a4", Some(Square::A3)),
+        ("4k3/8/8/8/6p1/8/7P/4K3 w - - 0 1", "h2h4", Some(Square::H3)),
+    ];
+
+    for (fen, uci, expected) in cases{
+        let position = Position::from_fen(fen);
+        let after = position.make_move(find_move(&position, uci)).unwrap();
+        assert_eq!(after.en_passant_square, expected, "{} in {}", uci, fen);
+    }
+}
+
+#[test]
+pub fn search_terminal_root_test(){
+    //fool's mate, white is checkmated
+    let mated = Position::from_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3").search(3);
+    assert!(mated.best_move.is_none());
+    assert!(mated.game_state == GameState::CHECKMATE);
+    assert_eq!(mated.score, -1000000.0);
+
+    let stalemate = Position::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").search(3);
+    assert!(stalemate.best_move.is_none());
+    assert!(stalemate.game_state == GameState::DRAW);
+    assert_eq!(stalemate.score, 0.0);
+
+    let bare_kings = Position::from_fen("8/8/4k3/8/8/3K4/8/8 w - - 0 1").search(3);
+    assert!(bare_kings.best_move.is_none());
+    assert!(bare_kings.game_state == GameState::DRAW);
+    assert_eq!(bare_kings.score, 0.0);
+
+    //a live root still returns a move, here the back rank mate
+    let live = Position::from_fen("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1").search(3);
+    assert_eq!(live.best_move.unwrap().get_tstring(), "a1a8");
+    assert!(live.score > 900000.0);
+}
+
+#[test]
+pub fn en_passant_rank_pin_test(){
+    //(fen, en passant capture, whether it is legal)
+    let cases = [
+        ("8/8/8/K2pP2r/8/8/8/k7 w - d6 0 1", "e5d6", false),
+        ("8/8/8/r2pP2K/8/8/8/k7 w - d6 0 1", "e5d6", false),
+        ("8/8/8/8/Q2Pp2k/8/8/K7 b - d3 0 1", "e4d3", false),
+        ("8/8/8/K2pP2r/8/8/8/k7 w - - 0 1", "e5d6", false),
+        ("8/8/8/KP1pP2r/8/8/8/k7 w - d6 0 1", "e5d6", true),
+        ("8/8/8/K2pPp1r/8/8/8/k7 w - d6 0 1", "e5d6", true),
+        ("7k/8/8/3pP3/8/8/8/K7 w - d6 0 1", "e5d6", true),
+    ];
+
+    for (fen, uci, legal) in cases{
+        let position = Position::from_fen(fen);
+        let moves: Vec<String> = position.evaluate().moves.iter().map(|m| m.get_tstring()).collect();
+        assert_eq!(moves.contains(&uci.to_string()), legal, "{} in {}", uci, fen);
+    }
+}
+
+#[test]
+pub fn dead_position_rule_test(){
+    //(fen, dead under STRICT, dead under PRACTICAL)
+    let cases = [
+        ("8/8/4k3/8/8/3K4/8/8 w - - 0 1", true, true),
+        ("8/8/4k3/8/8/3K4/8/5N2 w - - 0 1", true, true),
+        ("8/8/4k3/8/8/3K4/8/5B2 w - - 0 1", true, true),
+        ("8/8/4k3/8/8/3K4/8/4NN2 w - - 0 1", false, true),
+        ("8/8/2n1k3/8/8/3K4/8/4NN2 w - - 0 1", false, true),
+        ("8/8/4k3/8/8/3K4/8/4NB2 w - - 0 1", false, false),
+        ("8/8/4k3/8/8/3K4/8/4BB2 w - - 0 1", false, false),
+        ("8/8/4k3/8/8/3K4/4P3/8 w - - 0 1", false, false),
+        ("8/8/4k3/8/8/3K4/8/4R3 w - - 0 1", false, false),
+    ];
+
+    for (fen, strict, practical) in cases{
+        let position = Position::from_fen(fen);
+        assert_eq!(position.is_dead_position(MaterialRule::STRICT), strict, "strict {}", fen);
+        assert_eq!(position.is_dead_position(MaterialRule::PRACTICAL), practical, "practical {}", fen);
+    }
+
+    //evaluate follows the FIDE rule and plays KNN vs K on
+    let knights = Position::from_fen("8/8/4k3/8/8/3K4/8/4NN2 w - - 0 1").evaluate();
+    assert!(knights.game_state != GameState::DRAW);
+}
+
+#[test]
+pub fn pinned_piece_in_check_test(){
+    //the rook on e4 is pinned on the e-file, taking the checking bishop along the rank would expose the king
+    let fens = [
+        "4r1k1/8/8/8/1b2R3/8/8/4K3 w - - 0 1",
+        "4q1k1/8/8/8/1b2Q3/8/8/4K3 w - - 0 1",
+        "4k3/8/8/4r3/8/4R1b1/8/4K3 w - - 0 1",
+        "7k/8/8/8/8/2b5/8/r1R1K3 w - - 0 1",
+    ];
+
+    for fen in fens{
+        let position = Position::from_fen(fen);
+        let pinned: Bitboard = position.pieces[Side::WHITE][ROOK] | position.pieces[Side::WHITE][QUEEN];
+        for m in position.evaluate().moves{
+            assert_eq!(m.translation.unwrap().from.to_bitboard() & pinned, 0, "pinned piece moved with {} in {}", m.get_tstring(), fen);
+        }
+    }
+}
+
+#[test]
+pub fn validate_history_test(){
+    let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+    let mut position = Position::from_fen(start);
+    let mut moves = Vec::new();
+    for uci in ["e2e4", "e7e5", "g1f3", "b8c6", "f1c4"]{
+        let m = find_move(&position, uci);
+        position = position.make_move(m).unwrap();
+        moves.push(m);
+    }
+
+    let game = Game::from_moves(start, moves.clone());
+    assert!(game.validate_history().is_ok());
+    assert_eq!(game.get_played_moves().len(), 5);
+    assert!(*game.get_position() == position);
+
+    //a king walking two squares in the middle of the game is rejected at its index
+    let bad = Move{
+        translation: Some(Translation{ from: Square::E8, to: Square::E6 }),
+        promotion: None,
+        capture: None,
+        castling: None,
+        en_passant: None,
+    };
+    let mut corrupted = moves.clone();
+    corrupted.insert(3, bad);
+    let game = Game::from_moves(start, corrupted);
+    assert!(game.validate_history() == Err((3, bad)));
+    assert_eq!(game.get_played_moves().len(), 6);
+    assert_eq!(game.get_move_history().len(), 3);
+}
+
+#[test]
+pub fn free_rook_blocks_check_test(){
+    //c1 rook is pinned along the first rank, the f7 rook is free and can block the bishop's check on f2
+    let position = Position::from_fen("k7/5R2/8/8/7b/8/8/r1R1K3 w - - 0 1");
+    let eval = position.evaluate();
+    assert!(eval.game_state == GameState::CHECK);
+
+    let moves: Vec<String> = eval.moves.iter().map(|m| m.get_tstring()).collect();
+    assert!(moves.contains(&"f7f2".to_string()), "free rook block missing: {:?}", moves);
+    assert!(moves.iter().all(|m| !m.starts_with("c1")), "pinned rook moved: {:?}", moves);
+
+    //same with the pin on the file and the free rook blocking along a rank
+    let position = Position::from_fen("4r2k/8/8/8/1b2R3/8/7R/4K3 w - - 0 1");
+    let moves: Vec<String> = position.evaluate().moves.iter().map(|m| m.get_tstring()).collect();
+    assert!(moves.contains(&"h2d2".to_string()), "free rook block missing: {:?}", moves);
+    assert!(moves.iter().all(|m| !m.starts_with("e4")), "pinned rook moved: {:?}", moves);
+
+    //one rook pinned on the rank and one on the file, the file-pinned e3 rook must not slide across to block on c3
+    let position = Position::from_fen("4r2k/8/8/8/1b6/4R3/8/r1R1K3 w - - 0 1");
+    let moves: Vec<String> = position.evaluate().moves.iter().map(|m| m.get_tstring()).collect();
+    assert!(moves.iter().all(|m| !m.starts_with("e3") && !m.starts_with("c1")), "pinned rook moved: {:?}", moves);
+    assert!(moves.contains(&"e1e2".to_string()), "king moves missing: {:?}", moves);
+}
+
+#[test]
+pub fn can_castle_in_check_test(){
+    //the king's own square is attacked, both directions are refused even though the paths are clear
+    let cases = [
+        ("r3k2r/8/8/8/8/8/8/R3K2r w KQkq - 0 1", Side::WHITE),
+        ("r3k2r/8/8/8/1b6/8/8/R3K2R w KQkq - 0 1", Side::WHITE),
+        ("r3k2r/8/3N4/8/8/8/8/R3K2R b KQkq - 0 1", Side::BLACK),
+        ("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1", Side::WHITE),
+    ];
+
+    for (fen, side) in cases{
+        let position = Position::from_fen(fen);
+        let in_check = position.attackers_to(if side == Side::WHITE { Square::E1 } else { Square::E8 }, !side, position.pieces[Side::WHITE].occupancy() | position.pieces[Side::BLACK].occupancy()) != 0;
+        assert_eq!(position.can_castle(side, KING_SIDE), !in_check, "king side in {}", fen);
+        assert_eq!(position.can_castle(side, QUEEN_SIDE), !in_check, "queen side in {}", fen);
+    }
+
+    //rights in the FEN without the pieces to back them up
+    let position = Position::from_fen("4k3/8/8/8/8/8/8/R2K3R w KQ - 0 1");
+    assert!(!position.can_castle(Side::WHITE, KING_SIDE));
+    assert!(!position.can_castle(Side::WHITE, QUEEN_SIDE));
+}
+
+#[test]
+pub fn two_queens_pin_test(){
+    //the d2 queen is pinned on the e1-a5 diagonal, the h8 queen is free
+    let position = Position::from_fen("7Q/k7/8/b7/8/8/3Q4/4K3 w - - 0 1");
+    let moves: Vec<String> = position.evaluate().moves.iter().map(|m| m.get_tstring()).collect();
+
+    //same position with the pinned queen swapped for a knight gives the free queen's full move set
+    let reference = Position::from_fen("7Q/k7/8/b7/8/8/3N4/4K3 w - - 0 1");
+    let mut free_queen: Vec<String> = moves.iter().filter(|m| m.starts_with("h8")).cloned().collect();
+    let mut expected: Vec<String> = reference.evaluate().moves.iter().map(|m| m.get_tstring()).filter(|m| m.starts_with("h8")).collect();
+    free_queen.sort();
+    expected.sort();
+    assert_eq!(free_queen.len(), 21);
+    assert_eq!(free_queen, expected);
+
+    let mut pinned_queen: Vec<String> = moves.iter().filter(|m| m.starts_with("d2")).cloned().collect();
+    pinned_queen.sort();
+    assert_eq!(pinned_queen, vec!["d2a5", "d2b4", "d2c3"]);
+}
+
+#[test]
+pub fn game_termination_test(){
+    let cases = [
+        ("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1", GameResult::Draw(DrawReason::Stalemate), Termination::Stalemate),
+        ("8/8/4k3/8/8/3K4/8/8 w - - 0 1", GameResult::Draw(DrawReason::InsufficientMaterial), Termination::InsufficientMaterial),
+        ("8/8/4k3/8/8/3K4/8/4R3 w - - 100 80", GameResult::Draw(DrawReason::FiftyMoveRule), Termination::FiftyMoveRule),
+        ("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3", GameResult::BlackWins, Termination::Checkmate),
+    ];
+
+    for (fen, result, termination) in cases{
+        //the finished starting position is scored as soon as the game is set up
+        let mut game = Game::from_fen(fen);
+        assert_eq!(game.result(), Some(result));
+        assert_eq!(game.play(None), result);
+        assert_eq!(game.result(), Some(result));
+        assert_eq!(game.termination(), Some(termination));
+    }
+
+    let mut game = Game::new();
+    assert_eq!(game.resign(Side::WHITE), Ok(()));
+    assert_eq!(game.result(), Some(GameResult::BlackWins));
+    assert_eq!(game.termination(), Some(Termination::Resignation));
+
+    //white's flag falls but black has only a king left
+    let mut game = Game::from_fen("8/8/4k3/8/8/3K4/8/4R3 w - - 0 1");
+    assert_eq!(game.lose_on_time(Side::WHITE), Ok(()));
+    assert_eq!(game.result(), Some(GameResult::Draw(DrawReason::InsufficientMaterial)));
+    assert_eq!(game.termination(), Some(Termination::Timeout));
+
+    //the first result stands
+    assert_eq!(game.lose_on_time(Side::BLACK), Err(MoveError::GameOver));
+    assert_eq!(game.resign(Side::WHITE), Err(MoveError::GameOver));
+    assert_eq!(game.result(), Some(GameResult::Draw(DrawReason::InsufficientMaterial)));
+    assert_eq!(game.termination(), Some(Termination::Timeout));
+
+    let mut game = Game::from_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3");
+    assert_eq!(game.resign(Side::BLACK), Err(MoveError::GameOver));
+    assert_eq!(game.result(), Some(GameResult::BlackWins));
+    assert_eq!(game.termination(), Some(Termination::Checkmate));
+}
+
+fn piece_counts(position: &Position) -> [[u32; 6]; 2]{
+    let mut counts = [[0; 6]; 2];
+    for side in 0..2{
+        for piece in 0..6{
+            counts[side][piece] = position.pieces[side][piece].count_ones();
+        }
+    }
+    return counts;
+}
+
+#[test]
+pub fn make_move_piece_count_test(){
+    use rand::{Rng, SeedableRng};
+
+    let file = std::fs::File::open("./src/../testfens.json").unwrap();
+    let json: Value = serde_json::from_reader(std::io::BufReader::new(file)).unwrap();
+    let fens: Vec<&String> = json.as_object().unwrap().keys().collect();
+
+    let mut rng = rand_pcg::Pcg64::seed_from_u64(1515);
+
+    for _ in 0..200{
+        let mut position = Position::from_fen(fens[rng.gen_range(0..fens.len())]);
+
+        //random walk from the sampled position
+        for _ in 0..20{
+            let moves = position.evaluate().moves;
+            if moves.is_empty(){
+                break;
+            }
+            let m = moves[rng.gen_range(0..moves.len())];
+            let us = position.side_to_move.index();
+            let them = 1 - us;
+
+            let mut expected = piece_counts(&position);
+            if let Some(capture) = m.capture{
+                expected[them][capture] -= 1;
+            }
+            if let Some(promotion) = m.promotion{
+                expected[us][PAWN] -= 1;
+                expected[us][promotion] += 1;
+            }
+
+            let after = position.make_move(m).unwrap();
+            assert_eq!(piece_counts(&after), expected, "{} in {}", m.get_tstring(), position.to_fen());
+
+            //no square holds two pieces
+            let mut union: Bitboard = 0;
+            let mut total = 0;
+            for side in 0..2{
+                for piece in 0..6{
+                    union |= after.pieces[side][piece];
+                    total += after.pieces[side][piece].count_ones();
+                }
+            }
+            assert_eq!(union.count_ones(), total, "overlapping pieces after {} in {}", m.get_tstring(), position.to_fen());
+
+            position = after;
+        }
+    }
+}
+
+#[test]
+pub fn insufficient_material_bishops_test(){
+    //(fen, drawn by insufficient material)
+    let cases = [
+        ("8/8/4k3/8/8/3K4/8/2B5 w - - 0 1", true),
+        ("5b2/8/4k3/8/8/3K4/8/2B5 w - - 0 1", true),
+        ("2b5/8/4k3/8/8/3K4/8/2B5 w - - 0 1", false),
+        ("8/8/4k3/8/8/3K4/8/2B2B2 w - - 0 1", false),
+        ("8/8/4k3/8/8/3K4/8/2B1B3 w - - 0 1", true),
+        ("5n2/8/4k3/8/8/3K4/8/2B5 w - - 0 1", false),
+        ("5n2/8/4k3/8/8/3K4/8/2N5 w - - 0 1", false),
+        ("8/8/4k3/8/8/3K4/8/2N5 w - - 0 1", true),
+    ];
+
+    for (fen, drawn) in cases{
+        let eval = Position::from_fen(fen).evaluate();
+        assert_eq!(eval.game_state == GameState::DRAW, drawn, "{}", fen);
+    }
+}
+
+#[test]
+pub fn double_push_en_passant_test(){
+    //(fen, double push, en passant square afterwards)
+    let cases = [
+        ("4k3/4p3/8/3P4/8/8/8/4K3 b - - 0 1", "e7e5", Some(Square::E6)),
+        ("4k3/1p6/8/P7/8/8/8/4K3 b - - 0 1", "b7b5", Some(Square::B6)),
+        ("4k3/8/8/8/3p4/8/4P3/4K3 w - - 0 1", "e2e4", Some(Square::E3)),
+        ("4k3/4p3/8/8/8/8/8/4K3 b - - 0 1", "e7e5", None),
+        ("4k3/4p3/8/3P4/8/8/8/4K3 b - - 0 1", "e7e6", None),
+    ];
+
+    for (fen, uci, expected) in cases{
+        let position = Position::from_fen(fen);
+        let after = position.make_move(find_move(&position, uci)).unwrap();
+        assert_eq!(after.en_passant_square, expected, "{} in {}", uci, fen);
+        assert_eq!(after.zobrist_hash, ZOBRIST.hash_position(&after));
+    }
+
+    //the square set by black's push can be used straight away
+    let position = Position::from_fen("4k3/4p3/8/3P4/8/8/8/4K3 b - - 0 1");
+    let after = position.make_move(find_move(&position, "e7e5")).unwrap();
+    let moves: Vec<String> = after.evaluate().moves.iter().map(|m| m.get_tstring()).collect();
+    assert!(moves.contains(&"d5e6".to_string()));
+}
+
+fn moves_from_uci(fen: &str, line: &[&str]) -> Vec<Move>{
+    let mut position = Position::from_fen(fen);
+    let mut moves = Vec::new();
+    for uci in line{
+        let m = find_move(&position, uci);
+        position = position.make_move(m).unwrap();
+        moves.push(m);
+    }
+    return moves;
+}
+
+#[test]
+pub fn repetition_window_test(){
+    let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+    let shuffle = ["g1f3", "g8f6", "f3g1", "f6g8"];
+
+    //the start position comes up a third time after two knight shuffles
+    let line: Vec<&str> = shuffle.iter().chain(shuffle.iter()).cloned().collect();
+    let game = Game::from_moves(start, moves_from_uci(start, &line));
+    assert_eq!(game.result(), Some(GameResult::Draw(DrawReason::ThreefoldRepetition)));
+    assert_eq!(game.termination(), Some(Termination::Repetition));
+    assert_eq!(game.get_history().len(), 8);
+
+    //a pawn move between the shuffles starts a new window
+    let line = ["g1f3", "g8f6", "f3g1", "f6g8", "e2e4", "g8f6", "g1f3", "f6g8", "f3g1"];
+    let game = Game::from_moves(start, moves_from_uci(start, &line));
+    assert_eq!(game.result(), None);
+    assert_eq!(game.get_history().len(), 4);
+
+    //castling clears it as well
+    let fen = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+    let game = Game::from_moves(fen, moves_from_uci(fen, &["a1b1", "a8b8", "e1g1"]));
+    assert!(game.get_history().is_empty());
+}
+
+#[test]
+pub fn side_indexing_test(){
+    let mut position = Position::new_game();
+    assert_eq!(position.pieces[Side::WHITE], position.pieces[0]);
+    assert_eq!(position.pieces[Side::BLACK][KING], Square::E8.to_bitboard());
+
+    position.pieces[Side::BLACK][QUEEN] = 0;
+    assert_eq!(position.pieces[1][QUEEN], 0);
+}
+
+#[test]
+pub fn zobrist_stack_capacity_test(){
+    let mut stack = ZobristMoveStack::new();
+    for hash in 1..=150u64{
+        stack.add(hash);
+    }
+    assert_eq!(stack.len(), 150);
+    assert_eq!(stack.get_repetitions(1), 1);
+    assert_eq!(stack.get_repetitions(150), 1);
+    assert_eq!(stack.get_repetitions(0), 0);
+
+    stack.add(150);
+    assert_eq!(stack.get_repetitions(150), 2);
+    assert_eq!(stack.get_repetitions(149), 1);
+}
+
+#[test]
+pub fn legal_moves_test(){
+    let fens = [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        "4k3/8/8/8/8/8/4r3/4K3 w - - 0 1",
+        "k7/8/1Q6/8/8/8/8/7K b - - 0 1",
+    ];
+
+    for fen in fens{
+        let position = Position::from_fen(fen);
+        assert_eq!(position.legal_moves().len(), position.evaluate().moves.len(), "{}", fen);
+    }
+
+    assert_eq!(Position::new_game().legal_moves().len(), 20);
+}
+
+#[test]
+pub fn pawn_attacks_bb_test(){
+    let fens = [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "4k3/p6p/8/1P4P1/8/8/P6P/4K3 w - - 0 1",
+        "1k1r3r/pppqb1pp/1nn1p3/3bPp2/1P1PN3/P2BBN2/5PPP/2RQ1RK1 w - f6 0 15",
+    ];
+
+    for fen in fens{
+        let position = Position::from_fen(fen);
+        for side in [Side::WHITE, Side::BLACK]{
+            let mut expected: Bitboard = 0;
+            for square in position.pieces[side][PAWN].squares(){
+                expected |= get_pawn_attacks(side, square);
+            }
+            assert_eq!(position.pawn_attacks_bb(side), expected, "{} {}", fen, side);
+        }
+    }
+}
+
+#[test]
+pub fn make_unmake_move_test(){
+    let fens = [
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+        "r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 3 20",
+    ];
+
+    for fen in fens{
+        let mut position = Position::from_fen(fen);
+        let original = position;
+        for m in original.legal_moves(){
+            let copied = original.make_move(m).unwrap();
+            let undo = position.make_move_mut(m).unwrap();
+            assert!(position == copied, "{} {}", fen, m);
+            assert_eq!((position.halfmove_clock, position.fullmove_number), (copied.halfmove_clock, copied.fullmove_number), "{} {}", fen, m);
+            assert_eq!(position.zobrist_hash, ZOBRIST.hash_position(&position), "{} {}", fen, m);
+            position.unmake_move(undo);
+            assert!(position == original, "{} {}", fen, m);
+            assert_eq!((position.halfmove_clock, position.fullmove_number, position.zobrist_hash), (original.halfmove_clock, original.fullmove_number, original.zobrist_hash), "{} {}", fen, m);
+        }
+    }
+}
+
+#[test]
+pub fn negamax_mate_in_two_test(){
+    //1. Rh7 Kb8 2. Rg8# or the same with the rooks swapped
+    let position = Position::from_fen("k7/8/8/8/8/8/7R/4K1R1 w - - 0 1");
+    let (score, best_move) = search(&position, 3, f32::NEG_INFINITY, f32::INFINITY);
+    assert_eq!(score, mate_distance_score(1000000.0, 3));
+    let best_move = best_move.unwrap();
+    assert!(["h2h7", "g1g7"].contains(&best_move.get_tstring().as_str()), "{}", best_move);
+
+    //no mate in one
+    let (score, _) = search(&position, 1, f32::NEG_INFINITY, f32::INFINITY);
+    assert!(score < mate_distance_score(1000000.0, 3));
+}
+
+#[test]
+pub fn trapped_pieces_test(){
+    //Bxa7 after ...b6, the bishop only has b8 left
+    let position = Position::from_fen("4k3/B1p5/1p6/8/8/8/8/4K3 w - - 0 1");
+    assert_eq!(position.trapped_pieces(Side::WHITE), Square::A7.to_bitboard());
+    assert_eq!(position.trapped_pieces(Side::BLACK), 0);
+
+    //the same bishop at home is not trapped
+    let position = Position::from_fen("4k3/2p5/1p6/8/8/8/8/B3K3 w - - 0 1");
+    assert_eq!(position.trapped_pieces(Side::WHITE), 0);
+
+    //mirrored for black, a knight on h1 whose only way out is taking on f2
+    let position = Position::from_fen("4k3/8/8/8/8/6P1/5P2/4K2n b - - 0 1");
+    assert_eq!(position.trapped_pieces(Side::BLACK), Square::H1.to_bitboard());
+
+    let trapped = Position::from_fen("4k3/B1p5/1p6/8/8/8/8/4K3 w - - 0 1").evaluate().score.unwrap();
+    let free = Position::from_fen("4k3/2p5/1p6/8/3B4/8/8/4K3 w - - 0 1").evaluate().score.unwrap();
+    assert!(trapped < free);
+}
+
+#[test]
+pub fn fen_en_passant_validation_test(){
+    //black just played d7d5 next to the e5 pawn
+    let position = Position::try_from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1").unwrap();
+    assert_eq!(position.en_passant_square, Some(Square::D6));
+
+    //after 1. e4 nothing can take, the field is dropped to match what make_move would produce
+    let position = Position::try_from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1").unwrap();
+    assert_eq!(position.en_passant_square, None);
+    let played = Position::new_game().make_move_coords(Square::E2, Square::E4, None).unwrap();
+    assert_eq!(position.zobrist_hash, played.zobrist_hash);
+
+    let errors = [
+        //wrong rank for the side to move
+        "4k3/8/8/3pP3/8/8/8/4K3 w - d3 0 1",
+        "4k3/8/8/3pP3/8/8/8/4K3 b - d6 0 1",
+        //no pawn that could have double pushed
+        "4k3/8/8/4P3/8/8/8/4K3 w - d6 0 1",
+        //the pawn could not have passed through an occupied square
+        "4k3/8/3n4/3pP3/8/8/8/4K3 w - d6 0 1",
+        "4k3/3n4/8/3pP3/8/8/8/4K3 w - d6 0 1",
+    ];
+    for fen in errors{
+        assert_eq!(Position::try_from_fen(fen).err(), Some(FenError::BadEnPassant), "{}", fen);
+    }
+}
+
+#[test]
+pub fn key_after_test(){
+    let fens = [
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+        "r3k2r/8/8/8/8/8/1p6/R3K2R b KQkq - 0 1",
+        "4k3/8/8/8/3p4/8/4P3/4K3 w - - 0 1",
+    ];
+
+    for fen in fens{
+        let position = Position::from_fen(fen);
+        for m in position.legal_moves(){
+            assert_eq!(position.key_after(m), position.make_move(m).unwrap().hash(), "{} {}", fen, m);
+        }
+    }
+}
+
+#[test]
+pub fn quiescence_recapture_test(){
+    //Qxd5 wins a pawn at depth 1 but c6 takes the queen back
+    let position = Position::from_fen("4k3/8/2p5/3p4/8/8/3Q4/4K3 w - - 0 1");
+    let greedy = position.make_move(find_move(&position, "d2d5")).unwrap();
+
+    let before = position.evaluate().score.unwrap();
+    assert!(greedy.evaluate().score.unwrap() > before);
+
+    //black to move after the capture, flip to white's point of view
+    let resolved = -quiescence(&greedy, f32::NEG_INFINITY, f32::INFINITY);
+    assert!(resolved < before - 500.0);
+
+    let (_, best_move) = search(&position, 1, f32::NEG_INFINITY, f32::INFINITY);
+    assert!(best_move.unwrap().get_tstring() != "d2d5");
+}
+
+#[test]
+pub fn quiescence_in_check_test(){
+    //the knight checks and forks the queen, white is a queen up but the king has to step away and lose it
+    let forked = Position::from_fen("6k1/7p/8/8/8/2Q5/P3n3/6K1 w - - 0 1");
+    assert!(forked.evaluate().score.unwrap() > 500.0);
+    assert!(quiescence(&forked, f32::NEG_INFINITY, f32::INFINITY) < 0.0);
+
+    //only quiet knight blocks get out of check and the rook takes either one with mate,
+    //standing pat would have scored it as white's extra knight
+    let blocked = Position::from_fen("6k1/5ppp/8/8/8/8/3N2PP/r6K w - - 0 1");
+    let evasions = blocked.legal_moves();
+    assert!(!evasions.is_empty() && evasions.iter().all(|m| m.capture.is_none() && m.promotion.is_none()));
+    //mate scores sit within a ply count of 1e6
+    assert!(quiescence(&blocked, f32::NEG_INFINITY, f32::INFINITY) <= -999000.0);
+
+    let mated = Position::from_fen("4R1k1/5ppp/8/8/8/8/8/6K1 b - - 0 1");
+    assert!(quiescence(&mated, f32::NEG_INFINITY, f32::INFINITY) <= -999000.0);
+}
+
+#[test]
+pub fn capture_moves_test(){
+    let fens = [
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        "rnb1kbnr/pppp1ppp/8/4p3/5PPq/8/PPPPP2P/RNBQKBNR w KQkq - 1 3",
+        "4k3/8/8/8/1b6/8/3R4/4K3 w - - 0 1",
+    ];
+
+    for fen in fens{
+        let position = Position::from_fen(fen);
+        let mut captures: Vec<String> = position.capture_moves().iter().map(|m| m.get_tstring()).collect();
+        assert!(position.capture_moves().iter().all(|m| m.capture.is_some() || m.promotion.is_some()), "{}", fen);
+
+        let mut expected: Vec<String> = position.legal_moves().iter().filter(|m| m.capture.is_some() || m.promotion.is_some()).map(|m| m.get_tstring()).collect();
+        captures.sort();
+        expected.sort();
+        assert_eq!(captures, expected, "{}", fen);
+    }
+}
+
+#[test]
+pub fn see_test(){
+    //exd5 cxd5 Qxd5 wins a pawn, Qxd5 cxd5 exd5 gives the queen for two pawns
+    let position = Position::from_fen("4k3/8/2p5/3p4/4P3/8/3Q4/4K3 w - - 0 1");
+    assert_eq!(position.see(find_move(&position, "e4d5")), 100.0);
+    assert_eq!(position.see(find_move(&position, "d2d5")), 200.0 - 900.0);
+    assert!(position.see_ge(find_move(&position, "e4d5"), 0.0));
+    assert!(!position.see_ge(find_move(&position, "d2d5"), 0.0));
+
+    //rook takes an undefended knight, the queen behind the rook x-rays through
+    let position = Position::from_fen("4k3/8/8/3n4/8/8/3R4/3QK3 w - - 0 1");
+    assert_eq!(position.see(find_move(&position, "d2d5")), 300.0);
+
+    //a knight stepping onto a pawn-covered square just hangs
+    let position = Position::from_fen("4k3/8/4p3/8/8/2N5/8/4K3 w - - 0 1");
+    assert_eq!(position.see(find_move(&position, "c3d5")), -300.0);
+}
+
+#[test]
+pub fn see_pruning_node_count_test(){
+    let fens = [
+        "k7/8/8/8/8/8/7R/4K1R1 w - - 0 1",
+        "4k3/8/2p5/3p4/8/8/3Q4/4K3 w - - 0 1",
+        "4k3/8/8/3n4/8/8/3R4/3QK3 w - - 0 1",
+        "r3k3/8/8/8/8/8/4N3/4K3 w - - 0 1",
+    ];
+
+    let mut pruned_total = 0;
+    let mut full_total = 0;
+    for fen in fens{
+        let position = Position::from_fen(fen);
+
+        let mut pruned = Searcher::new();
+        let (_, pruned_move) = pruned.search(&position, 3, f32::NEG_INFINITY, f32::INFINITY);
+
+        let mut full = Searcher::new();
+        full.see_pruning = false;
+        let (_, full_move) = full.search(&position, 3, f32::NEG_INFINITY, f32::INFINITY);
+
+        assert!(pruned_move.unwrap().get_tstring() == full_move.unwrap().get_tstring(), "{}", fen);
+        assert!(pruned.nodes <= full.nodes, "{}", fen);
+        pruned_total += pruned.nodes;
+        full_total += full.nodes;
+    }
+    assert!(pruned_total < full_total);
+}
+
+//plain minimax without cutoffs, scored the way the searcher scores its nodes
+fn brute_force_minimax(position: &Position, depth: u8, ply: u8) -> f32{
+    let eval = position.evaluate();
+    let score = eval.score.unwrap_or(0.0);
+    let relative_score = if position.side_to_move == Side::WHITE { score } else { -score };
+
+    if eval.game_state == GameState::CHECKMATE{
+        return mate_distance_score(relative_score, ply);
+    }
+    if eval.game_state == GameState::DRAW{
+        return 0.0;
+    }
+    if depth == 0{
+        return relative_score;
+    }
+
+    return eval.moves.iter()
+        .filter_map(|m| position.make_move(*m))
+        .map(|child| -brute_force_minimax(&child, depth - 1, ply + 1))
+        .fold(f32::NEG_INFINITY, f32::max);
+}
+
+#[test]
+pub fn analysis_mode_exact_test(){
+    let fens = [
+        "4k3/8/2p5/3p4/4P3/8/3Q4/4K3 w - - 0 1",
+        "r3k3/8/8/8/8/8/4N3/4K3 w - - 0 1",
+        "k7/8/8/8/8/8/7R/4K1R1 w - - 0 1",
+    ];
+
+    for fen in fens{
+        let position = Position::from_fen(fen);
+        let mut searcher = Searcher::new();
+        searcher.analysis = true;
+        let (score, _) = searcher.search(&position, 3, f32::NEG_INFINITY, f32::INFINITY);
+        assert_eq!(score, brute_force_minimax(&position, 3, 0), "{}", fen);
+    }
+}
+
+#[test]
+pub fn move_from_uci_test(){
+    let position = Position::new_game();
+    let m = Move::from_uci("e2e4", &position).unwrap();
+    assert!(m.translation.unwrap().from == Square::E2 && m.translation.unwrap().to == Square::E4);
+    assert!(m.capture.is_none() && m.castling.is_none());
+
+    //promotion with a capture filled in from the position
+    let position = Position::from_fen("1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1");
+    let m = Move::from_uci("a7b8q", &position).unwrap();
+    assert_eq!(m.promotion, Some(QUEEN));
+    assert_eq!(m.capture, Some(ROOK));
+    assert!(Move::from_uci("a7a8N", &position).unwrap().promotion == Some(KNIGHT));
+
+    //the king's two step maps onto the castling move
+    let position = Position::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+    assert_eq!(Move::from_uci("e1g1", &position).unwrap().castling, Some(KING_SIDE));
+    assert_eq!(Move::from_uci("e1c1", &position).unwrap().castling, Some(QUEEN_SIDE));
+
+    //en passant is flagged
+    let position = Position::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1");
+    assert_eq!(Move::from_uci("e5d6", &position).unwrap().en_passant, Some(Square::D6));
+
+    let position = Position::new_game();
+    for illegal in ["e2e5", "e7e5", "e2e4x", "i2i4", "e2", "", "e1g1"]{
+        assert!(Move::from_uci(illegal, &position).is_none(), "{}", illegal);
+    }
+}
+
+#[test]
+pub fn san_test(){
+    let cases = [
+        ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", "g1f3", "Nf3"),
+        ("rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2", "e4d5", "exd5"),
+        ("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", "e1g1", "O-O"),
+        ("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", "e1c1", "O-O-O"),
+        ("7k/4P3/8/8/8/8/8/4K3 w - - 0 1", "e7e8q", "e8=Q+"),
+        ("k7/8/1K6/8/8/8/8/7Q w - - 0 1", "h1h8", "Qh8#"),
+        ("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6", "exd6"),
+    ];
+
+    for (fen, uci, san) in cases{
+        let position = Position::from_fen(fen);
+        let m = Move::from_uci(uci, &position).unwrap();
+        assert_eq!(m.to_san(&position), san, "{}", fen);
+    }
+}
+
+#[test]
+pub fn has_legal_move_test(){
+    let file = std::fs::File::open("./src/../testfens.json").unwrap();
+    let reader = std::io::BufReader::new(file);
+    let json: Value = serde_json::from_reader(reader).unwrap();
+
+    for fen in json.as_object().unwrap().keys(){
+        let position = Position::from_fen(fen);
+        assert_eq!(position.has_legal_move(), !position.legal_moves().is_empty(), "{}", fen);
+    }
+
+    //stalemate, checkmate, and a lone pinned piece next to a boxed in king
+    for fen in ["7k/5Q2/6K1/8/8/8/8/8 b - - 0 1", "k7/1Q6/1K6/8/8/8/8/8 b - - 0 1", "k7/1nK5/1P6/8/4B3/8/8/8 b - - 0 1"]{
+        let position = Position::from_fen(fen);
+        assert!(!position.has_legal_move(), "{}", fen);
+        assert!(position.legal_moves().is_empty(), "{}", fen);
+    }
+}
+
+#[test]
+pub fn san_disambiguation_test(){
+    let cases = [
+        ("rnbqkbnr/pppppppp/8/8/8/5N2/PPP1PPPP/RNBQKB1R w KQkq - 0 1", "b1d2", "Nbd2"),
+        ("4k3/8/8/R7/8/8/8/R3K3 w - - 0 1", "a1a3", "R1a3"),
+        //three queens on e1: h4 shares a file with h1 and a rank with e4
+        ("1k6/8/8/8/4Q2Q/8/K7/7Q w - - 0 1", "h4e1", "Qh4e1"),
+        ("1k6/8/8/8/4Q2Q/8/K7/7Q w - - 0 1", "e4e1", "Qee1"),
+        ("1k6/8/8/8/4Q2Q/8/K7/7Q w - - 0 1", "h1e1", "Q1e1"),
+    ];
+
+    for (fen, uci, san) in cases{
+        let position = Position::from_fen(fen);
+        let m = Move::from_uci(uci, &position).unwrap();
+        assert_eq!(m.to_san(&position), san, "{}", fen);
+    }
+}
+
+#[test]
+pub fn parse_san_test(){
+    let game = Game::new();
+    let san = game.parse_san("Nf3").unwrap();
+    let uci = Move::from_uci("g1f3", game.get_position()).unwrap();
+    assert!(san == uci);
+    assert!(game.parse_san("g1f3").is_none());
+    assert!(game.parse_san("Nf4").is_none());
+
+    let game = Game::from_fen("r6k/4P3/8/3p4/4P3/8/8/R3K2R w KQ - 0 1");
+    for (input, uci) in [("exd5", "e4d5"), ("O-O", "e1g1"), ("0-0-0", "e1c1"), ("e8=Q", "e7e8q"), ("e8=Q+", "e7e8q"), ("Rb1", "a1b1")]{
+        let expected = Move::from_uci(uci, game.get_position()).unwrap();
+        assert!(game.parse_san(input).unwrap() == expected, "{}", input);
+    }
+}
+
+#[test]
+pub fn pgn_export_test(){
+    let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+    let line = ["e2e4", "e7e5", "g1f3", "b8c6", "f1b5"];
+    let game = Game::from_moves(start, moves_from_uci(start, &line));
+    let pgn = game.get_pgn();
+    assert_eq!(pgn, "1. e4 e5 2. Nf3 Nc6 3. Bb5 *");
+
+    //the movetext parses back into the same game
+    let mut position = Position::from_fen(start);
+    for token in pgn.split_whitespace().filter(|token| !token.ends_with('.') && *token != "*"){
+        let m = Game::from_fen(&position.to_fen()).parse_san(token).unwrap();
+        position = position.make_move(m).unwrap();
+    }
+    assert!(position == *game.get_position());
+
+    //black to move keeps the starting move number
+    let fen = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 3 3";
+    let game = Game::from_moves(fen, moves_from_uci(fen, &["g8f6", "f1c4", "f8c5"]));
+    assert_eq!(game.get_pgn(), "3... Nf6 4. Bc4 Bc5 *");
+
+    let mut headers = PgnHeaders::new();
+    headers.white = "siegfried".to_string();
+    let pgn = game.get_pgn_with_headers(&headers);
+    assert!(pgn.contains("[White \"siegfried\"]\n"));
+    assert!(pgn.contains(&format!("[FEN \"{}\"]", fen)));
+    assert!(pgn.ends_with("\n\n3... Nf6 4. Bc4 Bc5 *"));
+
+    //finished games end in their result
+    let mut game = Game::new();
+    game.resign(Side::BLACK).unwrap();
+    assert_eq!(game.get_pgn(), "1-0");
+}
+
+#[test]
+pub fn magic_index_methods_test(){
+    let pext_rooks = build_rook_magics(Magic::pext_index);
+    let mul_rooks = build_rook_magics(Magic::mul_index);
+    let pext_bishops = build_bishop_magics(Magic::pext_index);
+    let mul_bishops = build_bishop_magics(Magic::mul_index);
+
+    let mut rng = Pcg64::seed_from_u64(1528);
+    for square in 0..64u8{
+        let s = square as usize;
+        for sample in 0..256{
+            //sparse and dense boards, plus the empty one
+            let occupancy: Bitboard = match sample{
+                0 => 0,
+                _ if sample % 2 == 0 => rng.gen::<u64>() & rng.gen::<u64>(),
+                _ => rng.gen::<u64>(),
+            };
+
+            let rook = mask_rook_attacks(square, occupancy);
+            assert_eq!(pext_rooks.attacks[pext_rooks.magics[s].offset + pext_rooks.magics[s].pext_index(occupancy)], rook);
+            assert_eq!(mul_rooks.attacks[mul_rooks.magics[s].offset + mul_rooks.magics[s].mul_index(occupancy)], rook);
+
+            let bishop = mask_bishop_attacks(square, occupancy);
+            assert_eq!(pext_bishops.attacks[pext_bishops.magics[s].offset + pext_bishops.magics[s].pext_index(occupancy)], bishop);
+            assert_eq!(mul_bishops.attacks[mul_bishops.magics[s].offset + mul_bishops.magics[s].mul_index(occupancy)], bishop);
+        }
+    }
+}
+
+#[test]
+pub fn shared_magic_table_test(){
+    //every square, every subset of its blockers, against the loop based attack masks
+    let rook_blockers = get_rook_blockers();
+    let bishop_blockers = get_bishop_blockers();
+    for square in 0..64u8{
+        for (blockers, attacks, reference) in [
+            (rook_blockers[square as usize], get_rook_attacks as fn(Square, Bitboard) -> Bitboard, mask_rook_attacks as fn(Square, Bitboard) -> Bitboard),
+            (bishop_blockers[square as usize], get_bishop_attacks, mask_bishop_attacks),
+        ]{
+            let mut occupancy: Bitboard = 0;
+            loop{
+                assert_eq!(attacks(square, occupancy), reference(square, occupancy));
+                occupancy = (occupancy | !blockers).wrapping_add(1) & blockers;
+                if occupancy == 0{
+                    break;
+                }
+            }
+        }
+    }
+
+    //one entry per blocker subset instead of 4096 per square
+    assert_eq!(build_rook_magics(Magic::pext_index).attacks.len(), 102400);
+    assert_eq!(build_bishop_magics(Magic::pext_index).attacks.len(), 5248);
+}
+
+#[test]
+pub fn ray_between_table_test(){
+    for from in 0..64u8{
+        for to in 0..64u8{
+            assert_eq!(get_ray_between_squares(from, to), compute_ray_between_squares(from, to));
+
+            let line = get_line_through_squares(from, to);
+            assert_eq!(line, get_line_through_squares(to, from));
+            let between = get_ray_between_squares(from, to);
+            if line != 0{
+                //both ends and everything between them sit on the line
+                assert_eq!(line & (between | from.to_bitboard() | to.to_bitboard()), between | from.to_bitboard() | to.to_bitboard());
+            }
+        }
+    }
+
+    assert_eq!(get_ray_between_squares(Square::A1, Square::H8), 0x0040201008040200);
+    assert_eq!(get_line_through_squares(Square::B2, Square::C3), 0x8040201008040201);
+    assert_eq!(get_line_through_squares(Square::E1, Square::E4), FILE_EBB);
+    assert_eq!(get_line_through_squares(Square::A1, Square::B3), 0);
+    assert_eq!(get_line_through_squares(Square::D4, Square::D4), 0);
+}
+
+#[test]
+#[allow(deprecated)]
+pub fn bit_iter_test(){
+    let boards: [Bitboard; 6] = [0, 1, 1 << 63, FILE_ABB, DARK_SQUARES, Bitboard::FULL];
+    for board in boards{
+        assert_eq!(board.squares().collect::<Vec<_>>(), board.get_squares());
+        assert_eq!(board.squares().len(), board.count_ones() as usize);
+    }
+
+    let position = Position::new_game();
+    for side in [Side::WHITE, Side::BLACK]{
+        let occupancy = position.pieces[side].occupancy();
+        assert_eq!(occupancy.squares().collect::<Vec<_>>(), occupancy.get_squares());
+    }
+}
+
+#[test]
+pub fn is_square_attacked_test(){
+    let fens = [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "1k1r3r/pppqb1pp/1nn1p3/3bPp2/1P1PN3/P2BBN2/5PPP/2RQ1RK1 w - f6 0 15",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        "k7/1nK5/1P6/8/4B3/8/8/8 b - - 0 1",
+    ];
+    let mut rng = Pcg64::seed_from_u64(1532);
+    for fen in fens{
+        let position = Position::from_fen(fen);
+        let occupancy = position.pieces[Side::WHITE].occupancy() | position.pieces[Side::BLACK].occupancy();
+        for by in [Side::WHITE, Side::BLACK]{
+            let attacked = position.get_side_attacks(by, occupancy).all();
+            for _ in 0..32{
+                let square: Square = rng.gen_range(0..64);
+                assert_eq!(position.is_square_attacked(square, by, occupancy), attacked & square.to_bitboard() != 0, "{} {}", fen, square);
+            }
+        }
+    }
+}
+
+#[test]
+pub fn attackers_to_test(){
+    //d5 is hit by pawns, knights and sliders on both sides and the white king, the e6 pawn blocks the black queen on f7
+    let position = Position::from_fen("3r3k/5q2/1n2p3/2Kp4/4PN2/1B6/3R4/6Q1 w - - 0 1");
+    let occupancy = position.pieces[Side::WHITE].occupancy() | position.pieces[Side::BLACK].occupancy();
+
+    for by in [Side::WHITE, Side::BLACK]{
+        //brute force: every piece of `by`, looking at its own attacks
+        let mut expected: Bitboard = 0;
+        for piece in 0..6{
+            for from in position.pieces[by][piece].squares(){
+                let attacks = match piece{
+                    PAWN => get_pawn_attacks(by, from),
+                    KNIGHT => get_knight_attacks(from),
+                    BISHOP => get_bishop_attacks(from, occupancy),
+                    ROOK => get_rook_attacks(from, occupancy),
+                    QUEEN => get_bishop_attacks(from, occupancy) | get_rook_attacks(from, occupancy),
+                    _ => get_king_attacks(from),
+                };
+                if attacks & Square::D5.to_bitboard() != 0{
+                    expected |= from.to_bitboard();
+                }
+            }
+        }
+        assert_eq!(position.attackers_to(Square::D5, by, occupancy), expected);
+    }
+
+    assert_eq!(position.attackers_to(Square::D5, Side::WHITE, occupancy), Square::E4.to_bitboard() | Square::F4.to_bitboard() | Square::B3.to_bitboard() | Square::D2.to_bitboard() | Square::C5.to_bitboard());
+    assert_eq!(position.attackers_to(Square::D5, Side::BLACK, occupancy), Square::E6.to_bitboard() | Square::B6.to_bitboard() | Square::D8.to_bitboard());
+}
+
+#[test]
+pub fn mvv_lva_ordering_test(){
+    //the d4 queen can take the c5 pawn and the e4 pawn can take the d5 queen
+    let position = Position::from_fen("4k3/8/8/2pq4/3QP3/8/8/4K3 w - - 0 1");
+    let mut moves = position.legal_moves();
+    order_moves(&position, &mut moves);
+
+    let pxq = Move::from_uci("e4d5", &position).unwrap();
+    let qxp = Move::from_uci("d4c5", &position).unwrap();
+    let quiet = Move::from_uci("e1d1", &position).unwrap();
+    assert!(pxq.mvv_lva_score(&position) > qxp.mvv_lva_score(&position));
+    assert!(qxp.mvv_lva_score(&position) > quiet.mvv_lva_score(&position));
+    assert_eq!(quiet.mvv_lva_score(&position), 0);
+
+    assert!(moves[0] == pxq);
+    let qxp_index = moves.iter().position(|m| *m == qxp).unwrap();
+    let first_quiet = moves.iter().position(|m| m.capture.is_none() && m.promotion.is_none()).unwrap();
+    assert!(qxp_index < first_quiet);
+}
+
+#[test]
+pub fn search_timed_test(){
+    let position = Position::new_game();
+    let start = std::time::Instant::now();
+    let (m, _, depth) = search_timed(&position, 50).unwrap();
+
+    //a node check interval and one unfinished iteration of slack on top of the budget
+    assert!(start.elapsed().as_millis() < 1000);
+    assert!(depth >= 1);
+    assert!(position.legal_moves().iter().any(|legal| *legal == m));
+
+    //a forced mate is found well within the budget
+    let (m, score, _) = search_timed(&Position::from_fen("k7/8/8/8/8/8/7R/4K1R1 w - - 0 1"), 2000).unwrap();
+    assert!(score >= mate_distance_score(1000000.0, 3));
+    assert!(Position::from_fen("k7/8/8/8/8/8/7R/4K1R1 w - - 0 1").legal_moves().iter().any(|legal| *legal == m));
+
+    //mated or stalemated at the root there's no move to give back
+    let fools_mate = Position::from_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3");
+    assert!(search_timed(&fools_mate, 50).is_none());
+    assert!(Searcher::new().iterative_deepening(&fools_mate, 3).is_none());
+    assert!(search_timed(&Position::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1"), 50).is_none());
+}
+
+#[test]
+pub fn timed_search_avoids_repetition_test(){
+    //a queen up, the move the search would pick leads to a position the game has already seen twice
+    let position = Position::from_fen("6k1/8/8/8/3K4/8/8/3Q4 w - - 0 1");
+    let pick = Searcher::new().search(&position, 3, f32::NEG_INFINITY, f32::INFINITY).1.unwrap();
+    let repeated = position.make_move(pick).unwrap().hash();
+    let mut history = ZobristMoveStack::new();
+    history.add(repeated);
+    history.add(repeated);
+
+    //a third time would be a draw, so a queen up it goes elsewhere
+    let mut searcher = Searcher::new();
+    searcher.history = history.clone();
+    let (score, m) = searcher.search(&position, 3, f32::NEG_INFINITY, f32::INFINITY);
+    assert!(m.unwrap() != pick);
+    assert!(score > 0.0);
+
+    let (m, score, _) = search_timed_with_history(&position, &history, 100).unwrap();
+    assert!(m != pick);
+    assert!(score > 0.0);
+}
+
+#[test]
+pub fn search_stop_flag_test(){
+    let position = Position::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1");
+    let stop = Arc::new(AtomicBool::new(false));
+
+    let mut searcher = Searcher::new();
+    searcher.stop = Some(stop.clone());
+    let handle = std::thread::spawn(move || searcher.iterative_deepening(&position, 64));
+
+    std::thread::sleep(std::time::Duration::from_millis(100));
+    let stopped_at = std::time::Instant::now();
+    stop.store(true, Ordering::Relaxed);
+    let (m, _, _) = handle.join().unwrap().unwrap();
+
+    assert!(stopped_at.elapsed().as_millis() < 1000);
+    assert!(position.legal_moves().iter().any(|legal| *legal == m));
+
+    //set before the search starts, depth 1 still runs to give a move
+    let mut searcher = Searcher::new();
+    searcher.stop = Some(Arc::new(AtomicBool::new(true)));
+    let (m, _, stats) = searcher.iterative_deepening(&position, 64).unwrap();
+    assert_eq!(stats.depth, 1);
+    assert!(position.legal_moves().iter().any(|legal| *legal == m));
+}
+
+#[test]
+pub fn search_stats_test(){
+    let position = Position::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1");
+    let root_moves = position.legal_moves().len() as u64;
+
+    for depth in 2..=3{
+        let (_, _, stats) = Searcher::new().iterative_deepening(&position, depth).unwrap();
+        assert_eq!(stats.depth, depth);
+        assert!(stats.nodes > root_moves);
+        assert!(stats.elapsed.as_nanos() == 0 || stats.nodes_per_second() > 0);
+    }
+
+    let stats = SearchStats{ nodes: 5000, depth: 1, elapsed: std::time::Duration::from_millis(500) };
+    assert_eq!(stats.nodes_per_second(), 10000);
+}
+
+#[test]
+pub fn uci_test(){
+    let output = run_uci_with(std::io::Cursor::new("uci\nisready\nposition startpos\ngo depth 1\n"), Vec::new());
+    let output = String::from_utf8(output).unwrap();
+    let lines: Vec<&str> = output.lines().collect();
+
+    assert!(lines.contains(&"uciok"));
+    assert!(lines.contains(&"readyok"));
+    let bestmove = lines.iter().find(|line| line.starts_with("bestmove ")).unwrap();
+    let m = Move::from_uci(&bestmove["bestmove ".len()..], &Position::new_game());
+    assert!(m.is_some());
+    assert!(lines.iter().any(|line| line.starts_with("info depth 1 ")));
+
+    //moves are played on top of the fen
+    let position = parse_position(&["fen", "k7/8/8/8/8/8/7R/4K1R1", "w", "-", "-", "0", "1", "moves", "g1g7", "a8b8"]).unwrap();
+    assert!(position.to_fen() == "1k6/6R1/8/8/8/8/7R/4K3 w - - 2 2");
+    assert!(parse_position(&["startpos", "moves", "e2e5"]).is_none());
+
+    assert_eq!(parse_go(&["depth", "3"]), GoLimit::Depth(3));
+    assert_eq!(parse_go(&["movetime", "250"]), GoLimit::MoveTime(250));
+    assert_eq!(parse_go(&["infinite"]), GoLimit::Infinite);
+
+    assert_eq!(format_score(-35.4), "cp -35");
+    assert_eq!(format_score(mate_distance_score(1000000.0, 3)), "mate 2");
+    assert_eq!(format_score(-mate_distance_score(1000000.0, 2)), "mate -1");
+}
+
+#[test]
+pub fn uci_info_test(){
+    let output = run_uci_with(std::io::Cursor::new("position startpos moves e2e4 e7e5\ngo depth 3\n"), Vec::new());
+    let output = String::from_utf8(output).unwrap();
+    let infos: Vec<&str> = output.lines().filter(|line| line.starts_with("info depth")).collect();
+    assert_eq!(infos.len(), 3);
+
+    for (i, info) in infos.iter().enumerate(){
+        let fields: Vec<&str> = info.split_whitespace().collect();
+        let value = |name: &str| fields[fields.iter().position(|f| *f == name).unwrap() + 1];
+
+        assert_eq!(value("depth").parse::<usize>().unwrap(), i + 1);
+        assert_eq!(value("score"), "cp");
+        assert!(fields[fields.iter().position(|f| *f == "score").unwrap() + 2].parse::<i32>().is_ok());
+        assert!(value("nodes").parse::<u64>().is_ok());
+        assert!(value("nps").parse::<u64>().is_ok());
+
+        //the pv is a playable line of at least the searched depth
+        let pv = &fields[fields.iter().position(|f| *f == "pv").unwrap() + 1..];
+        assert!(pv.len() > i);
+        let mut position = parse_position(&["startpos", "moves", "e2e4", "e7e5"]).unwrap();
+        for m in pv{
+            position = position.make_move(Move::from_uci(m, &position).unwrap()).unwrap();
+        }
+    }
+
+    //mates are reported in moves
+    let output = run_uci_with(std::io::Cursor::new("position fen k7/8/8/8/8/8/7R/4K1R1 w - - 0 1\ngo depth 3\n"), Vec::new());
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.lines().any(|line| line.starts_with("info depth 3 score mate 2 ")));
+}
+
+#[test]
+pub fn extract_pv_test(){
+    let position = Position::from_fen("k7/8/8/8/8/8/7R/4K1R1 w - - 0 1");
+    let mut searcher = Searcher::new();
+    let (score, best_move) = searcher.search(&position, 3, f32::NEG_INFINITY, f32::INFINITY);
+    assert_eq!(score, mate_distance_score(1000000.0, 3));
+
+    //mate in two is three plies, the last one mates
+    let pv = extract_pv(&position, &searcher.tt, 10);
+    assert_eq!(pv.len(), 3);
+    assert!(pv[0] == best_move.unwrap());
+
+    let mut end = position;
+    for m in pv.iter(){
+        end = end.make_move(*m).unwrap();
+    }
+    assert!(end.is_in_check());
+    assert!(!end.has_legal_move());
+
+    assert_eq!(extract_pv(&position, &searcher.tt, 2).len(), 2);
+    assert!(extract_pv(&position, &TranspositionTable::new(), 10).is_empty());
+}
+
+//the placement with a white king dropped on an empty square, None if the square is taken
+fn place_white_king(placement: &str, square: Square) -> Option<String>{
+    let mut board: Vec<Vec<char>> = placement.split('/').map(|rank| {
+        rank.chars().flat_map(|c| match c.to_digit(10){
+            Some(empty) => vec!['.'; empty as usize],
+            None => vec![c],
+        }).collect()
+    }).collect();
+
+    let cell = &mut board[7 - square as usize / 8][square as usize % 8];
+    if *cell != '.'{
+        return None;
+    }
+    *cell = 'K';
+
+    let ranks: Vec<String> = board.iter().map(|rank| {
+        let mut packed = String::new();
+        let mut empty = 0;
+        for c in rank{
+            if *c == '.'{
+                empty += 1;
+                continue;
+            }
+            if empty > 0{
+                packed.push_str(&empty.to_string());
+                empty = 0;
+            }
+            packed.push(*c);
+        }
+        if empty > 0{
+            packed.push_str(&empty.to_string());
+        }
+        packed
+    }).collect();
+    return Some(ranks.join("/"));
+}
+
+//square the piece-square tables like best for the white king
+fn best_king_square(placement: &str, squares: &[Square]) -> Square{
+    let mut best = (f32::NEG_INFINITY, 64);
+    for square in squares{
+        if let Some(fen) = place_white_king(placement, *square){
+            let score = Position::from_fen(&format!("{} w - - 0 1", fen)).get_pst_score();
+            if score > best.0{
+                best = (score, *square);
+            }
+        }
+    }
+    return best.1;
+}
+
+#[test]
+pub fn tapered_eval_test(){
+    assert_eq!(Position::new_game().game_phase(), PHASE_MAX);
+    assert_eq!(Position::from_fen("4k3/pppppppp/8/8/8/8/PPPPPPPP/4K3 w - - 0 1").game_phase(), 0);
+    assert_eq!(Position::from_fen("r3k3/8/8/8/8/8/8/1N2K3 w - - 0 1").game_phase(), 3);
+
+    //mirrored positions score the same for either side
+    assert_eq!(Position::new_game().get_pst_score(), 0.0);
+
+    //with the pieces still on, the king wants the castled corner
+    let all_squares: Vec<Square> = (0..64).collect();
+    let opening = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQ3R";
+    assert_eq!(best_king_square(opening, &all_squares), Square::G1);
+
+    //with only kings left it wants the centre
+    let bare = "k7/8/8/8/8/8/8/8";
+    let near_black_king = [Square::A7, Square::B7, Square::B8];
+    let squares: Vec<Square> = all_squares.iter().copied().filter(|s| !near_black_king.contains(s)).collect();
+    assert!([Square::D4, Square::E4, Square::D5, Square::E5].contains(&best_king_square(bare, &squares)));
+
+    //halfway between the two the score is the average of both tables
+    assert_eq!(taper(30, -30, PHASE_MAX / 2), 0.0);
+}
+
+#[test]
+pub fn pawn_structure_test(){
+    let structure = |fen: &str| Position::from_fen(fen).get_pawn_structure_score(&EvalParams::default());
+
+    //black's c7-f7 chain stays the same throughout
+    let clean = Position::from_fen("4k3/2pppp2/8/8/8/8/3PP3/4K3 w - - 0 1");
+    assert_eq!(clean.doubled_pawns(Side::WHITE) | clean.isolated_pawns(Side::WHITE) | clean.passed_pawns(Side::WHITE), 0);
+    assert_eq!(clean.get_pawn_structure_score(&EvalParams::default()), 0.0);
+
+    let doubled = Position::from_fen("4k3/2pppp2/8/8/8/3P4/3PP3/4K3 w - - 0 1");
+    assert_eq!(doubled.doubled_pawns(Side::WHITE), Square::D3.to_bitboard());
+    assert!(doubled.get_pawn_structure_score(&EvalParams::default()) < clean.get_pawn_structure_score(&EvalParams::default()));
+
+    let isolated = Position::from_fen("4k3/2pppp2/8/8/8/8/2P1P3/4K3 w - - 0 1");
+    assert_eq!(isolated.isolated_pawns(Side::WHITE), Square::C2.to_bitboard() | Square::E2.to_bitboard());
+    assert!(isolated.get_pawn_structure_score(&EvalParams::default()) < clean.get_pawn_structure_score(&EvalParams::default()));
+
+    //the h pawn is isolated either way, only the missing g7 pawn makes it a passer
+    let passed = Position::from_fen("4k3/2pppp2/8/8/7P/8/3PP3/4K3 w - - 0 1");
+    let blocked = Position::from_fen("4k3/2ppp1p1/8/8/7P/8/3PP3/4K3 w - - 0 1");
+    assert_eq!(passed.passed_pawns(Side::WHITE), Square::H4.to_bitboard());
+    assert_eq!(blocked.passed_pawns(Side::WHITE), 0);
+    assert!(passed.get_pawn_structure_score(&EvalParams::default()) > blocked.get_pawn_structure_score(&EvalParams::default()));
+
+    //further up the board is worth more, for either side
+    assert!(structure("4k3/2pppp2/7P/8/8/8/3PP3/4K3 w - - 0 1") > structure("4k3/2pppp2/8/8/7P/8/3PP3/4K3 w - - 0 1"));
+    assert!(structure("4k3/3pp3/8/8/8/p7/2PPPP2/4K3 w - - 0 1") < structure("4k3/3pp3/p7/8/8/8/2PPPP2/4K3 w - - 0 1"));
+    assert_eq!(Position::from_fen("4k3/3pp3/8/8/8/p7/2PPPP2/4K3 w - - 0 1").passed_pawns(Side::BLACK), Square::A3.to_bitboard());
+}
+
+#[test]
+pub fn eval_params_test(){
+    //a pawn up, the same structure terms and mobility whatever the piece values are
+    let position = Position::from_fen("4k3/7p/8/8/8/8/P6P/4K3 w - - 0 1");
+    assert!(position.evaluate().score == position.evaluate_with(&EvalParams::default()).score);
+
+    let mut doubled = EvalParams::default();
+    doubled.piece_values[PAWN] *= 2.0;
+
+    //only the extra pawn's value moves the score
+    let delta = doubled.piece_values[PAWN] - EvalParams::default().piece_values[PAWN];
+    assert_eq!(position.evaluate_with(&doubled).score.unwrap() - position.evaluate().score.unwrap(), delta);
+
+    //equal pawns cancel out
+    let level = Position::from_fen("4k3/p6p/8/8/8/8/P6P/4K3 w - - 0 1");
+    assert_eq!(level.evaluate_with(&doubled).score, level.evaluate().score);
+}
+
+#[test]
+pub fn static_eval_test(){
+    let file = std::fs::File::open("./src/../testfens.json").unwrap();
+    let reader = std::io::BufReader::new(file);
+    let json: Value = serde_json::from_reader(reader).unwrap();
+
+    let mut checked = 0;
+    for fen in json.as_object().unwrap().keys(){
+        let position = Position::from_fen(fen);
+        let eval = position.evaluate();
+        if eval.game_state == GameState::CHECKMATE || eval.game_state == GameState::DRAW{
+            continue;
+        }
+        assert_eq!(eval.score, Some(position.static_eval()), "{}", fen);
+        checked += 1;
+    }
+    assert!(checked > 0);
+
+    let mut params = EvalParams::default();
+    params.mobility_multiplier = 0.0;
+    let position = Position::from_fen("1k1r3r/pppqb1pp/1nn1p3/3bPp2/1P1PN3/P2BBN2/5PPP/2RQ1RK1 w - f6 0 15");
+    assert_eq!(position.evaluate_with(&params).score, Some(position.static_eval_with(&params)));
+}
+
+#[test]
+pub fn king_safety_test(){
+    let params = EvalParams::default();
+    //the same castled kings, white's f, g and h pawns either at home or pushed two squares
+    let shielded = Position::from_fen("r2q1rk1/ppp2ppp/8/8/8/8/PPP2PPP/R2Q1RK1 w - - 0 1");
+    let pushed = Position::from_fen("r2q1rk1/ppp2ppp/8/8/5PPP/8/PPP5/R2Q1RK1 w - - 0 1");
+
+    assert_eq!(shielded.missing_shield_pawns(Side::WHITE), 0);
+    assert_eq!(pushed.missing_shield_pawns(Side::WHITE), 3);
+    assert_eq!(pushed.missing_shield_pawns(Side::BLACK), 0);
+
+    let black_attacks = |position: &Position| {
+        let occupancy = position.pieces[Side::WHITE].occupancy() | position.pieces[Side::BLACK].occupancy();
+        position.get_side_attacks(Side::BLACK, occupancy).all()
+    };
+    assert!(shielded.king_safety_penalty(Side::WHITE, black_attacks(&shielded), &params) < pushed.king_safety_penalty(Side::WHITE, black_attacks(&pushed), &params));
+    assert!(shielded.static_eval() > pushed.static_eval());
+
+    //a queen and rook lined up on the king's squares count as two attackers
+    let attacked = Position::from_fen("6k1/8/8/8/8/6q1/5r2/6K1 w - - 0 1");
+    assert_eq!(attacked.king_attackers(Side::WHITE, black_attacks(&attacked)), 2);
+
+    //the same pushed pawns cost nothing with only kings and pawns left
+    let endgame = Position::from_fen("6k1/5ppp/8/8/5PPP/8/8/6K1 w - - 0 1");
+    assert_eq!(endgame.king_safety_penalty(Side::WHITE, black_attacks(&endgame), &params), 0.0);
+}
+
+#[test]
+pub fn rook_file_test(){
+    //the same rook on the open d file or behind its own pawn on e, the pawns stay put
+    let open = Position::from_fen("4k3/pp3ppp/8/8/8/8/PP2PPPP/3RK3 w - - 0 1");
+    let closed = Position::from_fen("4k3/pp3ppp/8/8/8/8/PP2PPPP/4RK2 w - - 0 1");
+    assert_eq!(open.rooks_on_open_files(Side::WHITE), Square::D1.to_bitboard());
+    assert_eq!(closed.rooks_on_open_files(Side::WHITE) | closed.rooks_on_half_open_files(Side::WHITE), 0);
+
+    let params = EvalParams::default();
+    assert_eq!(open.get_rook_score(&params) - closed.get_rook_score(&params), params.rook_open_file_bonus);
+
+    //only black's pawn on the c file
+    let half_open = Position::from_fen("4k3/2p5/8/8/8/8/8/2R1K3 w - - 0 1");
+    assert_eq!(half_open.rooks_on_half_open_files(Side::WHITE), Square::C1.to_bitboard());
+    assert_eq!(half_open.rooks_on_open_files(Side::WHITE), 0);
+
+    //the queen between them disconnects the rooks
+    assert!(Position::from_fen("4k3/8/8/8/8/8/8/R4RK1 w - - 0 1").connected_rooks(Side::WHITE));
+    assert!(!Position::from_fen("4k3/8/8/8/8/8/8/R2Q1RK1 w - - 0 1").connected_rooks(Side::WHITE));
+    assert!(Position::from_fen("r3k3/8/8/8/r7/8/8/4K3 w - - 0 1").connected_rooks(Side::BLACK));
+}
+
+#[test]
+pub fn bishop_pair_test(){
+    let params = EvalParams::default();
+    let mut no_pair = EvalParams::default();
+    no_pair.bishop_pair_bonus = 0.0;
+
+    //black gave a bishop for white's knight, material is level
+    let position = Position::from_fen("r1bqk2r/ppp2ppp/2n5/3pp3/3PP3/8/PPP2PPP/R1BQKB1R w KQkq - 0 1");
+    assert!(position.has_bishop_pair(Side::WHITE));
+    assert!(!position.has_bishop_pair(Side::BLACK));
+    assert_eq!(position.static_eval_with(&params) - position.static_eval_with(&no_pair), params.bishop_pair_bonus);
+
+    //and the other way round
+    let position = Position::from_fen("r1bqkb1r/ppp2ppp/8/3pp3/3PP3/2N5/PPP2PPP/R1BQK2R b KQkq - 0 1");
+    assert_eq!(position.static_eval_with(&params) - position.static_eval_with(&no_pair), -params.bishop_pair_bonus);
+
+    //both pairs cancel out
+    let position = Position::new_game();
+    assert_eq!(position.static_eval_with(&params), position.static_eval_with(&no_pair));
+}
+
+#[test]
+pub fn position_eq_hash_test(){
+    use std::collections::HashMap;
+    use std::hash::{Hash, Hasher};
+    use std::collections::hash_map::DefaultHasher;
+
+    let hash_of = |position: &Position| {
+        let mut hasher = DefaultHasher::new();
+        Hash::hash(position, &mut hasher);
+        hasher.finish()
+    };
+
+    let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+    let a = Position::from_fen(fen);
+    let b = Position::from_fen(fen);
+    assert!(a == b);
+    assert_eq!(hash_of(&a), hash_of(&b));
+
+    //the same placement reached later in the game is the same position
+    let later = Position::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 12 40");
+    assert!(a == later);
+    assert_eq!(hash_of(&a), hash_of(&later));
+
+    //but not with other rights or the other side to move
+    assert!(a != Position::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w Kkq - 0 1"));
+    assert!(a != Position::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b KQkq - 0 1"));
+
+    //a knight shuffle transposes back to the start
+    let mut seen: HashMap<Position, usize> = HashMap::new();
+    let mut position = Position::new_game();
+    seen.insert(position, 0);
+    for (i, uci) in ["g1f3", "g8f6", "f3g1", "f6g8"].iter().enumerate(){
+        position = position.make_move(Move::from_uci(uci, &position).unwrap()).unwrap();
+        seen.entry(position).or_insert(i + 1);
+    }
+    assert_eq!(seen.len(), 4);
+    assert_eq!(seen[&Position::new_game()], 0);
+}
+
+#[test]
+pub fn default_test(){
+    assert_eq!(Position::default().to_fen(), "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+    assert!(*Game::default().get_position() == Position::new_game());
+}
+
+#[test]
+pub fn parse_square_and_move_test(){
+    assert_eq!(Square::try_from_string("e4"), Ok(Square::E4));
+    assert_eq!(Square::try_from_string("h8"), Ok(Square::H8));
+    assert_eq!(Square::try_from_string("z9"), Err(SquareParseError::BadFile));
+    assert_eq!(Square::try_from_string("a9"), Err(SquareParseError::BadRank));
+    assert_eq!(Square::try_from_string("e"), Err(SquareParseError::WrongLength));
+
+    let m: Move = "e7e8q".parse().unwrap();
+    let translation = m.translation.unwrap();
+    assert_eq!((translation.from, translation.to), (Square::E7, Square::E8));
+    assert_eq!(m.promotion, Some(QUEEN));
+    assert!(m.capture.is_none() && m.castling.is_none() && m.en_passant.is_none());
+
+    //the parsed move names the same transition as the generated one
+    let position = Position::new_game();
+    let parsed: Move = "g1f3".parse().unwrap();
+    assert!(parsed.same_transition(&Move::from_uci("g1f3", &position).unwrap()));
+
+    assert_eq!("e2".parse::<Move>().err(), Some(MoveParseError::WrongLength));
+    assert_eq!("z2e4".parse::<Move>().err(), Some(MoveParseError::BadSquare(SquareParseError::BadFile)));
+    assert_eq!("e7e8k".parse::<Move>().err(), Some(MoveParseError::BadPromotion));
+}
+
+#[test]
+pub fn position_to_ascii_test(){
+    let position = Position::new_game();
+    let ascii = position.to_ascii();
+    let lines: Vec<&str> = ascii.lines().collect();
+
+    assert!(lines[0].starts_with("8   r  n  b  q  k  b  n  r"));
+    assert!(lines[1].starts_with("7   p  p  p  p  p  p  p  p"));
+    assert!(lines[3].starts_with("5   .  .  .  .  .  .  .  ."));
+    assert!(lines[6].starts_with("2   P  P  P  P  P  P  P  P"));
+    assert!(lines[7].starts_with("1   R  N  B  Q  K  B  N  R"));
+    assert_eq!(lines.last().unwrap().trim(), "A  B  C  D  E  F  G  H");
+    assert_eq!(format!("{}", position), ascii);
+}
+
+#[test]
+pub fn position_to_ascii_oriented_test(){
+    let position = Position::from_fen("4k3/8/8/8/8/8/8/R3K3 w Q - 0 1");
+    let white = position.to_ascii_oriented(Side::WHITE);
+    let black = position.to_ascii_oriented(Side::BLACK);
+
+    assert_eq!(white, position.to_ascii());
+    assert_ne!(white, black);
+
+    //a1 is the bottom left corner for white and the top right corner for black
+    let white_lines: Vec<&str> = white.lines().collect();
+    let black_lines: Vec<&str> = black.lines().collect();
+    assert!(white_lines[7].starts_with("1   R  .  .  .  K"));
+    assert!(black_lines[0].starts_with("1   .  .  .  K  .  .  .  R"));
+    assert!(black_lines[7].starts_with("8   .  .  .  k"));
+    assert_eq!(black_lines.last().unwrap().trim(), "H  G  F  E  D  C  B  A");
+}
+
+#[test]
+pub fn write_display_test(){
+    let position = Position::new_game();
+    let mut out: Vec<u8> = Vec::new();
+    write_position(&position, &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), format!("{}\n", position.to_ascii()));
+
+    let mut out: Vec<u8> = Vec::new();
+    write_bitboard(Square::A1.to_bitboard() | Square::H8.to_bitboard(), &mut out).unwrap();
+    let text = String::from_utf8(out).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines[0], "8    .  .  .  .  .  .  .  1 ");
+    assert_eq!(lines[7], "1    1  .  .  .  .  .  .  . ");
+    assert_eq!(lines.last().unwrap().trim(), "A  B  C  D  E  F  G  H");
+}
+
+#[test]
+pub fn move_predicates_test(){
+    let quiet = Move::from_uci("e2e4", &Position::new_game()).unwrap();
+    let capture = Move::from_uci("e4d5", &Position::from_fen("4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1")).unwrap();
+    let promotion = Move::from_uci("a7a8q", &Position::from_fen("4k3/P7/8/8/8/8/8/4K3 w - - 0 1")).unwrap();
+    let castle = Move::from_uci("e1g1", &Position::from_fen("4k3/8/8/8/8/8/8/4K2R w K - 0 1")).unwrap();
+    let en_passant = Move::from_uci("e5d6", &Position::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1")).unwrap();
+
+    let classify = |m: &Move| (m.is_capture(), m.is_promotion(), m.is_castle(), m.is_en_passant(), m.is_quiet());
+    assert_eq!(classify(&quiet), (false, false, false, false, true));
+    assert_eq!(classify(&capture), (true, false, false, false, false));
+    assert_eq!(classify(&promotion), (false, true, false, false, false));
+    assert_eq!(classify(&castle), (false, false, true, false, true));
+    assert_eq!(classify(&en_passant), (true, false, false, true, false));
+}
+
+#[test]
+pub fn null_move_test(){
+    let null = Move::null();
+    assert!(null.is_null());
+    assert!(!Move::from_uci("e2e4", &Position::new_game()).unwrap().is_null());
+    assert_eq!(format!("{}", null), "0000");
+
+    let position = Position::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1");
+    let passed = position.make_null_move();
+    assert!(passed.side_to_move == Side::BLACK);
+    assert_eq!(passed.zobrist_hash, ZOBRIST.hash_position(&passed));
+    assert!(passed.make_null_move() == position);
+    assert_eq!(passed.make_null_move().zobrist_hash, position.zobrist_hash);
+
+    //the en passant square doesn't survive the pass
+    let position = Position::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1");
+    let passed = position.make_null_move();
+    assert_eq!(passed.en_passant_square, None);
+    assert_eq!(passed.zobrist_hash, ZOBRIST.hash_position(&passed));
+    let back = passed.make_null_move();
+    assert_eq!(back.to_fen(), "4k3/8/8/3pP3/8/8/8/4K3 w - - 0 1");
+    assert_eq!(back.zobrist_hash, ZOBRIST.hash_position(&back));
+}
+
+#[test]
+pub fn null_move_pruning_node_count_test(){
+    let position = Position::from_fen("r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4");
+
+    let mut pruned = Searcher::new();
+    let (_, pruned_move) = pruned.search(&position, 4, f32::NEG_INFINITY, f32::INFINITY);
+
+    let mut full = Searcher::new();
+    full.null_move_pruning = false;
+    let (_, full_move) = full.search(&position, 4, f32::NEG_INFINITY, f32::INFINITY);
+
+    assert!(pruned_move.unwrap().get_tstring() == full_move.unwrap().get_tstring());
+    assert!(pruned.nodes < full.nodes, "{} {}", pruned.nodes, full.nodes);
+}
+
+#[test]
+pub fn check_extension_mate_test(){
+    //philidor's legacy, 1.Nh6+ Kh8 2.Qg8+ Rxg8 3.Nf7# is all checks and ends on a quiet move
+    let position = Position::from_fen("5rk1/5Npp/8/3Q4/8/8/8/K7 w - - 0 1");
+
+    let mut plain = Searcher::new();
+    plain.check_extensions = false;
+    let (plain_score, _) = plain.search(&position, 4, f32::NEG_INFINITY, f32::INFINITY);
+    assert!(plain_score < MATE_BOUND, "{}", plain_score);
+
+    let mut extended = Searcher::new();
+    let (score, best_move) = extended.search(&position, 4, f32::NEG_INFINITY, f32::INFINITY);
+    assert!(score >= MATE_BOUND, "{}", score);
+    assert_eq!(best_move.unwrap().get_tstring(), "f7h6");
+}
+
+#[test]
+pub fn in_check_test(){
+    for fen in [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "rnbqkbnr/ppp2ppp/3p4/1B2p3/4P3/8/PPPP1PPP/RNBQK1NR b KQkq - 1 3",
+        "4k3/8/8/8/8/8/4r3/4K3 w - - 0 1",
+        "4k3/p7/8/8/8/5n2/8/4K3 w - - 0 1",
+        "4k3/8/8/8/7B/8/P7/4K3 b - - 0 1",
+        "4k3/8/8/8/8/8/3p4/4K3 w - - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    ]{
+        let position = Position::from_fen(fen);
+        let us = position.side_to_move;
+        assert_eq!(position.in_check(us), position.evaluate().game_state == GameState::CHECK, "{}", fen);
+        assert_eq!(position.in_check(us), position.is_in_check(), "{}", fen);
+        //the side that just moved can't be left in check
+        assert!(!position.in_check(!us), "{}", fen);
+    }
+}
+
+//every move from `position` and the positions below it to `depth` is generated once
+fn assert_unique_moves(position: &Position, depth: u8){
+    let moves = position.legal_moves();
+    let mut names: Vec<String> = moves.iter().map(|m| m.get_tstring()).collect();
+    names.sort();
+    names.dedup();
+    assert_eq!(names.len(), moves.len(), "{}", position.to_fen());
+
+    if depth > 1{
+        for m in moves{
+            assert_unique_moves(&position.make_move(m).unwrap(), depth - 1);
+        }
+    }
+}
+
+#[test]
+pub fn evasion_moves_unique_test(){
+    for fen in [
+        //king attacked twice, by the rook and the knight
+        "4k3/8/8/8/8/5n2/8/r3K3 w - - 0 1",
+        //single sliding checker that pieces can capture or block
+        "4k3/8/8/1b6/8/2N5/3P4/R3K2R w KQ - 0 1",
+        //the checking pawn can be taken en passant
+        "8/8/8/2k5/3Pp3/8/8/4K3 b - d3 0 1",
+        //promotions that capture the checker
+        "1r2k3/P7/8/8/8/8/8/1K6 w - - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    ]{
+        assert_unique_moves(&Position::from_fen(fen), 3);
+    }
+}
+
+#[test]
+pub fn castling_move_display_test(){
+    let position = Position::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+    let king_side = Move::from_uci("e1g1", &position).unwrap();
+    let queen_side = Move::from_uci("e1c1", &position).unwrap();
+
+    assert_eq!(format!("{}", king_side), "O-O");
+    assert_eq!(format!("{}", queen_side), "O-O-O");
+    assert!(king_side == Move::castle(Side::WHITE, KING_SIDE));
+    assert!(queen_side == Move::castle(Side::WHITE, QUEEN_SIDE));
+    //long algebraic stays the king's coordinates for uci
+    assert_eq!(king_side.get_tstring(), "e1g1");
+
+    let black = position.make_null_move();
+    assert!(Move::from_uci("e8c8", &black).unwrap() == Move::castle(Side::BLACK, QUEEN_SIDE));
+    assert_eq!(format!("{}", Move::castle(Side::BLACK, KING_SIDE)), "O-O");
+}
+
+#[test]
+pub fn en_passant_move_display_test(){
+    let position = Position::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1");
+    let en_passant = Move::from_uci("e5d6", &position).unwrap();
+    assert_eq!(format!("{}", en_passant), "e5xd6");
+
+    //a hand built en passant move without the capture field still reads as a capture
+    let bare = Move{
+        translation: Some(Translation{from: Square::E5, to: Square::D6}),
+        promotion: None,
+        capture: None,
+        castling: None,
+        en_passant: Some(Square::D6),
+    };
+    assert_eq!(format!("{}", bare), "e5xd6");
+    assert_eq!(en_passant.to_san(&position), "exd6");
+}
+
+#[test]
+pub fn position_builder_test(){
+    let back_rank = [ROOK, KNIGHT, BISHOP, QUEEN, KING, BISHOP, KNIGHT, ROOK];
+    let mut builder = PositionBuilder::new().castling(Castling::new_game());
+    for file in 0..8{
+        builder = builder
+            .piece(Square::from_rank_and_file(0, file), back_rank[file], Side::WHITE)
+            .piece(Square::from_rank_and_file(1, file), PAWN, Side::WHITE)
+            .piece(Square::from_rank_and_file(6, file), PAWN, Side::BLACK)
+            .piece(Square::from_rank_and_file(7, file), back_rank[file], Side::BLACK);
+    }
+    let position = builder.build();
+    assert!(position == Position::new_game());
+    assert_eq!(position.zobrist_hash, Position::new_game().zobrist_hash);
+    assert_eq!(position.to_fen(), Position::new_game().to_fen());
+
+    let position = PositionBuilder::new()
+        .piece(Square::E1, KING, Side::WHITE)
+        .piece(Square::E8, KING, Side::BLACK)
+        .piece(Square::A7, PAWN, Side::WHITE)
+        .side_to_move(Side::BLACK)
+        .fullmove_number(40)
+        .build();
+    assert_eq!(position.to_fen(), "4k3/P7/8/8/8/8/8/4K3 b - - 0 40");
+
+    //setting and removing pieces keeps the hash in step
+    let mut position = Position::new_game();
+    position.set_piece(Square::E2, QUEEN, Side::BLACK);
+    assert!(position.piece_at(Square::E2) == Some((QUEEN, Side::BLACK)));
+    assert_eq!(position.zobrist_hash, ZOBRIST.hash_position(&position));
+    assert!(position.remove_piece(Square::E2) == Some((QUEEN, Side::BLACK)));
+    assert!(position.remove_piece(Square::E2).is_none());
+    assert!(position.piece_at(Square::E2).is_none());
+    assert_eq!(position.zobrist_hash, ZOBRIST.hash_position(&position));
+}
+
+#[test]
+pub fn fen_without_clocks_test(){
+    let full = Position::try_from_fen("r3k2r/8/8/8/4p3/8/3P4/R3K2R w KQkq - 3 20").unwrap();
+    assert_eq!((full.halfmove_clock, full.fullmove_number), (3, 20));
+
+    let short = Position::try_from_fen("r3k2r/8/8/8/4p3/8/3P4/R3K2R w KQkq -").unwrap();
+    assert_eq!((short.halfmove_clock, short.fullmove_number), (0, 1));
+    assert!(short == full);
+    assert_eq!(short.to_fen(), "r3k2r/8/8/8/4p3/8/3P4/R3K2R w KQkq - 0 1");
+
+    let en_passant = Position::try_from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6").unwrap();
+    assert_eq!(en_passant.en_passant_square, Some(Square::D6));
+
+    assert_eq!(Position::try_from_fen("4k3/8/8/8/8/8/8/4K3 w -").err(), Some(FenError::WrongFieldCount));
+}
+
+#[test]
+pub fn fen_legality_test(){
+    let errors = [
+        ("4k3/8/8/8/8/8/8/3KK3 w - - 0 1", FenError::BadKingCount),
+        ("8/8/8/8/8/8/8/4K3 w - - 0 1", FenError::BadKingCount),
+        ("4k2k/8/8/8/8/8/8/4K3 b - - 0 1", FenError::BadKingCount),
+        ("4k3/8/8/8/8/8/8/P3K3 w - - 0 1", FenError::PawnOnBackRank),
+        ("p3k3/8/8/8/8/8/8/4K3 w - - 0 1", FenError::PawnOnBackRank),
+    ];
+    for (fen, error) in errors{
+        assert_eq!(Position::try_from_fen(fen).err(), Some(error), "{}", fen);
+    }
+
+    assert!(Position::try_from_fen("4k3/p7/8/8/8/8/P7/4K3 w - - 0 1").is_ok());
+}
+
+#[test]
+pub fn chess960_castling_test(){
+    //king on b1 with rooks on a1 and f1, castling king side leaves the rook where it is
+    let fen = "1k6/8/8/8/8/8/8/RK3R2 w FA - 0 1";
+    let position = Position::from_fen(fen);
+    assert!(position.chess960);
+    assert_eq!(position.to_fen(), fen);
+    assert!(position.can_castle(Side::WHITE, KING_SIDE));
+    assert!(position.can_castle(Side::WHITE, QUEEN_SIDE));
+
+    let king_side = Move::from_uci("b1f1", &position).unwrap();
+    let queen_side = Move::from_uci("b1a1", &position).unwrap();
+    assert_eq!(king_side.castling, Some(KING_SIDE));
+    assert_eq!(queen_side.castling, Some(QUEEN_SIDE));
+    assert_eq!(format!("{}", king_side), "O-O");
+
+    for (m, expected) in [(king_side, "1k6/8/8/8/8/8/8/R4RK1 b - - 1 1"), (queen_side, "1k6/8/8/8/8/8/8/2KR1R2 b - - 1 1")]{
+        let after = position.make_move(m).unwrap();
+        assert_eq!(after.to_fen(), expected);
+        assert_eq!(after.zobrist_hash, ZOBRIST.hash_position(&after));
+        assert_eq!(position.key_after(m), after.zobrist_hash);
+
+        let mut in_place = position;
+        let undo = in_place.make_move_mut(m).unwrap();
+        in_place.unmake_move(undo);
+        assert_eq!(in_place.to_fen(), fen);
+        assert_eq!(in_place.zobrist_hash, position.zobrist_hash);
+    }
+
+    //black castles from the same files, white's king side is cut off by the rook on f8
+    let black = Position::from_fen("rk3r2/8/8/8/8/8/8/RK3R2 b FAfa - 0 1");
+    assert!(!black.can_castle(Side::WHITE, KING_SIDE));
+    let after = black.make_move(Move::from_uci("b8a8", &black).unwrap()).unwrap();
+    assert_eq!(after.to_fen(), "2kr1r2/8/8/8/8/8/8/RK3R2 w FA - 1 2");
+
+    //the castling rook on b1 shields c1 from the rook on a1 until it moves
+    assert!(Position::from_fen("4k3/8/8/8/8/8/8/1R1K4 w B - 0 1").can_castle(Side::WHITE, QUEEN_SIDE));
+    assert!(!Position::from_fen("4k3/8/8/8/8/8/8/rR1K4 w B - 0 1").can_castle(Side::WHITE, QUEEN_SIDE));
+
+    //a rook moving off its file loses that right only
+    let after = position.make_move(Move::from_uci("f1f2", &position).unwrap()).unwrap();
+    assert_eq!(after.to_fen(), "1k6/8/8/8/8/8/5R2/RK6 b A - 1 1");
+
+    //a standard position keeps KQkq and the king's two square step
+    let standard = Position::new_game();
+    assert!(!standard.chess960);
+    assert_eq!(Position::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").legal_moves().iter().filter(|m| m.is_castle()).count(), 2);
+}
+
+#[test]
+pub fn chess960_perft_test(){
+    let position = Position::from_fen("bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9");
+    assert_eq!(position.perft(1), 21);
+    assert_eq!(position.perft(2), 528);
+    assert_eq!(position.perft(3), 12189);
+}
+
+#[test]
+pub fn game_play_move_test(){
+    let mut game = Game::new();
+    for m in ["e4", "e5", "Bc4", "Nc6", "d1h5", "Nf6"]{
+        assert_eq!(game.play_move(m), Ok(()), "{}", m);
+        assert!(game.result().is_none());
+    }
+
+    assert_eq!(game.play_move("Qxf8"), Err(MoveError::Illegal));
+    assert_eq!(game.play_move("e4e5"), Err(MoveError::Illegal));
+    assert_eq!(game.get_move_history().len(), 6);
+
+    assert_eq!(game.play_move("Qxf7#"), Ok(()));
+    assert!(game.get_position().evaluate().game_state == GameState::CHECKMATE);
+    assert_eq!(game.result(), Some(GameResult::WhiteWins));
+    assert_eq!(game.termination(), Some(Termination::Checkmate));
+    assert_eq!(game.play_move("Ke7"), Err(MoveError::GameOver));
+}
+
+#[test]
+pub fn game_over_test(){
+    let game = Game::from_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3");
+    assert!(game.is_game_over());
+    assert_eq!(game.result(), Some(GameResult::BlackWins));
+    assert_eq!(game.termination(), Some(Termination::Checkmate));
+
+    let game = Game::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1");
+    assert!(game.is_game_over());
+    assert_eq!(game.result(), Some(GameResult::Draw(DrawReason::Stalemate)));
+    assert_eq!(game.termination(), Some(Termination::Stalemate));
+
+    let mut game = Game::new();
+    assert!(!game.is_game_over());
+    assert_eq!(game.result(), None);
+    game.resign(Side::BLACK).unwrap();
+    assert!(game.is_game_over());
+    assert_eq!(game.result(), Some(GameResult::WhiteWins));
+}
+
+#[test]
+pub fn game_undo_move_test(){
+    let mut game = Game::new();
+    assert!(!game.undo_move());
+
+    for m in ["e4", "e5", "Nf3"]{
+        game.play_move(m).unwrap();
+    }
+    assert!(game.undo_move());
+    assert!(game.undo_move());
+
+    let after_e4 = Position::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1");
+    assert!(*game.get_position() == after_e4);
+    assert_eq!(game.get_position().to_fen(), after_e4.to_fen());
+    assert_eq!(game.get_move_history(), &vec!["e4".to_string()]);
+    assert_eq!(game.get_played_moves().len(), 1);
+    assert_eq!(game.get_pgn(), "1. e4 *");
+
+    //a mate taken back reopens the game
+    let mut game = Game::new();
+    for m in ["f3", "e5", "g4", "Qh4#"]{
+        game.play_move(m).unwrap();
+    }
+    assert!(game.is_game_over());
+    assert!(game.undo_move());
+    assert!(!game.is_game_over());
+    assert_eq!(game.play_move("d5"), Ok(()));
+}
+
+#[test]
+pub fn draw_reason_test(){
+    let cases = [
+        ("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1", DrawReason::Stalemate),
+        ("8/8/4k3/8/8/3K4/8/8 w - - 0 1", DrawReason::InsufficientMaterial),
+        ("8/8/4k3/8/8/3K4/8/4R3 w - - 100 80", DrawReason::FiftyMoveRule),
+    ];
+    for (fen, reason) in cases{
+        let eval = Position::from_fen(fen).evaluate();
+        assert!(eval.game_state == GameState::DRAW, "{}", fen);
+        assert_eq!(eval.draw_reason, Some(reason), "{}", fen);
+    }
+
+    //the knights shuffle back twice, the third occurrence of the start is a repetition
+    let mut position = Position::new_game();
+    let mut history = ZobristMoveStack::new();
+    for _ in 0..2{
+        for uci in ["g1f3", "g8f6", "f3g1", "f6g8"]{
+            let next = position.make_move(Move::from_uci(uci, &position).unwrap()).unwrap();
+            history.record(&position, &next);
+            position = next;
+        }
+    }
+    let eval = position.evaluate_with_history(&history);
+    assert!(eval.game_state == GameState::DRAW);
+    assert_eq!(eval.draw_reason, Some(DrawReason::ThreefoldRepetition));
+
+    assert_eq!(Position::new_game().evaluate().draw_reason, None);
+    assert_eq!(Position::from_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3").evaluate().draw_reason, None);
+}
+
+#[test]
+pub fn side_index_test(){
+    assert_eq!(Side::WHITE.index(), 0);
+    assert_eq!(Side::BLACK.index(), 1);
+    let sides: Vec<usize> = Side::iter().map(|side| side.index()).collect();
+    assert_eq!(sides, vec![0, 1]);
+}
+
+#[test]
+pub fn piece_type_test(){
+    assert_eq!(format!("{}", PieceType::from(KNIGHT)), "KNIGHT");
+    assert_eq!(format!("{}", PieceType::King), "KING");
+    for piece in PAWN..=KING{
+        assert_eq!(usize::from(PieceType::from(piece)), piece);
+    }
+    assert_eq!(PieceType::Queen as usize, QUEEN);
+}
+
+#[test]
+pub fn parse_epd_test(){
+    let epd = parse_epd("2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id \"WAC.001\";").unwrap();
+    assert_eq!(epd.id.as_deref(), Some("WAC.001"));
+    assert_eq!(epd.best_moves.len(), 1);
+    assert!(epd.avoid_moves.is_empty());
+    let best = epd.best_moves[0];
+    assert!(epd.position.legal_moves().contains(&best));
+    assert_eq!(best.get_tstring(), "g3g6");
+
+    let epd = parse_epd("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - am Nh3 Na3; bm e4 d4").unwrap();
+    assert!(epd.id.is_none());
+    assert_eq!(epd.avoid_moves.iter().map(|m| m.get_tstring()).collect::<Vec<_>>(), vec!["g1h3", "b1a3"]);
+    assert_eq!(epd.best_moves.iter().map(|m| m.get_tstring()).collect::<Vec<_>>(), vec!["e2e4", "d2d4"]);
+
+    assert!(parse_epd("8/8/8/8 w -").err() == Some(EpdError::WrongFieldCount));
+    assert!(parse_epd("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - bm Nf6;").err() == Some(EpdError::BadMove("Nf6".to_string())));
+    assert!(parse_epd("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - id \"open;").err() == Some(EpdError::UnterminatedString));
+}
+
+#[test]
+pub fn perft_parallel_test(){
+    let position = Position::new_game();
+    let expected = [20, 400, 8902, 197281, 4865609];
+    for depth in 1..=5{
+        let nodes = position.perft(depth);
+        assert_eq!(nodes, expected[depth as usize - 1]);
+        assert_eq!(position.perft_parallel(depth), nodes);
+    }
+}
+
+#[test]
+pub fn const_leaper_maps_test(){
+    for square in Square::all(){
+        assert_eq!(get_knight_attacks(square), mask_knight_attacks(square));
+        assert_eq!(get_king_attacks(square), mask_king_attacks(square));
+        for side in Side::iter(){
+            assert_eq!(get_pawn_attacks(side, square), mask_pawn_attacks(side, square));
+        }
+    }
+
+    assert_eq!(get_knight_attacks(Square::A1), Square::B3.to_bitboard() | Square::C2.to_bitboard());
+    assert_eq!(get_king_attacks(Square::H8), Square::G8.to_bitboard() | Square::G7.to_bitboard() | Square::H7.to_bitboard());
+    assert_eq!(get_pawn_attacks(Side::BLACK, Square::A7), Square::B6.to_bitboard());
+}
+
+#[test]
+pub fn squares_order_test(){
+    let squares: Vec<Square> = Squares.into_iter().collect();
+    assert_eq!(squares, (0..=63).collect::<Vec<Square>>());
+    assert_eq!(Square::all().collect::<Vec<Square>>(), squares);
+    assert!(Square::all_with_bitboards().all(|(square, bitboard)| bitboard == 1 << square));
+}
+
+#[test]
+pub fn mirror_eval_symmetry_test(){
+    let fens = [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "1k1r3r/pppqb1pp/1nn1p3/3bPp2/1P1PN3/P2BBN2/5PPP/2RQ1RK1 w - f6 0 15",
+        "r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N1PN2/PP3PPP/R2QKB1R w KQ - 0 8",
+        "r1b1k2r/ppppnppp/2n2q2/2b5/3NP3/2P1B3/PP3PPP/RN1QKB1R w KQkq - 0 7",
+        "4k3/8/8/3b4/8/8/3B4/R3K2R b KQ - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        "8/8/4k3/3p4/3P1K2/8/5R2/8 b - - 0 60",
+        "6k1/5ppp/8/8/8/8/1q3PPP/3R2K1 w - - 0 1",
+    ];
+    for fen in fens{
+        let position = Position::from_fen(fen);
+        let mirrored = position.mirror();
+        assert!(mirrored.mirror() == position, "{}", fen);
+        assert_eq!(mirrored.zobrist_hash, ZOBRIST.hash_position(&mirrored), "{}", fen);
+        assert_eq!(position.static_eval(), -mirrored.static_eval(), "{}", fen);
+    }
+
+    let mirrored = Position::from_fen("rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b Kq e3 0 3").mirror();
+    assert_eq!(mirrored.to_fen(), "rnbqkbnr/pppp1ppp/8/3Pp3/8/8/PPP1PPPP/RNBQKBNR w Qk e6 0 3");
+}
+
+//a Polyglot entry as it sits on disk, the move packs to square, from square and promotion
+fn book_entry_bytes(key: u64, from: Square, to: Square, promotion: u16, weight: u16) -> Vec<u8>{
+    let raw_move = to as u16 | (from as u16) << 6 | promotion << 12;
+    let mut bytes = key.to_be_bytes().to_vec();
+    bytes.extend_from_slice(&raw_move.to_be_bytes());
+    bytes.extend_from_slice(&weight.to_be_bytes());
+    bytes.extend_from_slice(&0u32.to_be_bytes());
+    return bytes;
+}
+
+#[test]
+pub fn polyglot_key_test(){
+    let reference = [
+        ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", 0x463b96181691fc9c),
+        ("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1", 0x823c9b50fd114196),
+        ("rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 2", 0x0756b94461c50fb0),
+        ("rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR b KQkq - 0 2", 0x662fafb965db29d4),
+        ("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3", 0x22a48b5a8e47ff78),
+        ("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPPKPPP/RNBQ1BNR b kq - 0 3", 0x652a607ca3f242c1),
+        ("rnbq1bnr/ppp1pkpp/8/3pPp2/8/8/PPPPKPPP/RNBQ1BNR w - - 0 4", 0x00fdd303c946bdd9),
+        ("rnbqkbnr/p1pppppp/8/8/PpP4P/8/1P1PPPP1/RNBQKBNR b KQkq c3 0 3", 0x3c8123ea7b067637),
+        ("rnbqkbnr/p1pppppp/8/8/P6P/R1p5/1P1PPPP1/1NBQKBNR b Kkq - 0 4", 0x5c3f9b829b279560),
+    ];
+    for (fen, key) in reference{
+        assert_eq!(polyglot_key(&Position::from_fen(fen)), key, "{}", fen);
+    }
+}
+
+#[test]
+pub fn polyglot_book_test(){
+    let start = Position::new_game();
+    let key = polyglot_key(&start);
+    let castling = Position::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+
+    let mut bytes = Vec::new();
+    bytes.extend(book_entry_bytes(key, Square::E2, Square::E4, 0, 3));
+    bytes.extend(book_entry_bytes(key, Square::D2, Square::D4, 0, 0));
+    bytes.extend(book_entry_bytes(polyglot_key(&castling), Square::E1, Square::H1, 0, 1));
+    let path = std::env::temp_dir().join(format!("siegfried-book-{}.bin", std::process::id()));
+    std::fs::write(&path, &bytes).unwrap();
+    let book = Book::open(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(book.len(), 3);
+    assert_eq!(book.entries(key).len(), 2);
+    let e4 = Move::from_uci("e2e4", &start).unwrap();
+    let mut rng = Pcg64::seed_from_u64(7);
+    for _ in 0..10{
+        assert!(book.probe_with(&start, &mut rng) == Some(e4));
+    }
+
+    //polyglot writes castling as the king taking its rook
+    assert!(book.probe(&castling) == Some(Move::castle(Side::WHITE, KING_SIDE)));
+    assert!(book.probe(&Position::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1")).is_none());
+
+    assert!(Book::from_bytes(&bytes[..20]).is_err());
+}
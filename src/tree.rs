@@ -1,6 +1,6 @@
 use std::collections::{HashMap};
 use std::ops::Mul;
-use crate::position::{Move, Position};
+use crate::position::{Move, Position, ZobristMoveStack};
 use crate::types::{GameStateConstants, Side, SideConstants, GameState};
 
 use rand::seq::SliceRandom;
@@ -34,6 +34,25 @@ fn calculate_moves_to_expand(total_moves: usize) -> usize{
     return  moves_to_expand;
 }
 
+//pull mate scores towards zero by the ply they were found at so shorter mates are preferred
+pub fn mate_distance_score(score: f32, depth: u8) -> f32{
+    return score - score.signum() * depth as f32;
+}
+
+//scores are from white's point of view, white takes the highest child and black the lowest
+pub fn best_score(side_to_move: Side, scores: &[f32]) -> Option<f32>{
+    let best = if side_to_move == Side::WHITE{
+        scores.iter().cloned().fold(f32::NEG_INFINITY, f32::max)
+    }
+    else{
+        scores.iter().cloned().fold(f32::INFINITY, f32::min)
+    };
+
+    if best.is_finite(){
+        return Some(best);
+    }
+    return None;
+}
 
 #[derive(Clone)]
 pub struct Node{
@@ -51,18 +70,25 @@ pub struct PositionTree{
     pub children: HashMap<usize, Vec<usize>>,
     pub values: HashMap<usize, Node>,
     pub depth: u8,
+    pub history: ZobristMoveStack,
 }
 
 impl PositionTree{
     pub fn new(position: Position) -> PositionTree{
+        return PositionTree::with_history(position, ZobristMoveStack::new());
+    }
+
+    //history holds the hashes of the positions played before the root
+    pub fn with_history(position: Position, history: ZobristMoveStack) -> PositionTree{
         let mut tree = PositionTree{
             root: 0,
             parent: HashMap::new(),
             children: HashMap::new(),
             values: HashMap::new(),
             depth: 0,
+            history: history.clone(),
         };
-        let eval = position.evaluate();
+        let eval = position.evaluate_with_history(&history);
         tree.values.insert(0, Node{
             parent_move: None,
             position,
@@ -112,20 +138,41 @@ impl PositionTree{
         pub score: i32,
         */
 
+    //root history followed by the positions on the line from the root to index, each line keeps its own copy
+    pub fn get_line_history(&self, index: usize) -> ZobristMoveStack{
+        let mut line = vec![index];
+        let mut current = index;
+        while let Some(parent) = self.get_parent(current){
+            line.push(parent);
+            current = parent;
+        }
+        line.reverse();
+
+        let mut history = self.history.clone();
+        for pair in line.windows(2){
+            history.record(&self.get_node(pair[0]).position, &self.get_node(pair[1]).position);
+        }
+        history.add(self.get_node(index).position.zobrist_hash);
+        return history;
+    }
+
     fn get_node_children(&self, index: usize) -> Vec<Option<Node>>{
         let node = self.get_node(index);
+        let history = self.get_line_history(index);
         node.available_moves.clone().into_par_iter().map(|m| {
             let new_position_wrapped = node.position.make_move(m);
             if new_position_wrapped.is_some(){
                 let new_position = new_position_wrapped.unwrap();
-                let eval = new_position.evaluate();
+                let eval = new_position.evaluate_with_history(&history);
+                let depth = node.depth + 1;
+                let score = if eval.game_state == GameState::CHECKMATE { eval.score.map(|s| mate_distance_score(s, depth)) } else { eval.score };
                 Some(Node{
                     parent_move: Some(m.clone()),
                     position: new_position,
                     available_moves: eval.moves,
-                    score: eval.score,
+                    score,
                     game_state: eval.game_state,
-                    depth: node.depth + 1
+                    depth
                 })
             }
             else{
@@ -170,9 +217,9 @@ impl PositionTree{
         }
         self.depth = depth;
         self.children.insert(index, child_indices);
-        //update score of index node to be the score of the average of the children
+        //the side to move picks its best child
         let mut node = self.get_node_mut(index);
-        node.score = Some(scores.par_iter().sum::<f32>() / scores.len() as f32);   
+        node.score = best_score(node.position.side_to_move, &scores);
     }
 
     fn get_nodes_to_expand(&self, index: usize) -> Vec<usize>{
@@ -226,11 +273,6 @@ impl PositionTree{
 
         while current_parents.len() > 0{
 
-            let children_total: HashMap<usize, usize> = current_parents.par_iter().map(|p| {
-                let children = self.get_children(*p).unwrap();
-                (*p, children.len())
-            }).collect();
-
             let children_scores: HashMap<usize, Vec<f32>> = current_parents.par_iter().map(|p| {
                 let children = self.get_children(*p).unwrap();
                 let scores = children.par_iter().map(|c| self.get_score(*c).unwrap()).collect::<Vec<f32>>();
@@ -240,10 +282,9 @@ impl PositionTree{
             let mut new_parents: Vec<usize> = Vec::new();
 
             for parent in current_parents{
-                let total = children_total.get(&parent).unwrap();
                 let scores = children_scores.get(&parent).unwrap();
                 let mut node = self.get_node_mut(parent);
-                node.score = Some(scores.par_iter().sum::<f32>() / *total as f32);
+                node.score = best_score(node.position.side_to_move, scores);
                 let grandparent_wrapped = &self.get_parent(parent);
                 if grandparent_wrapped.is_some(){
                     let grandparent = grandparent_wrapped.unwrap();
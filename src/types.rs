@@ -91,6 +91,103 @@ impl GameStateConstants for GameState{
     const ONGOING: GameState = GameState(3);
 }
 
+//GAME RESULT
+#[derive(PartialEq, Eq)]
+#[derive(Debug)]
+#[derive(Copy)]
+#[derive(Clone)]
+pub enum GameResult{
+    WhiteWins,
+    BlackWins,
+    Draw(DrawReason),
+}
+
+impl GameResult{
+    //result of a finished game given the final state, the side to move in it and why it's drawn if it is
+    pub fn from_game_state(game_state: &GameState, side_to_move: Side, draw_reason: Option<DrawReason>) -> Option<GameResult>{
+        if *game_state == GameState::CHECKMATE{
+            return if side_to_move == Side::WHITE { Some(GameResult::BlackWins) } else { Some(GameResult::WhiteWins) };
+        }
+        else if *game_state == GameState::DRAW{
+            return draw_reason.map(GameResult::Draw);
+        }
+        return None;
+    }
+}
+
+impl Display for GameResult {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match *self{
+            GameResult::WhiteWins => write!(f, "1-0"),
+            GameResult::BlackWins => write!(f, "0-1"),
+            GameResult::Draw(_) => write!(f, "1/2-1/2"),
+        }
+    }
+}
+
+//how a game ended
+#[derive(PartialEq, Eq)]
+#[derive(Debug)]
+#[derive(Copy)]
+#[derive(Clone)]
+pub enum Termination{
+    Checkmate,
+    Stalemate,
+    FiftyMoveRule,
+    Repetition,
+    InsufficientMaterial,
+    Resignation,
+    Timeout,
+}
+
+impl Display for Termination {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match *self{
+            Termination::Checkmate => write!(f, "Checkmate"),
+            Termination::Stalemate => write!(f, "Stalemate"),
+            Termination::FiftyMoveRule => write!(f, "Fifty-move rule"),
+            Termination::Repetition => write!(f, "Three-fold repetition"),
+            Termination::InsufficientMaterial => write!(f, "Insufficient material"),
+            Termination::Resignation => write!(f, "Resignation"),
+            Termination::Timeout => write!(f, "Timeout"),
+        }
+    }
+}
+
+//why a position is drawn
+#[derive(PartialEq, Eq)]
+#[derive(Debug)]
+#[derive(Copy)]
+#[derive(Clone)]
+pub enum DrawReason{
+    ThreefoldRepetition,
+    FiftyMoveRule,
+    InsufficientMaterial,
+    Stalemate,
+}
+
+impl Display for DrawReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match *self{
+            DrawReason::ThreefoldRepetition => write!(f, "Three-fold repetition"),
+            DrawReason::FiftyMoveRule => write!(f, "Fifty-move rule"),
+            DrawReason::InsufficientMaterial => write!(f, "Insufficient material"),
+            DrawReason::Stalemate => write!(f, "Stalemate"),
+        }
+    }
+}
+
+impl From<DrawReason> for Termination{
+    fn from(reason: DrawReason) -> Termination{
+        match reason{
+            DrawReason::ThreefoldRepetition => Termination::Repetition,
+            DrawReason::FiftyMoveRule => Termination::FiftyMoveRule,
+            DrawReason::InsufficientMaterial => Termination::InsufficientMaterial,
+            DrawReason::Stalemate => Termination::Stalemate,
+        }
+    }
+}
+
 //CASTLING SIDE
 pub const KING_SIDE : CastlingDirection = 0;
 pub const QUEEN_SIDE : CastlingDirection = 1;
@@ -105,22 +202,96 @@ pub const ROOK: Piece = 3;
 pub const QUEEN: Piece = 4;
 pub const KING: Piece = 5;
 
+//Piece is a bare usize for array indexing, PieceType is the checked form for matching and printing
+#[derive(PartialEq, Eq)]
+#[derive(Debug)]
+#[derive(Copy)]
+#[derive(Clone)]
+#[repr(usize)]
+pub enum PieceType{
+    Pawn = PAWN,
+    Knight = KNIGHT,
+    Bishop = BISHOP,
+    Rook = ROOK,
+    Queen = QUEEN,
+    King = KING,
+}
+
+impl From<Piece> for PieceType{
+    fn from(piece: Piece) -> Self{
+        match piece{
+            PAWN => PieceType::Pawn,
+            KNIGHT => PieceType::Knight,
+            BISHOP => PieceType::Bishop,
+            ROOK => PieceType::Rook,
+            QUEEN => PieceType::Queen,
+            KING => PieceType::King,
+            _ => panic!("Error: Unexpected value in Piece: {}", piece)
+        }
+    }
+}
+
+impl From<PieceType> for Piece{
+    fn from(piece_type: PieceType) -> Self{
+        return piece_type as Piece;
+    }
+}
+
+impl Display for PieceType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        return write!(f, "{}", PIECES[*self as usize]);
+    }
+}
+
 #[derive(Copy)]
 #[derive(Clone)]
 pub struct Magic{
     pub mask: Bitboard,
     pub magic: Bitboard,
-    pub attacks: [Bitboard; 4096],
+    //start of this square's 1 << shift entries in the shared attack table
+    pub offset: usize,
     pub shift: usize
 }
 
+//the 64 magics of one slider and the attack table they all index into
+pub struct MagicTable{
+    pub magics: Vec<Magic>,
+    pub attacks: Vec<Bitboard>,
+}
+
+impl MagicTable{
+    pub fn get_attacks(&self, square: Square, occupancy: Bitboard) -> Bitboard{
+        let magic = &self.magics[square as usize];
+        return self.attacks[magic.offset + magic.get_index(occupancy)];
+    }
+}
+
 pub trait MagicIndex{
     fn get_index(&self, occupied: Bitboard) -> usize;
 }
 
+impl Magic{
+    //gathers the mask bits in order, a single instruction on BMI2 cpus
+    pub fn pext_index(&self, occupancy: Bitboard) -> usize {
+        return Pext::pext(occupancy, self.mask) as usize;
+    }
+
+    //classic multiply and shift, fast everywhere but needs the precomputed magic
+    pub fn mul_index(&self, occupancy: Bitboard) -> usize {
+        return ((occupancy & self.mask).wrapping_mul(self.magic) >> (64 - self.shift)) as usize;
+    }
+}
+
+//pext by default, the magic-mul feature switches to multiply-shift for cpus without BMI2
 impl MagicIndex for Magic{
+    #[cfg(not(feature = "magic-mul"))]
     fn get_index(&self, occupancy: Bitboard) -> usize {
-        return Pext::pext(occupancy, self.mask) as usize;
+        return self.pext_index(occupancy);
+    }
+
+    #[cfg(feature = "magic-mul")]
+    fn get_index(&self, occupancy: Bitboard) -> usize {
+        return self.mul_index(occupancy);
     }
 }
 
@@ -137,10 +308,22 @@ pub trait SideConstants{
 
 pub trait SideMethods{
     fn to_char(&self) -> char;
+    fn index(&self) -> usize;
+    fn iter() -> std::array::IntoIter<Side, 2>;
 }
 
 impl SideMethods for Side{
 
+    //position of the side in per-side arrays
+    fn index(&self) -> usize{
+        return self.0;
+    }
+
+    //white then black
+    fn iter() -> std::array::IntoIter<Side, 2>{
+        return [Side::WHITE, Side::BLACK].into_iter();
+    }
+
     fn to_char(&self) -> char {
         match *self{
             Side::WHITE => 'w',
@@ -206,9 +389,35 @@ pub trait SquareMethods{
     fn get_file(&self) -> usize;
     fn from_rank_and_file(rank: usize, file: usize) -> Square;
     fn from_string(square: &str) -> Square;
+    fn try_from_string(square: &str) -> std::result::Result<Square, SquareParseError>;
     fn as_string(&self) -> String;
+    fn all() -> std::ops::Range<Square>;
+    fn all_with_bitboards() -> SquaresWithBitboards;
 }
 
+//Square is a plain u8 which already parses as a number, so square names go through try_from_string
+#[derive(PartialEq)]
+#[derive(Debug)]
+#[derive(Copy)]
+#[derive(Clone)]
+pub enum SquareParseError{
+    WrongLength,
+    BadFile,
+    BadRank,
+}
+
+impl Display for SquareParseError{
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self{
+            SquareParseError::WrongLength => write!(f, "A square is a file and a rank"),
+            SquareParseError::BadFile => write!(f, "File must be a-h"),
+            SquareParseError::BadRank => write!(f, "Rank must be 1-8"),
+        }
+    }
+}
+
+pub type SquaresWithBitboards = std::iter::Map<std::ops::Range<Square>, fn(Square) -> (Square, Bitboard)>;
+
 impl SquareMethods for Square{
     fn to_bitboard(&self) -> Bitboard{
         return 1_u64 << *self as u8;
@@ -223,11 +432,28 @@ impl SquareMethods for Square{
     fn from_rank_and_file(rank: usize, file: usize) -> Square{
         return (rank * 8 + file) as Square;
     }
+    //parse a square like e4, panicking on malformed input
     fn from_string(square: &str) -> Square {
-        let mut chars = square.chars();
-        let file = chars.next().unwrap() as usize - 'a' as usize;
-        let rank = chars.next().unwrap() as usize - '1' as usize;
-        return Square::from_rank_and_file(rank, file);
+        return match Square::try_from_string(square){
+            Ok(square) => square,
+            Err(error) => panic!("Invalid square '{}': {}", square, error)
+        };
+    }
+    //parse a square like e4, the file has to be a-h and the rank 1-8
+    fn try_from_string(square: &str) -> std::result::Result<Square, SquareParseError> {
+        let chars: Vec<char> = square.chars().collect();
+        if chars.len() != 2{
+            return Err(SquareParseError::WrongLength);
+        }
+        if !('a'..='h').contains(&chars[0]){
+            return Err(SquareParseError::BadFile);
+        }
+        if !('1'..='8').contains(&chars[1]){
+            return Err(SquareParseError::BadRank);
+        }
+        let file = chars[0] as usize - 'a' as usize;
+        let rank = chars[1] as usize - '1' as usize;
+        return Ok(Square::from_rank_and_file(rank, file));
     }
     fn as_string(&self) -> String{
         let mut string = String::new();
@@ -235,6 +461,14 @@ impl SquareMethods for Square{
         string.push((self.get_rank() + '1' as usize) as u8 as char);
         return string;
     }
+    //every square from A1 to H8
+    fn all() -> std::ops::Range<Square>{
+        return Squares.into_iter();
+    }
+    //every square from A1 to H8 paired with its single bit bitboard
+    fn all_with_bitboards() -> SquaresWithBitboards{
+        return Square::all().map(|square| (square, square.to_bitboard()));
+    }
 }
 
 impl SquareConstants for Square{
@@ -273,30 +507,15 @@ impl SquareConstants for Square{
     const NONE: Square = 64;
 }
 
+//iterates the 64 squares from A1 to H8, same as Square::all()
 pub struct Squares;
 
 impl IntoIterator for Squares{
     type Item = Square;
-    type IntoIter = std::array::IntoIter<Square, 64>;
+    type IntoIter = std::ops::Range<Square>;
+    //Square is the square's index, so the plain range is already A1 to H8
     fn into_iter(self) -> Self::IntoIter {
-        std::array::IntoIter::into_iter([
-            Square::A1, Square::B1, Square::C1, Square::D1,
-            Square::E1, Square::F1, Square::G1, Square::H1,
-            Square::A2, Square::B2, Square::C2, Square::D2,
-            Square::E2, Square::F2, Square::G2, Square::H2,
-            Square::A3, Square::B3, Square::C3, Square::D3,
-            Square::E3, Square::F3, Square::G3, Square::H3,
-            Square::A4, Square::B4, Square::C4, Square::D4,
-            Square::E4, Square::F4, Square::G4, Square::H4,
-            Square::A5, Square::B5, Square::C5, Square::D5,
-            Square::E5, Square::F5, Square::G5, Square::H5,
-            Square::A6, Square::B6, Square::C6, Square::D6,
-            Square::E6, Square::F6, Square::G6, Square::H6,
-            Square::A7, Square::B7, Square::C7, Square::D7,
-            Square::E7, Square::F7, Square::G7, Square::H7,
-            Square::A8, Square::B8, Square::C8, Square::D8,
-            Square::E8, Square::F8, Square::G8, Square::H8,
-        ].into_iter())
+        return Square::A1..Square::NONE;
     }
 }
 
@@ -0,0 +1,187 @@
+use std::{io::{BufRead, Write}, sync::{Arc, Mutex, atomic::{AtomicBool, Ordering}}, thread::JoinHandle, time::{Duration, Instant}};
+use crate::{position::{Position, Move}, search::{Searcher, SearchStats, MAX_TIMED_DEPTH, MATE_BOUND}};
+
+const ENGINE_NAME: &str = "Siegfried";
+const ENGINE_AUTHOR: &str = "skullhood";
+
+//how long a `go` may search
+#[derive(PartialEq, Eq)]
+#[derive(Debug)]
+#[derive(Copy)]
+#[derive(Clone)]
+pub enum GoLimit{
+    Depth(u8),
+    MoveTime(u64),
+    Infinite,
+}
+
+//the search running in the background, `stop` ends it early and it prints its own bestmove
+struct RunningSearch{
+    limit: GoLimit,
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+}
+
+impl RunningSearch{
+    fn finish(self){
+        self.stop.store(true, Ordering::Relaxed);
+        self.handle.join().unwrap();
+    }
+
+    //let a depth or movetime search run out, only an infinite one is stopped
+    fn wait(self){
+        if self.limit == GoLimit::Infinite{
+            self.stop.store(true, Ordering::Relaxed);
+        }
+        self.handle.join().unwrap();
+    }
+}
+
+//speak uci over stdin and stdout until `quit` or the input closes
+pub fn run_uci(){
+    let stdin = std::io::stdin();
+    run_uci_with(stdin.lock(), std::io::stdout());
+}
+
+//the protocol loop over any input and output, searches run on their own thread so `stop` and `isready` are answered
+//while thinking. gives the output back once the last search has printed its bestmove
+pub fn run_uci_with<R: BufRead, W: Write + Send + 'static>(input: R, output: W) -> W{
+    let output = Arc::new(Mutex::new(output));
+    let mut position = Position::new_game();
+    let mut search: Option<RunningSearch> = None;
+
+    for line in input.lines(){
+        let line = match line{
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+
+        match tokens.first().copied(){
+            Some("uci") => {
+                send(&output, &format!("id name {} {}", ENGINE_NAME, env!("CARGO_PKG_VERSION")));
+                send(&output, &format!("id author {}", ENGINE_AUTHOR));
+                send(&output, "uciok");
+            },
+            Some("isready") => send(&output, "readyok"),
+            Some("ucinewgame") => {
+                if let Some(running) = search.take(){
+                    running.finish();
+                }
+                position = Position::new_game();
+            },
+            Some("position") => {
+                match parse_position(&tokens[1..]){
+                    Some(parsed) => position = parsed,
+                    None => send(&output, &format!("info string invalid position: {}", line)),
+                }
+            },
+            Some("go") => {
+                if let Some(running) = search.take(){
+                    running.finish();
+                }
+                search = Some(start_search(position, parse_go(&tokens[1..]), output.clone()));
+            },
+            Some("stop") => {
+                if let Some(running) = search.take(){
+                    running.finish();
+                }
+            },
+            Some("quit") => {
+                if let Some(running) = search.take(){
+                    running.finish();
+                }
+                break;
+            },
+            _ => {},
+        }
+    }
+
+    //the input ran out with a search still going
+    if let Some(running) = search.take(){
+        running.wait();
+    }
+
+    return match Arc::try_unwrap(output){
+        Ok(output) => output.into_inner().unwrap(),
+        Err(_) => panic!("uci output still shared after the search finished"),
+    };
+}
+
+fn send<W: Write>(output: &Arc<Mutex<W>>, line: &str){
+    let mut output = output.lock().unwrap();
+    writeln!(output, "{}", line).unwrap();
+    output.flush().unwrap();
+}
+
+//`startpos` or `fen <fields>`, then an optional `moves` list played in uci notation
+pub fn parse_position(tokens: &[&str]) -> Option<Position>{
+    let moves_at = tokens.iter().position(|t| *t == "moves").unwrap_or(tokens.len());
+
+    let mut position = match tokens.first().copied(){
+        Some("startpos") => Position::new_game(),
+        Some("fen") => Position::try_from_fen(&tokens[1..moves_at].join(" ")).ok()?,
+        _ => return None,
+    };
+
+    for uci in tokens.iter().skip(moves_at + 1){
+        let m = Move::from_uci(uci, &position)?;
+        position = position.make_move(m)?;
+    }
+
+    return Some(position);
+}
+
+//`depth N`, `movetime N` or `infinite`, anything else searches until `stop`
+pub fn parse_go(tokens: &[&str]) -> GoLimit{
+    let value = |name: &str| tokens.iter().position(|t| *t == name).and_then(|i| tokens.get(i + 1)).and_then(|v| v.parse::<u64>().ok());
+
+    if let Some(depth) = value("depth"){
+        return GoLimit::Depth(depth.clamp(1, MAX_TIMED_DEPTH as u64) as u8);
+    }
+    if let Some(millis) = value("movetime"){
+        return GoLimit::MoveTime(millis);
+    }
+    return GoLimit::Infinite;
+}
+
+fn start_search<W: Write + Send + 'static>(position: Position, limit: GoLimit, output: Arc<Mutex<W>>) -> RunningSearch{
+    let stop = Arc::new(AtomicBool::new(false));
+
+    let mut searcher = Searcher::new();
+    searcher.stop = Some(stop.clone());
+    let max_depth = match limit{
+        GoLimit::Depth(depth) => depth,
+        GoLimit::MoveTime(millis) => {
+            searcher.deadline = Some(Instant::now() + Duration::from_millis(millis));
+            MAX_TIMED_DEPTH
+        },
+        GoLimit::Infinite => MAX_TIMED_DEPTH,
+    };
+
+    let handle = std::thread::spawn(move || {
+        match searcher.iterative_deepening_with(&position, max_depth, |stats, score, pv| send(&output, &info_line(stats, score, pv))){
+            Some((best_move, _, _)) => send(&output, &format!("bestmove {}", best_move.get_tstring())),
+            None => send(&output, "bestmove 0000"),
+        }
+    });
+
+    return RunningSearch{ limit, stop, handle };
+}
+
+//one line per completed depth
+pub fn info_line(stats: &SearchStats, score: f32, pv: &[Move]) -> String{
+    let pv: Vec<String> = pv.iter().map(|m| m.get_tstring()).collect();
+    return format!("info depth {} score {} nodes {} nps {} time {} pv {}",
+        stats.depth, format_score(score), stats.nodes, stats.nodes_per_second(), stats.elapsed.as_millis(), pv.join(" "));
+}
+
+//integer centipawns from the side to move's point of view, mates in full moves (negative when getting mated)
+pub fn format_score(score: f32) -> String{
+    if score.abs() >= MATE_BOUND{
+        let plies = (1000000.0 - score.abs()).round() as i32;
+        let moves = (plies + 1) / 2;
+        return format!("mate {}", if score > 0.0 { moves } else { -moves });
+    }
+    return format!("cp {}", score.round() as i32);
+}